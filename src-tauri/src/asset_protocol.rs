@@ -0,0 +1,411 @@
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use tauri::http::{header, Method, Request, Response, StatusCode};
+use tauri::AppHandle;
+
+use crate::commands::images::{
+    get_app_data_dir, is_content_addressed, percent_decode, AssetPath, ImageFormat,
+};
+use crate::error::AppError;
+
+/// URI scheme the webview loads question images from. The frontend builds URLs with
+/// `convertFileSrc(relativePath, "quizforge-asset")`, which gives
+/// `quizforge-asset://localhost/<path>` (or `http://quizforge-asset.localhost/<path>`
+/// on Windows) with the relative path percent-encoded.
+pub const ASSET_SCHEME: &str = "quizforge-asset";
+
+/// Bytes read to recognise the image format
+const SNIFF_BYTES: u64 = 1024;
+
+/// Content-addressed files never change, so the webview may keep them for a year
+const IMMUTABLE_CACHE: &str = "public, max-age=31536000, immutable";
+/// Files named before content addressing can be replaced; revalidate with the ETag
+const REVALIDATE_CACHE: &str = "no-cache";
+
+/// Answer one request for `ASSET_SCHEME`
+pub fn handle(app_handle: &AppHandle, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    match get_app_data_dir(app_handle) {
+        Ok(app_data_dir) => asset_response(&app_data_dir, request),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.message()),
+    }
+}
+
+/// Serve the image a request names from the assets directory under `app_data_dir`.
+/// Supports `GET` and `HEAD`, a single `Range` and `If-None-Match`.
+pub fn asset_response(app_data_dir: &Path, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let head = match *request.method() {
+        Method::GET => false,
+        Method::HEAD => true,
+        _ => {
+            return error_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Only GET and HEAD are supported",
+            )
+        }
+    };
+
+    let relative_path = percent_decode(request.uri().path().trim_start_matches('/'))
+        .and_then(|path| String::from_utf8(path).ok());
+    let Some(relative_path) = relative_path else {
+        return error_response(StatusCode::BAD_REQUEST, "Malformed asset path");
+    };
+    let path = match AssetPath::parse(&relative_path).and_then(|p| p.resolve(app_data_dir)) {
+        Ok(path) => path,
+        Err(AppError::InvalidAssetPath(message)) => {
+            return error_response(StatusCode::FORBIDDEN, &message)
+        }
+        Err(_) => return error_response(StatusCode::NOT_FOUND, "Image not found"),
+    };
+
+    let opened = fs::File::open(&path).and_then(|file| Ok((file.metadata()?, file)));
+    let (metadata, mut file) = match opened {
+        Ok((metadata, file)) if metadata.is_file() => (metadata, file),
+        _ => return error_response(StatusCode::NOT_FOUND, "Image not found"),
+    };
+    let size = metadata.len();
+
+    let mut start = Vec::new();
+    if file
+        .by_ref()
+        .take(SNIFF_BYTES)
+        .read_to_end(&mut start)
+        .is_err()
+    {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read image");
+    }
+    let Some(format) = ImageFormat::sniff(&start) else {
+        return error_response(StatusCode::UNSUPPORTED_MEDIA_TYPE, "Not a supported image");
+    };
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs());
+    let etag = format!("\"{:x}-{:x}\"", size, modified);
+    // Thumbnails are named after their image but are remade when the settings change
+    let content_addressed =
+        is_content_addressed(&relative_path) && !relative_path.starts_with("assets/thumbnails/");
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, format.mime_type())
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag)
+        .header(
+            header::CACHE_CONTROL,
+            if content_addressed {
+                IMMUTABLE_CACHE
+            } else {
+                REVALIDATE_CACHE
+            },
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+    if format == ImageFormat::Svg {
+        // Opened directly, an SVG is a document; keep its scripts from running
+        response = response.header(
+            header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; style-src 'unsafe-inline'; sandbox",
+        );
+    }
+
+    let if_none_match = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok());
+    let cached = if_none_match.is_some_and(|tags| {
+        tags.split(',')
+            .any(|tag| tag.trim() == etag || tag.trim() == "*")
+    });
+    if cached {
+        return finish(response.status(StatusCode::NOT_MODIFIED), Vec::new());
+    }
+
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        // Other units are ignored and the whole file is sent
+        .filter(|range| range.trim_start().starts_with("bytes="));
+    let (status, first, last) = match range.map(|range| parse_range(range, size)) {
+        None => (StatusCode::OK, 0, size.saturating_sub(1)),
+        Some(Some((first, last))) => {
+            response = response.header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", first, last, size),
+            );
+            (StatusCode::PARTIAL_CONTENT, first, last)
+        }
+        Some(None) => {
+            return finish(
+                response
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", size)),
+                Vec::new(),
+            )
+        }
+    };
+    let length = if size == 0 { 0 } else { last - first + 1 };
+    response = response.header(header::CONTENT_LENGTH, length);
+
+    if head {
+        return finish(response.status(status), Vec::new());
+    }
+
+    let mut body = Vec::with_capacity(length as usize);
+    let read = file
+        .seek(SeekFrom::Start(first))
+        .and_then(|_| file.take(length).read_to_end(&mut body));
+    if read.is_err() {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read image");
+    }
+
+    finish(response.status(status), body)
+}
+
+/// The inclusive byte range a `Range` header asks for, or `None` when it cannot be
+/// satisfied. Only single ranges are supported; for several, the first is served.
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = range
+        .trim()
+        .strip_prefix("bytes=")?
+        .split(',')
+        .next()?
+        .trim();
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+
+    let (first, last) = if first.is_empty() {
+        // The last N bytes
+        let suffix: u64 = last.parse().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        (size.saturating_sub(suffix), size.checked_sub(1)?)
+    } else {
+        let first: u64 = first.parse().ok()?;
+        let last = match last {
+            "" => size.checked_sub(1)?,
+            last => last.parse::<u64>().ok()?.min(size.checked_sub(1)?),
+        };
+        (first, last)
+    };
+
+    (first <= last && first < size).then_some((first, last))
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    finish(
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+        message.as_bytes().to_vec(),
+    )
+}
+
+fn finish(builder: tauri::http::response::Builder, body: Vec<u8>) -> Response<Vec<u8>> {
+    // The builder only fails on invalid header values, and every value here is ASCII
+    builder.body(body).unwrap_or_else(|_| {
+        let mut response = Response::new(Vec::new());
+        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        response
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0123456789";
+
+    /// An app data dir holding `assets/images/a.png` and, outside the assets
+    /// directory, `quizforge.db`
+    fn app_data_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("quizforge-assets-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("assets/images")).unwrap();
+        fs::write(dir.join("assets/images/a.png"), PNG).unwrap();
+        fs::write(dir.join("assets/images/notes.txt"), "not an image").unwrap();
+        fs::write(dir.join("quizforge.db"), "SQLite format 3").unwrap();
+        dir
+    }
+
+    fn get(dir: &Path, path: &str) -> Response<Vec<u8>> {
+        request(dir, Method::GET, path, &[])
+    }
+
+    fn request(
+        dir: &Path,
+        method: Method,
+        path: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> Response<Vec<u8>> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("quizforge-asset://localhost/{}", path));
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        asset_response(dir, &request.body(Vec::new()).unwrap())
+    }
+
+    #[test]
+    fn asset_paths_must_stay_inside_the_assets_directory() {
+        assert!(AssetPath::parse("assets/images/a.png").is_ok());
+        assert!(AssetPath::parse("assets/./images/a.png").is_ok());
+
+        for path in [
+            "",
+            "assets",
+            "assets/",
+            "images/a.png",
+            "assets/../quizforge.db",
+            "assets/images/../../quizforge.db",
+            "../assets/images/a.png",
+            "/assets/images/a.png",
+            "/etc/passwd",
+            "assets\\images\\a.png",
+            "assets/images/..\\..\\quizforge.db",
+            "assets/images/a.png\0",
+        ] {
+            match AssetPath::parse(path) {
+                Err(AppError::InvalidAssetPath(_)) => {}
+                other => panic!("{:?} gave {:?}", path, other),
+            }
+        }
+    }
+
+    #[test]
+    fn requests_outside_the_assets_directory_are_forbidden() {
+        let dir = app_data_dir("forbidden");
+
+        for path in [
+            "assets/images%2F..%2F..%2Fquizforge.db",
+            "assets/%2e%2e/quizforge.db",
+            "assets%5Cimages%5Ca.png",
+            "%2Fetc%2Fpasswd",
+            "",
+        ] {
+            assert_eq!(get(&dir, path).status(), StatusCode::FORBIDDEN, "{}", path);
+        }
+        assert_eq!(
+            get(&dir, "assets/images/a.png%").status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            get(&dir, "assets/images/%FF.png").status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_assets_directory_are_forbidden() {
+        let dir = app_data_dir("symlink");
+        std::os::unix::fs::symlink(dir.join("quizforge.db"), dir.join("assets/images/db.png"))
+            .unwrap();
+
+        assert_eq!(
+            get(&dir, "assets/images/db.png").status(),
+            StatusCode::FORBIDDEN
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn images_are_served_with_their_sniffed_type() {
+        let dir = app_data_dir("served");
+
+        let response = get(&dir, "assets/images/a.png");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], REVALIDATE_CACHE);
+        assert_eq!(response.body().as_slice(), PNG);
+
+        assert_eq!(
+            get(&dir, "assets/images/b.png").status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get(&dir, "assets/images").status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            get(&dir, "assets/images/notes.txt").status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ranges_are_clamped_to_the_file() {
+        assert_eq!(parse_range("bytes=0-", 10), Some((0, 9)));
+        assert_eq!(parse_range("bytes=2-4", 10), Some((2, 4)));
+        assert_eq!(parse_range("bytes=2-100", 10), Some((2, 9)));
+        assert_eq!(parse_range(" bytes= 9 - ", 10), Some((9, 9)));
+    }
+
+    #[test]
+    fn suffix_ranges_count_from_the_end() {
+        assert_eq!(parse_range("bytes=-4", 10), Some((6, 9)));
+        assert_eq!(parse_range("bytes=-20", 10), Some((0, 9)));
+        assert_eq!(parse_range("bytes=-0", 10), None);
+        assert_eq!(parse_range("bytes=-4", 0), None);
+    }
+
+    #[test]
+    fn ranges_outside_the_file_cannot_be_satisfied() {
+        assert_eq!(parse_range("bytes=10-", 10), None);
+        assert_eq!(parse_range("bytes=20-30", 10), None);
+        assert_eq!(parse_range("bytes=5-2", 10), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("bytes=a-b", 10), None);
+        assert_eq!(parse_range("bytes=4", 10), None);
+        assert_eq!(parse_range("items=0-4", 10), None);
+    }
+
+    #[test]
+    fn only_the_first_of_several_ranges_is_served() {
+        assert_eq!(parse_range("bytes=0-1, 4-5", 10), Some((0, 1)));
+        assert_eq!(parse_range("bytes=-2,0-1", 10), Some((8, 9)));
+        assert_eq!(parse_range("bytes=20-30, 0-1", 10), None);
+    }
+
+    #[test]
+    fn range_requests_get_partial_content() {
+        let dir = app_data_dir("ranges");
+        let path = "assets/images/a.png";
+
+        let partial = request(&dir, Method::GET, path, &[(header::RANGE, "bytes=-4")]);
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(partial.headers()[header::CONTENT_RANGE], "bytes 14-17/18");
+        assert_eq!(partial.headers()[header::CONTENT_LENGTH], "4");
+        assert_eq!(partial.body().as_slice(), b"6789");
+
+        let unsatisfiable = request(&dir, Method::GET, path, &[(header::RANGE, "bytes=18-")]);
+        assert_eq!(unsatisfiable.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(unsatisfiable.headers()[header::CONTENT_RANGE], "bytes */18");
+
+        // Units other than bytes are ignored
+        let whole = request(&dir, Method::GET, path, &[(header::RANGE, "items=0-1")]);
+        assert_eq!(whole.status(), StatusCode::OK);
+        assert_eq!(whole.body().len(), PNG.len());
+
+        let head = request(&dir, Method::HEAD, path, &[(header::RANGE, "bytes=0-3")]);
+        assert_eq!(head.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(head.headers()[header::CONTENT_LENGTH], "4");
+        assert!(head.body().is_empty());
+
+        let etag = whole.headers()[header::ETAG].to_str().unwrap().to_string();
+        let cached = request(&dir, Method::GET, path, &[(header::IF_NONE_MATCH, &etag)]);
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert!(cached.body().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
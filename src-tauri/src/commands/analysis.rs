@@ -0,0 +1,586 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::commands::questions::fetch_question_with_details;
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use crate::grading::ResponseData;
+use crate::statistics;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// Below this many responses an item is reported but not flagged; a handful of
+/// answers says little about a question
+const MIN_RESPONSES_TO_FLAG: usize = 5;
+
+/// Question types whose answers are option ids
+const CHOICE_TYPES: &[&str] = &["SINGLE_CHOICE", "MULTIPLE_CHOICE", "IMAGE_IDENTIFICATION"];
+
+/// Width of a score histogram bin, in percentage points
+const HISTOGRAM_BIN_WIDTH: f64 = 10.0;
+
+/// Which responses to analyse. All fields are optional and combine; an empty scope
+/// covers every completed quiz and exam attempt.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemAnalysisScope {
+    pub subject_id: Option<i64>,
+    pub topic_id: Option<i64>,
+    /// Only responses from attempts at this quiz
+    pub quiz_id: Option<i64>,
+    /// Only responses from attempts at this exam
+    pub exam_id: Option<i64>,
+}
+
+/// How often one option of a choice question was picked
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionAnalysis {
+    pub option_id: i64,
+    pub option_text: String,
+    pub is_correct: bool,
+    pub selections: usize,
+    /// Share of the question's responses that picked this option, 0-1
+    pub selection_rate: f64,
+    /// Mean attempt percentage of those who picked it; a distractor that strong
+    /// learners pick is worth a second look
+    pub mean_total_score: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemAnalysis {
+    pub question_id: i64,
+    pub question_text: String,
+    pub question_type: String,
+    pub responses: usize,
+    /// Facility index: mean share of the points earned, 0-1. For all-or-nothing
+    /// questions this is the proportion answering correctly.
+    pub p_value: f64,
+    /// Point-biserial correlation between the item score and the attempt percentage;
+    /// `None` when either does not vary
+    pub discrimination: Option<f64>,
+    /// Choice questions only, in display order. Answers naming options that have
+    /// since been removed are not counted.
+    pub options: Vec<OptionAnalysis>,
+    /// `NEGATIVE_DISCRIMINATION` and/or `UNUSED_OPTION`
+    pub flags: Vec<String>,
+}
+
+/// One stored answer, with the score of the attempt it belongs to
+struct ItemResponse {
+    answer: Option<ResponseData>,
+    item_score: f64,
+    total_score: f64,
+}
+
+/// Classical item statistics for every question answered in completed attempts
+/// within `scope`, ordered by question id
+#[tauri::command]
+pub fn get_item_analysis(
+    db: State<DbConnection>,
+    scope: ItemAnalysisScope,
+) -> AppResult<Vec<ItemAnalysis>> {
+    if scope.quiz_id.is_some() && scope.exam_id.is_some() {
+        return Err(AppError::validation(
+            "Choose a quiz or an exam, not both",
+            &["quizId", "examId"],
+        ));
+    }
+
+    let conn = db.0.lock()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT r.question_id, r.response_data, r.is_correct, r.points_earned, q.points, a.percentage
+         FROM attempt_responses r
+         JOIN quiz_attempts a ON a.id = r.attempt_id
+         JOIN questions q ON q.id = r.question_id
+         WHERE a.completed_at IS NOT NULL AND a.percentage IS NOT NULL
+           AND (?1 IS NULL OR q.subject_id = ?1) AND (?2 IS NULL OR q.topic_id = ?2)
+           AND (?3 IS NULL OR a.quiz_id = ?3) AND ?4 IS NULL
+         UNION ALL
+         SELECT r.question_id, r.response_data, r.is_correct, r.points_earned, q.points, a.percentage
+         FROM exam_responses r
+         JOIN exam_attempts a ON a.id = r.attempt_id
+         JOIN questions q ON q.id = r.question_id
+         WHERE a.completed_at IS NOT NULL AND a.percentage IS NOT NULL
+           AND (?1 IS NULL OR q.subject_id = ?1) AND (?2 IS NULL OR q.topic_id = ?2)
+           AND ?3 IS NULL AND (?4 IS NULL OR a.exam_id = ?4)",
+    )?;
+    let rows = stmt
+        .query_map(
+            (
+                scope.subject_id,
+                scope.topic_id,
+                scope.quiz_id,
+                scope.exam_id,
+            ),
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<bool>>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                    row.get::<_, i32>(4)?,
+                    row.get::<_, f64>(5)?,
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut by_question: BTreeMap<i64, Vec<ItemResponse>> = BTreeMap::new();
+    for (question_id, response_data, is_correct, points_earned, points, percentage) in rows {
+        // Partial credit counts as such; questions worth no points are right or wrong
+        let item_score = if points > 0 {
+            (points_earned.unwrap_or(0.0) / points as f64).clamp(0.0, 1.0)
+        } else if is_correct.unwrap_or(false) {
+            1.0
+        } else {
+            0.0
+        };
+        by_question
+            .entry(question_id)
+            .or_default()
+            .push(ItemResponse {
+                answer: serde_json::from_str(&response_data)?,
+                item_score,
+                total_score: percentage,
+            });
+    }
+
+    by_question
+        .into_iter()
+        .map(|(question_id, responses)| {
+            let question = fetch_question_with_details(&conn, question_id)?;
+            let item_scores: Vec<f64> = responses.iter().map(|r| r.item_score).collect();
+            let total_scores: Vec<f64> = responses.iter().map(|r| r.total_score).collect();
+
+            let options = if CHOICE_TYPES.contains(&question.question.question_type.as_str()) {
+                let selections: Vec<HashSet<i64>> = responses
+                    .iter()
+                    .map(|r| {
+                        r.answer
+                            .as_ref()
+                            .map(|answer| answer.ids().into_iter().collect())
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                question
+                    .options
+                    .iter()
+                    .map(|option| {
+                        let totals: Vec<f64> = selections
+                            .iter()
+                            .zip(&responses)
+                            .filter(|(selected, _)| selected.contains(&option.id))
+                            .map(|(_, response)| response.total_score)
+                            .collect();
+                        OptionAnalysis {
+                            option_id: option.id,
+                            option_text: option.option_text.clone(),
+                            is_correct: option.is_correct,
+                            selections: totals.len(),
+                            selection_rate: totals.len() as f64 / responses.len() as f64,
+                            mean_total_score: statistics::mean(&totals),
+                        }
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            let discrimination = statistics::correlation(&item_scores, &total_scores);
+
+            let mut flags = Vec::new();
+            if responses.len() >= MIN_RESPONSES_TO_FLAG {
+                if discrimination.is_some_and(|d| d < 0.0) {
+                    flags.push("NEGATIVE_DISCRIMINATION".to_string());
+                }
+                if options.iter().any(|option| option.selections == 0) {
+                    flags.push("UNUSED_OPTION".to_string());
+                }
+            }
+
+            Ok(ItemAnalysis {
+                question_id,
+                question_text: question.question.question_text,
+                question_type: question.question.question_type,
+                responses: responses.len(),
+                p_value: statistics::mean(&item_scores).unwrap_or(0.0),
+                discrimination,
+                options,
+                flags,
+            })
+        })
+        .collect()
+}
+
+/// Which attempts `get_test_statistics` covers. Dates are `YYYY-MM-DD` (UTC, as
+/// attempts are stored) and inclusive; attempts are dated by when they were completed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestStatisticsFilter {
+    pub quiz_id: Option<i64>,
+    pub exam_id: Option<i64>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreBin {
+    pub from: f64,
+    pub to: f64,
+    pub count: usize,
+}
+
+/// Score distribution and reliability of one quiz or exam. Scores are attempt
+/// percentages.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestStatistics {
+    /// `QUIZ` or `EXAM`
+    pub test_type: String,
+    pub test_id: i64,
+    pub name: String,
+    pub attempts: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    /// Bins of `HISTOGRAM_BIN_WIDTH` points from 0 to 100; the last includes 100
+    pub histogram: Vec<ScoreBin>,
+    /// KR-20 when every item was scored all or nothing, Cronbach's alpha otherwise
+    pub reliability: Option<f64>,
+    /// `KR20` or `CRONBACH_ALPHA`
+    pub reliability_method: Option<String>,
+    /// Questions the reliability is computed over: those answered in every attempt.
+    /// Quizzes that draw questions at random may share few or none.
+    pub reliability_items: usize,
+    /// Standard error of measurement in percentage points of the score on the
+    /// `reliability_items`
+    pub standard_error: Option<f64>,
+}
+
+/// One completed attempt and its per-question points
+struct TestAttempt {
+    percentage: f64,
+    /// question id -> (points earned, points available)
+    items: HashMap<i64, (f64, f64)>,
+}
+
+/// Score distribution and reliability of each quiz and exam with completed attempts
+/// matching `filter`, exams first, each ordered by id
+#[tauri::command]
+pub fn get_test_statistics(
+    db: State<DbConnection>,
+    filter: TestStatisticsFilter,
+) -> AppResult<Vec<TestStatistics>> {
+    if filter.quiz_id.is_some() && filter.exam_id.is_some() {
+        return Err(AppError::validation(
+            "Choose a quiz or an exam, not both",
+            &["quizId", "examId"],
+        ));
+    }
+
+    let conn = db.0.lock()?;
+
+    for (field, date) in [("from", &filter.from), ("to", &filter.to)] {
+        if let Some(date) = date {
+            let valid: bool = conn.query_row("SELECT date(?1) IS ?1", [date], |row| row.get(0))?;
+            if !valid {
+                return Err(AppError::validation(
+                    format!("Dates must be given as YYYY-MM-DD, not {}", date),
+                    &[field],
+                ));
+            }
+        }
+    }
+
+    let params = (filter.quiz_id, filter.exam_id, &filter.from, &filter.to);
+    let mut stmt = conn.prepare(
+        "SELECT 'QUIZ', t.id, t.name, a.id, a.percentage
+         FROM quiz_attempts a JOIN quizzes t ON t.id = a.quiz_id
+         WHERE a.completed_at IS NOT NULL AND a.percentage IS NOT NULL
+           AND (?1 IS NULL OR t.id = ?1) AND ?2 IS NULL
+           AND (?3 IS NULL OR date(a.completed_at) >= ?3)
+           AND (?4 IS NULL OR date(a.completed_at) <= ?4)
+         UNION ALL
+         SELECT 'EXAM', t.id, t.name, a.id, a.percentage
+         FROM exam_attempts a JOIN exams t ON t.id = a.exam_id
+         WHERE a.completed_at IS NOT NULL AND a.percentage IS NOT NULL
+           AND ?1 IS NULL AND (?2 IS NULL OR t.id = ?2)
+           AND (?3 IS NULL OR date(a.completed_at) >= ?3)
+           AND (?4 IS NULL OR date(a.completed_at) <= ?4)",
+    )?;
+    let attempts = stmt
+        .query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, f64>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT 'QUIZ', r.attempt_id, r.question_id, r.points_earned, q.points
+         FROM attempt_responses r
+         JOIN quiz_attempts a ON a.id = r.attempt_id
+         JOIN questions q ON q.id = r.question_id
+         WHERE a.completed_at IS NOT NULL
+           AND (?1 IS NULL OR a.quiz_id = ?1) AND ?2 IS NULL
+           AND (?3 IS NULL OR date(a.completed_at) >= ?3)
+           AND (?4 IS NULL OR date(a.completed_at) <= ?4)
+         UNION ALL
+         SELECT 'EXAM', r.attempt_id, r.question_id, r.points_earned, q.points
+         FROM exam_responses r
+         JOIN exam_attempts a ON a.id = r.attempt_id
+         JOIN questions q ON q.id = r.question_id
+         WHERE a.completed_at IS NOT NULL
+           AND ?1 IS NULL AND (?2 IS NULL OR a.exam_id = ?2)
+           AND (?3 IS NULL OR date(a.completed_at) >= ?3)
+           AND (?4 IS NULL OR date(a.completed_at) <= ?4)",
+    )?;
+    let mut items: HashMap<(String, i64), HashMap<i64, (f64, f64)>> = HashMap::new();
+    let rows = stmt.query_map(params, |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, Option<f64>>(3)?,
+            row.get::<_, f64>(4)?,
+        ))
+    })?;
+    for row in rows {
+        let (test_type, attempt_id, question_id, points_earned, points) = row?;
+        items
+            .entry((test_type, attempt_id))
+            .or_default()
+            .insert(question_id, (points_earned.unwrap_or(0.0), points));
+    }
+
+    let mut tests: BTreeMap<(String, i64), (String, Vec<TestAttempt>)> = BTreeMap::new();
+    for (test_type, test_id, name, attempt_id, percentage) in attempts {
+        let attempt = TestAttempt {
+            percentage,
+            items: items
+                .remove(&(test_type.clone(), attempt_id))
+                .unwrap_or_default(),
+        };
+        tests
+            .entry((test_type, test_id))
+            .or_insert_with(|| (name, Vec::new()))
+            .1
+            .push(attempt);
+    }
+
+    Ok(tests
+        .into_iter()
+        .map(|((test_type, test_id), (name, attempts))| {
+            test_statistics(test_type, test_id, name, &attempts)
+        })
+        .collect())
+}
+
+fn test_statistics(
+    test_type: String,
+    test_id: i64,
+    name: String,
+    attempts: &[TestAttempt],
+) -> TestStatistics {
+    let scores: Vec<f64> = attempts.iter().map(|attempt| attempt.percentage).collect();
+
+    let bins = (100.0 / HISTOGRAM_BIN_WIDTH) as usize;
+    let mut histogram: Vec<ScoreBin> = (0..bins)
+        .map(|bin| ScoreBin {
+            from: bin as f64 * HISTOGRAM_BIN_WIDTH,
+            to: (bin + 1) as f64 * HISTOGRAM_BIN_WIDTH,
+            count: 0,
+        })
+        .collect();
+    for score in &scores {
+        let bin = (score.clamp(0.0, 100.0) / HISTOGRAM_BIN_WIDTH) as usize;
+        histogram[bin.min(bins - 1)].count += 1;
+    }
+
+    // Reliability needs the same items for everyone
+    let mut common: BTreeSet<i64> = attempts
+        .first()
+        .map(|attempt| attempt.items.keys().copied().collect())
+        .unwrap_or_default();
+    for attempt in attempts {
+        common.retain(|question_id| attempt.items.contains_key(question_id));
+    }
+    let matrix: Vec<Vec<f64>> = attempts
+        .iter()
+        .map(|attempt| common.iter().map(|id| attempt.items[id].0).collect())
+        .collect();
+    let dichotomous = attempts.iter().all(|attempt| {
+        common.iter().all(|id| {
+            let (earned, points) = attempt.items[id];
+            earned == 0.0 || earned == points
+        })
+    });
+
+    // The error of measurement belongs to the same items as the reliability, so it is
+    // scaled by the spread of the scores on those items, not of the whole attempt
+    let common_scores: Vec<f64> = attempts
+        .iter()
+        .map(|attempt| {
+            let (earned, points) = common.iter().fold((0.0, 0.0), |(earned, points), id| {
+                let item = attempt.items[id];
+                (earned + item.0, points + item.1)
+            });
+            if points > 0.0 {
+                earned / points * 100.0
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    let std_dev = statistics::std_dev(&scores).unwrap_or(0.0);
+    let reliability = statistics::cronbach_alpha(&matrix);
+    TestStatistics {
+        test_type,
+        test_id,
+        name,
+        attempts: attempts.len(),
+        mean: statistics::mean(&scores).unwrap_or(0.0),
+        median: statistics::median(&scores).unwrap_or(0.0),
+        std_dev,
+        histogram,
+        reliability,
+        reliability_method: reliability.map(|_| {
+            if dichotomous {
+                "KR20"
+            } else {
+                "CRONBACH_ALPHA"
+            }
+            .to_string()
+        }),
+        reliability_items: common.len(),
+        // A negative coefficient means no measurable consistency at all
+        standard_error: reliability
+            .zip(statistics::std_dev(&common_scores))
+            .map(|(reliability, std_dev)| std_dev * (1.0 - reliability.clamp(0.0, 1.0)).sqrt()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An attempt at items worth `points` each, scored `earned`, by question id from 1
+    fn attempt(earned: &[f64], points: f64) -> TestAttempt {
+        let total: f64 = earned.iter().sum();
+        TestAttempt {
+            percentage: total / (points * earned.len() as f64) * 100.0,
+            items: earned
+                .iter()
+                .enumerate()
+                .map(|(index, earned)| (index as i64 + 1, (*earned, points)))
+                .collect(),
+        }
+    }
+
+    fn statistics_of(attempts: &[TestAttempt]) -> TestStatistics {
+        test_statistics("QUIZ".to_string(), 1, "Quiz".to_string(), attempts)
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("a value");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn kr20_and_standard_error_of_a_dichotomous_test() {
+        let attempts: Vec<_> = [
+            [1.0, 1.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+        ]
+        .iter()
+        .map(|row| attempt(row, 1.0))
+        .collect();
+        let stats = statistics_of(&attempts);
+
+        assert_eq!(stats.reliability_method.as_deref(), Some("KR20"));
+        assert_eq!(stats.reliability_items, 4);
+        assert_close(stats.reliability, 0.8);
+        // Scores 100, 75, 50, 25, 0 have variance 1250: sqrt(1250 * (1 - 0.8))
+        assert_close(Some(stats.std_dev), 1250.0_f64.sqrt());
+        assert_close(stats.standard_error, 250.0_f64.sqrt());
+        assert_close(Some(stats.mean), 50.0);
+        let counts: Vec<_> = stats.histogram.iter().map(|bin| bin.count).collect();
+        assert_eq!(counts, [1, 0, 1, 0, 0, 1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn partial_credit_is_reported_as_alpha() {
+        let attempts = [
+            attempt(&[2.0, 1.0], 2.0),
+            attempt(&[1.0, 1.0], 2.0),
+            attempt(&[0.0, 0.0], 2.0),
+        ];
+        let stats = statistics_of(&attempts);
+
+        assert_eq!(stats.reliability_method.as_deref(), Some("CRONBACH_ALPHA"));
+        assert_close(stats.reliability, 6.0 / 7.0);
+    }
+
+    #[test]
+    fn reliability_uses_only_the_questions_every_attempt_answered() {
+        let mut attempts: Vec<_> = [[1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]
+            .iter()
+            .map(|row| attempt(row, 1.0))
+            .collect();
+        // A question only the first attempt drew, answered wrongly
+        attempts[0].items.insert(3, (0.0, 1.0));
+        let stats = statistics_of(&attempts);
+
+        assert_eq!(stats.reliability_items, 2);
+        // Item variances 2/9 each, totals 2, 1, 0 with variance 2/3: 2 * (1 - 2/3)
+        assert_close(stats.reliability, 2.0 / 3.0);
+        // Scores on the two common items are 100, 50 and 0
+        assert_close(stats.standard_error, (5000.0_f64 / 3.0 / 3.0).sqrt());
+    }
+
+    #[test]
+    fn negative_reliability_gives_the_full_spread_as_the_error() {
+        let attempts = [
+            attempt(&[1.0, 0.0], 1.0),
+            attempt(&[0.0, 1.0], 1.0),
+            attempt(&[1.0, 1.0], 1.0),
+        ];
+        let stats = statistics_of(&attempts);
+
+        assert_close(stats.reliability, -2.0);
+        assert_close(stats.standard_error, stats.std_dev);
+    }
+
+    #[test]
+    fn no_reliability_without_spread_or_with_one_item() {
+        let same_total = statistics_of(&[attempt(&[1.0, 0.0], 1.0), attempt(&[0.0, 1.0], 1.0)]);
+        assert_eq!(same_total.reliability, None);
+        assert_eq!(same_total.reliability_method, None);
+        assert_eq!(same_total.standard_error, None);
+
+        let one_item = statistics_of(&[attempt(&[1.0], 1.0), attempt(&[0.0], 1.0)]);
+        assert_eq!(one_item.reliability_items, 1);
+        assert_eq!(one_item.reliability, None);
+        assert_eq!(one_item.standard_error, None);
+
+        let single_attempt = statistics_of(&[attempt(&[1.0, 0.0, 1.0], 1.0)]);
+        assert_eq!(single_attempt.reliability, None);
+        assert_close(Some(single_attempt.std_dev), 0.0);
+    }
+}
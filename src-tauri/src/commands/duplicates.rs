@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+
+use crate::commands::library::remap_response;
+use crate::commands::questions::{fetch_question_with_details, QuestionWithDetails};
+use crate::db::{with_transaction, DbConnection};
+use crate::error::{AppError, AppResult};
+use crate::similarity;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+/// Similarity at which questions are reported as duplicates when no threshold is given
+const DEFAULT_THRESHOLD: f64 = 0.8;
+
+/// Where to look for duplicates. Both may be given to search one topic of a subject.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateScope {
+    pub topic_id: Option<i64>,
+    pub subject_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatePair {
+    pub question_id: i64,
+    pub other_question_id: i64,
+    pub similarity: f64,
+}
+
+/// Questions that are near-duplicates of each other, oldest first. `pairs` lists the
+/// similarities that link them; two questions in a cluster need not be similar directly.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateCluster {
+    pub questions: Vec<QuestionWithDetails>,
+    pub pairs: Vec<DuplicatePair>,
+    pub max_similarity: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeResult {
+    pub question: QuestionWithDetails,
+    pub moved_responses: usize,
+    pub removed_questions: usize,
+}
+
+/// Clusters of near-duplicate questions in a topic or subject, most similar first.
+/// Similarity compares the wording of the question and its answers, ignoring case,
+/// punctuation and option order; `threshold` is between 0 and 1.
+#[tauri::command]
+pub fn find_duplicate_questions(
+    db: State<DbConnection>,
+    scope: DuplicateScope,
+    threshold: Option<f64>,
+) -> AppResult<Vec<DuplicateCluster>> {
+    let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD);
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        return Err(AppError::validation(
+            "Threshold must be greater than 0 and at most 1",
+            &["threshold"],
+        ));
+    }
+    if scope.topic_id.is_none() && scope.subject_id.is_none() {
+        return Err(AppError::validation(
+            "Choose a topic or a subject to search",
+            &["topicId", "subjectId"],
+        ));
+    }
+
+    let conn = db.0.lock()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id FROM questions
+             WHERE (?1 IS NULL OR topic_id = ?1) AND (?2 IS NULL OR subject_id = ?2)
+             ORDER BY created_at ASC, id ASC",
+    )?;
+    let questions = stmt
+        .query_map((scope.topic_id, scope.subject_id), |row| {
+            row.get::<_, i64>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .map(|id| fetch_question_with_details(&conn, id))
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let fingerprints: Vec<_> = questions.iter().map(similarity::fingerprint).collect();
+    let pairs = similarity::similar_pairs(&fingerprints, threshold);
+
+    let mut clusters: Vec<DuplicateCluster> = similarity::clusters(questions.len(), &pairs)
+        .into_iter()
+        .map(|members| {
+            let pairs: Vec<DuplicatePair> = pairs
+                .iter()
+                .filter(|(a, _, _)| members.contains(a))
+                .map(|(a, b, similarity)| DuplicatePair {
+                    question_id: questions[*a].question.id,
+                    other_question_id: questions[*b].question.id,
+                    similarity: *similarity,
+                })
+                .collect();
+            DuplicateCluster {
+                questions: members
+                    .iter()
+                    .map(|index| questions[*index].clone())
+                    .collect(),
+                max_similarity: pairs.iter().map(|pair| pair.similarity).fold(0.0, f64::max),
+                pairs,
+            }
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.max_similarity.total_cmp(&a.max_similarity));
+
+    Ok(clusters)
+}
+
+/// Fold duplicates into the question being kept. Quiz and exam responses to the
+/// merged questions move to `keep_id`, with their chosen options, order items and
+/// matches pointed at the kept question's by text; tags are combined. An attempt that
+/// was served the kept question as well keeps only its answer to that one. The merged
+/// questions are then deleted. All of them must have the kept question's type.
+#[tauri::command]
+pub fn merge_questions(
+    db: State<DbConnection>,
+    keep_id: i64,
+    mut merge_ids: Vec<i64>,
+) -> AppResult<MergeResult> {
+    merge_ids.sort_unstable();
+    merge_ids.dedup();
+
+    if merge_ids.is_empty() {
+        return Err(AppError::validation(
+            "Choose questions to merge",
+            &["mergeIds"],
+        ));
+    }
+    if merge_ids.contains(&keep_id) {
+        return Err(AppError::validation(
+            "The question being kept cannot also be merged",
+            &["mergeIds"],
+        ));
+    }
+
+    let mut conn = db.0.lock()?;
+
+    // Enable foreign keys for cascade deletes
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+
+    with_transaction(&mut conn, |tx| merge_into(tx, keep_id, &merge_ids))
+}
+
+/// The body of `merge_questions`, inside its transaction
+fn merge_into(
+    tx: &rusqlite::Connection,
+    keep_id: i64,
+    merge_ids: &[i64],
+) -> AppResult<MergeResult> {
+    let keep = fetch_question_with_details(tx, keep_id)?;
+    let mut moved_responses = 0;
+    let mut removed_questions = 0;
+
+    for merge_id in merge_ids.iter().copied() {
+        let merged = fetch_question_with_details(tx, merge_id)?;
+        // Responses are kept as answered, which only means something for the same type
+        if merged.question.question_type != keep.question.question_type {
+            return Err(AppError::validation(
+                format!(
+                    "Question {} is {} and cannot be merged into a {} question",
+                    merge_id, merged.question.question_type, keep.question.question_type
+                ),
+                &["mergeIds"],
+            ));
+        }
+        let ids = matching_ids(&merged, &keep);
+        let question_type = merged.question.question_type.as_str();
+
+        for table in ["attempt_responses", "exam_responses"] {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT id, response_data FROM {0}
+                 WHERE question_id = ?1
+                   AND attempt_id NOT IN (SELECT attempt_id FROM {0} WHERE question_id = ?2)",
+                table
+            ))?;
+            let responses = stmt
+                .query_map([merge_id, keep_id], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (id, data) in responses {
+                let data = data.map(|data| remap(&data, question_type, false, &ids));
+                // The stored revision belonged to the merged question
+                tx.execute(
+                    &format!(
+                        "UPDATE {} SET question_id = ?1, response_data = ?2, revision = NULL
+                         WHERE id = ?3",
+                        table
+                    ),
+                    (keep_id, data, id),
+                )?;
+                moved_responses += 1;
+            }
+        }
+
+        for table in ["quiz_attempt_questions", "exam_attempt_questions"] {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT id, option_order FROM {0}
+                 WHERE question_id = ?1
+                   AND attempt_id NOT IN (SELECT attempt_id FROM {0} WHERE question_id = ?2)",
+                table
+            ))?;
+            let served = stmt
+                .query_map([merge_id, keep_id], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (id, order) in served {
+                let order = order.map(|order| remap(&order, question_type, true, &ids));
+                tx.execute(
+                    &format!(
+                        "UPDATE {} SET question_id = ?1, option_order = ?2 WHERE id = ?3",
+                        table
+                    ),
+                    (keep_id, order, id),
+                )?;
+            }
+        }
+
+        // An attempt that was served both questions keeps its rows for the kept one,
+        // so the question is neither served nor scored twice
+        for table in [
+            "attempt_responses",
+            "exam_responses",
+            "quiz_attempt_questions",
+            "exam_attempt_questions",
+        ] {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE question_id = ?", table),
+                [merge_id],
+            )?;
+        }
+
+        tx.execute(
+            "INSERT OR IGNORE INTO question_tags (question_id, tag_id)
+             SELECT ?1, tag_id FROM question_tags WHERE question_id = ?2",
+            (keep_id, merge_id),
+        )?;
+        tx.execute("DELETE FROM questions WHERE id = ?", [merge_id])?;
+        removed_questions += 1;
+    }
+
+    Ok(MergeResult {
+        question: fetch_question_with_details(tx, keep_id)?,
+        moved_responses,
+        removed_questions,
+    })
+}
+
+/// Option, order item and match ids of `from` mapped to the ids of the items in `to`
+/// with the same normalised text. Items without a counterpart are left unmapped.
+fn matching_ids(
+    from: &QuestionWithDetails,
+    to: &QuestionWithDetails,
+) -> HashMap<&'static str, HashMap<i64, i64>> {
+    fn by_text<'a>(
+        from: impl Iterator<Item = (i64, &'a str)>,
+        to: impl Iterator<Item = (i64, &'a str)>,
+    ) -> HashMap<i64, i64> {
+        let targets: HashMap<String, i64> = to
+            .map(|(id, text)| (similarity::normalize(text), id))
+            .collect();
+        from.filter_map(|(id, text)| {
+            targets
+                .get(&similarity::normalize(text))
+                .map(|target| (id, *target))
+        })
+        .collect()
+    }
+
+    let mut ids = HashMap::new();
+    ids.insert(
+        "question_options",
+        by_text(
+            from.options.iter().map(|o| (o.id, o.option_text.as_str())),
+            to.options.iter().map(|o| (o.id, o.option_text.as_str())),
+        ),
+    );
+    ids.insert(
+        "question_order_items",
+        by_text(
+            from.order_items
+                .iter()
+                .map(|i| (i.id, i.item_text.as_str())),
+            to.order_items.iter().map(|i| (i.id, i.item_text.as_str())),
+        ),
+    );
+    ids.insert(
+        "question_matches",
+        by_text(
+            from.matches.iter().map(|m| (m.id, m.left_item.as_str())),
+            to.matches.iter().map(|m| (m.id, m.left_item.as_str())),
+        ),
+    );
+    ids
+}
+
+fn remap(
+    text: &str,
+    question_type: &str,
+    option_order: bool,
+    ids: &HashMap<&str, HashMap<i64, i64>>,
+) -> String {
+    match remap_response(&Value::from(text), question_type, option_order, ids) {
+        Value::String(text) => text,
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::commands::questions::insert_question;
+    use crate::db::test_connection;
+
+    fn capital_question(conn: &rusqlite::Connection) -> i64 {
+        insert_question(
+            conn,
+            &serde_json::from_value(json!({
+                "subjectId": 1, "topicId": 1, "questionType": "SINGLE_CHOICE",
+                "questionText": "What is the capital of France?", "difficulty": "EASY", "points": 1,
+                "options": [
+                    { "optionText": "Paris", "isCorrect": true, "displayOrder": 0 },
+                    { "optionText": "Lyon", "isCorrect": false, "displayOrder": 1 },
+                ],
+                "blanks": [],
+            }))
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn count(conn: &rusqlite::Connection, sql: &str, attempt_id: i64) -> i64 {
+        conn.query_row(sql, [attempt_id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn attempt_served_both_questions_keeps_one_row_each() {
+        let conn = test_connection();
+        conn.execute_batch(
+            "INSERT INTO subjects (name) VALUES ('S');
+             INSERT INTO topics (subject_id, name) VALUES (1, 'T');
+             INSERT INTO quizzes (topic_id, name, question_count) VALUES (1, 'Q', 2);
+             INSERT INTO quiz_attempts (quiz_id, completed_at) VALUES (1, datetime('now'));
+             INSERT INTO quiz_attempts (quiz_id, completed_at) VALUES (1, datetime('now'));",
+        )
+        .unwrap();
+        let keep_id = capital_question(&conn);
+        let merge_id = capital_question(&conn);
+
+        // Attempt 1 was served both questions, attempt 2 only the duplicate
+        for (attempt_id, question_id, position) in
+            [(1, keep_id, 0), (1, merge_id, 1), (2, merge_id, 0)]
+        {
+            conn.execute(
+                "INSERT INTO quiz_attempt_questions (attempt_id, question_id, position)
+                 VALUES (?1, ?2, ?3)",
+                (attempt_id, question_id, position),
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO attempt_responses (attempt_id, question_id, response_data, points_earned)
+                 VALUES (?1, ?2, 'null', ?3)",
+                (attempt_id, question_id, position),
+            )
+            .unwrap();
+        }
+
+        let result = merge_into(&conn, keep_id, &[merge_id]).unwrap();
+        assert_eq!((result.moved_responses, result.removed_questions), (1, 1));
+
+        for attempt_id in [1, 2] {
+            let served = "SELECT COUNT(*) FROM quiz_attempt_questions WHERE attempt_id = ?";
+            let answered = "SELECT COUNT(*) FROM attempt_responses WHERE attempt_id = ?";
+            assert_eq!(
+                count(&conn, served, attempt_id),
+                1,
+                "attempt {}",
+                attempt_id
+            );
+            assert_eq!(
+                count(&conn, answered, attempt_id),
+                1,
+                "attempt {}",
+                attempt_id
+            );
+        }
+        // Attempt 1 keeps its answer to the kept question, not the duplicate's
+        let points = "SELECT points_earned FROM attempt_responses WHERE attempt_id = ?";
+        assert_eq!(count(&conn, points, 1), 0);
+        let question = "SELECT question_id FROM attempt_responses WHERE attempt_id = ?";
+        assert_eq!(count(&conn, question, 2), keep_id);
+    }
+
+    #[test]
+    fn questions_of_another_type_are_not_merged() {
+        let conn = test_connection();
+        conn.execute_batch(
+            "INSERT INTO subjects (name) VALUES ('S');
+             INSERT INTO topics (subject_id, name) VALUES (1, 'T');",
+        )
+        .unwrap();
+        let keep_id = capital_question(&conn);
+        let other_id = insert_question(
+            &conn,
+            &serde_json::from_value(json!({
+                "subjectId": 1, "topicId": 1, "questionType": "FILL_BLANK",
+                "questionText": "The capital of France is ___", "difficulty": "EASY", "points": 1,
+                "options": [],
+                "blanks": [{ "blankIndex": 0, "correctAnswer": "Paris", "isNumeric": false, "inputType": "INPUT" }],
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        match merge_into(&conn, keep_id, &[other_id]) {
+            Err(AppError::Validation { fields, .. }) => assert_eq!(fields, ["mergeIds"]),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+}
@@ -1,6 +1,18 @@
+use std::collections::hash_map::{Entry, HashMap};
+
+use crate::commands::questions::{fetch_question_with_details, QuestionWithDetails};
+use crate::commands::quizzes::{served_question_ids, served_questions, store_served_questions};
+use crate::commands::revisions::{answered_question, current_revision};
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use crate::grading::{
+    grade_served, AttemptResult, ResponseData, ResponseDetail, SubmittedResponse,
+};
+use crate::random::{fresh_seed, SeededRng};
+use crate::scheduling::record_responses;
+use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use crate::db::DbConnection;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -94,17 +106,15 @@ pub struct UpdateExamData {
 }
 
 #[tauri::command]
-pub fn get_exams(db: State<DbConnection>, subject_id: i64) -> Result<Vec<ExamWithTopics>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_exams(db: State<DbConnection>, subject_id: i64) -> AppResult<Vec<ExamWithTopics>> {
+    let conn = db.0.lock()?;
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, subject_id, name, description, total_question_count, time_limit_minutes,
+    let mut stmt = conn.prepare(
+        "SELECT id, subject_id, name, description, total_question_count, time_limit_minutes,
              shuffle_questions, shuffle_options, show_answers_after, passing_score_percent,
              created_at, updated_at
              FROM exams WHERE subject_id = ? ORDER BY created_at DESC",
-        )
-        .map_err(|e| e.to_string())?;
+    )?;
 
     let exams = stmt
         .query_map([subject_id], |row| {
@@ -122,23 +132,19 @@ pub fn get_exams(db: State<DbConnection>, subject_id: i64) -> Result<Vec<ExamWit
                 created_at: row.get(10)?,
                 updated_at: row.get(11)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     // For each exam, fetch its topics
     let mut exams_with_topics = Vec::new();
     for exam in exams {
-        let mut topic_stmt = conn
-            .prepare(
-                "SELECT et.id, et.exam_id, et.topic_id, t.name, et.question_count
+        let mut topic_stmt = conn.prepare(
+            "SELECT et.id, et.exam_id, et.topic_id, t.name, et.question_count
                  FROM exam_topics et
                  JOIN topics t ON et.topic_id = t.id
                  WHERE et.exam_id = ?
                  ORDER BY t.name",
-            )
-            .map_err(|e| e.to_string())?;
+        )?;
 
         let topics = topic_stmt
             .query_map([exam.id], |row| {
@@ -149,10 +155,8 @@ pub fn get_exams(db: State<DbConnection>, subject_id: i64) -> Result<Vec<ExamWit
                     topic_name: row.get(3)?,
                     question_count: row.get(4)?,
                 })
-            })
-            .map_err(|e| e.to_string())?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| e.to_string())?;
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
 
         exams_with_topics.push(ExamWithTopics {
             id: exam.id,
@@ -175,45 +179,45 @@ pub fn get_exams(db: State<DbConnection>, subject_id: i64) -> Result<Vec<ExamWit
 }
 
 #[tauri::command]
-pub fn get_exam(db: State<DbConnection>, id: i64) -> Result<ExamWithTopics, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_exam(db: State<DbConnection>, id: i64) -> AppResult<ExamWithTopics> {
+    let conn = db.0.lock()?;
+    fetch_exam_with_topics(&conn, id)
+}
 
-    let exam = conn
-        .query_row(
-            "SELECT id, subject_id, name, description, total_question_count, time_limit_minutes,
+/// Load an exam and its topic blueprint
+pub fn fetch_exam_with_topics(conn: &rusqlite::Connection, id: i64) -> AppResult<ExamWithTopics> {
+    let exam = conn.query_row(
+        "SELECT id, subject_id, name, description, total_question_count, time_limit_minutes,
              shuffle_questions, shuffle_options, show_answers_after, passing_score_percent,
              created_at, updated_at
              FROM exams WHERE id = ?",
-            [id],
-            |row| {
-                Ok(Exam {
-                    id: row.get(0)?,
-                    subject_id: row.get(1)?,
-                    name: row.get(2)?,
-                    description: row.get(3)?,
-                    total_question_count: row.get(4)?,
-                    time_limit_minutes: row.get(5)?,
-                    shuffle_questions: row.get::<_, i32>(6)? != 0,
-                    shuffle_options: row.get::<_, i32>(7)? != 0,
-                    show_answers_after: row.get(8)?,
-                    passing_score_percent: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+        [id],
+        |row| {
+            Ok(Exam {
+                id: row.get(0)?,
+                subject_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                total_question_count: row.get(4)?,
+                time_limit_minutes: row.get(5)?,
+                shuffle_questions: row.get::<_, i32>(6)? != 0,
+                shuffle_options: row.get::<_, i32>(7)? != 0,
+                show_answers_after: row.get(8)?,
+                passing_score_percent: row.get(9)?,
+                created_at: row.get(10)?,
+                updated_at: row.get(11)?,
+            })
+        },
+    )?;
 
     // Fetch topics for this exam
-    let mut topic_stmt = conn
-        .prepare(
-            "SELECT et.id, et.exam_id, et.topic_id, t.name, et.question_count
+    let mut topic_stmt = conn.prepare(
+        "SELECT et.id, et.exam_id, et.topic_id, t.name, et.question_count
              FROM exam_topics et
              JOIN topics t ON et.topic_id = t.id
              WHERE et.exam_id = ?
              ORDER BY t.name",
-        )
-        .map_err(|e| e.to_string())?;
+    )?;
 
     let topics = topic_stmt
         .query_map([id], |row| {
@@ -224,10 +228,8 @@ pub fn get_exam(db: State<DbConnection>, id: i64) -> Result<ExamWithTopics, Stri
                 topic_name: row.get(3)?,
                 question_count: row.get(4)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(ExamWithTopics {
         id: exam.id,
@@ -247,110 +249,351 @@ pub fn get_exam(db: State<DbConnection>, id: i64) -> Result<ExamWithTopics, Stri
 }
 
 #[tauri::command]
-pub fn create_exam(db: State<DbConnection>, data: CreateExamData) -> Result<ExamWithTopics, String> {
-    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn create_exam(db: State<DbConnection>, data: CreateExamData) -> AppResult<ExamWithTopics> {
+    db.transaction(|tx| {
+        validate_blueprint(tx, data.subject_id, data.total_question_count, &data.topics)?;
 
-    // Start transaction
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+        // Insert exam
+        tx.execute(
+            "INSERT INTO exams (subject_id, name, description, total_question_count, time_limit_minutes,
+             shuffle_questions, shuffle_options, show_answers_after, passing_score_percent)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            (
+                data.subject_id,
+                &data.name,
+                &data.description,
+                data.total_question_count,
+                data.time_limit_minutes,
+                data.shuffle_questions as i32,
+                data.shuffle_options as i32,
+                &data.show_answers_after,
+                data.passing_score_percent,
+            ),
+        )?;
+
+        let exam_id = tx.last_insert_rowid();
+
+        insert_exam_topics(tx, exam_id, &data.topics)?;
+
+        // Fetch and return the created exam
+        fetch_exam_with_topics(tx, exam_id)
+    })
+}
 
-    // Insert exam
-    tx.execute(
-        "INSERT INTO exams (subject_id, name, description, total_question_count, time_limit_minutes,
-         shuffle_questions, shuffle_options, show_answers_after, passing_score_percent)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-        (
-            data.subject_id,
-            &data.name,
-            &data.description,
-            data.total_question_count,
-            data.time_limit_minutes,
-            data.shuffle_questions as i32,
-            data.shuffle_options as i32,
-            &data.show_answers_after,
-            data.passing_score_percent,
-        ),
-    )
-    .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn update_exam(
+    db: State<DbConnection>,
+    id: i64,
+    data: UpdateExamData,
+) -> AppResult<ExamWithTopics> {
+    db.transaction(|tx| {
+        let subject_id: i64 = tx
+            .query_row("SELECT subject_id FROM exams WHERE id = ?", [id], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .ok_or_else(|| AppError::not_found(format!("Exam {} not found", id)))?;
 
-    let exam_id = tx.last_insert_rowid();
+        validate_blueprint(tx, subject_id, data.total_question_count, &data.topics)?;
 
-    // Insert exam topics
-    for topic in &data.topics {
+        // Update exam
         tx.execute(
+            "UPDATE exams SET name = ?1, description = ?2, total_question_count = ?3,
+             time_limit_minutes = ?4, shuffle_questions = ?5, shuffle_options = ?6,
+             show_answers_after = ?7, passing_score_percent = ?8, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?9",
+            (
+                &data.name,
+                &data.description,
+                data.total_question_count,
+                data.time_limit_minutes,
+                data.shuffle_questions as i32,
+                data.shuffle_options as i32,
+                &data.show_answers_after,
+                data.passing_score_percent,
+                id,
+            ),
+        )?;
+
+        // Replace the exam topics
+        tx.execute("DELETE FROM exam_topics WHERE exam_id = ?", [id])?;
+        insert_exam_topics(tx, id, &data.topics)?;
+
+        // Fetch and return the updated exam
+        fetch_exam_with_topics(tx, id)
+    })
+}
+
+fn insert_exam_topics(
+    conn: &rusqlite::Connection,
+    exam_id: i64,
+    topics: &[CreateExamTopicData],
+) -> AppResult<()> {
+    for topic in topics {
+        conn.execute(
             "INSERT INTO exam_topics (exam_id, topic_id, question_count) VALUES (?1, ?2, ?3)",
             (exam_id, topic.topic_id, topic.question_count),
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
-    // Commit transaction
-    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    // Drop the connection lock before calling get_exam
-    drop(conn);
+#[tauri::command]
+pub fn delete_exam(db: State<DbConnection>, id: i64) -> AppResult<()> {
+    let conn = db.0.lock()?;
 
-    // Fetch and return the created exam
-    get_exam(db, exam_id)
+    conn.execute("DELETE FROM exams WHERE id = ?", [id])?;
+
+    Ok(())
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssembledExam {
+    pub exam_id: i64,
+    /// The attempt opened for this sitting; pass it to `save_exam_attempt`
+    pub attempt_id: i64,
+    pub seed: i64,
+    pub questions: Vec<QuestionWithDetails>,
+}
+
+/// Draw the questions for one sitting of an exam: `question_count` random questions
+/// from each `exam_topics` row, then shuffled as the exam settings ask. Opens an exam
+/// attempt and records exactly what was served. Passing the seed of an earlier
+/// attempt replays it from what that attempt stored.
 #[tauri::command]
-pub fn update_exam(db: State<DbConnection>, id: i64, data: UpdateExamData) -> Result<ExamWithTopics, String> {
-    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn assemble_exam(
+    db: State<DbConnection>,
+    exam_id: i64,
+    seed: Option<i64>,
+) -> AppResult<AssembledExam> {
+    let mut conn = db.0.lock()?;
+
+    let (subject_id, total_question_count, shuffle_questions, shuffle_options): (
+        i64,
+        i32,
+        bool,
+        bool,
+    ) = conn.query_row(
+        "SELECT subject_id, total_question_count, shuffle_questions, shuffle_options
+             FROM exams WHERE id = ?",
+        [exam_id],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get::<_, i32>(2)? != 0,
+                row.get::<_, i32>(3)? != 0,
+            ))
+        },
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT et.topic_id, et.question_count
+             FROM exam_topics et
+             JOIN topics t ON et.topic_id = t.id
+             WHERE et.exam_id = ?
+             ORDER BY t.name, et.id",
+    )?;
+
+    let topics = stmt
+        .query_map([exam_id], |row| {
+            Ok(CreateExamTopicData {
+                topic_id: row.get(0)?,
+                question_count: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    drop(stmt);
+
+    let replayed: Option<i64> = match seed {
+        Some(seed) => conn
+            .query_row(
+                "SELECT id FROM exam_attempts WHERE exam_id = ?1 AND seed = ?2
+                 ORDER BY id ASC LIMIT 1",
+                (exam_id, seed),
+                |row| row.get(0),
+            )
+            .optional()?,
+        None => None,
+    };
+
+    let seed = seed.unwrap_or_else(fresh_seed);
+    let questions = match replayed {
+        Some(attempt_id) => served_questions(&conn, "exam_attempt_questions", attempt_id)?,
+        None => {
+            // The bank may have changed since the exam was saved
+            validate_blueprint(&conn, subject_id, total_question_count, &topics)?;
+            draw_exam_questions(&conn, &topics, shuffle_questions, shuffle_options, seed)?
+        }
+    };
+    if questions.is_empty() {
+        return Err(AppError::conflict(
+            "None of the questions of that attempt remain",
+        ));
+    }
 
-    // Start transaction
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let tx = conn.transaction()?;
 
-    // Update exam
     tx.execute(
-        "UPDATE exams SET name = ?1, description = ?2, total_question_count = ?3,
-         time_limit_minutes = ?4, shuffle_questions = ?5, shuffle_options = ?6,
-         show_answers_after = ?7, passing_score_percent = ?8, updated_at = CURRENT_TIMESTAMP
-         WHERE id = ?9",
-        (
-            &data.name,
-            &data.description,
-            data.total_question_count,
-            data.time_limit_minutes,
-            data.shuffle_questions as i32,
-            data.shuffle_options as i32,
-            &data.show_answers_after,
-            data.passing_score_percent,
-            id,
-        ),
-    )
-    .map_err(|e| e.to_string())?;
+        "INSERT INTO exam_attempts (exam_id, seed, completed_at) VALUES (?1, ?2, NULL)",
+        (exam_id, seed),
+    )?;
+    let attempt_id = tx.last_insert_rowid();
 
-    // Delete existing exam topics
-    tx.execute("DELETE FROM exam_topics WHERE exam_id = ?", [id])
-        .map_err(|e| e.to_string())?;
+    // Remember what was served so the attempt is graded against it
+    store_served_questions(&tx, "exam_attempt_questions", attempt_id, &questions)?;
 
-    // Insert new exam topics
-    for topic in &data.topics {
-        tx.execute(
-            "INSERT INTO exam_topics (exam_id, topic_id, question_count) VALUES (?1, ?2, ?3)",
-            (id, topic.topic_id, topic.question_count),
-        )
-        .map_err(|e| e.to_string())?;
+    tx.commit()?;
+
+    Ok(AssembledExam {
+        exam_id,
+        attempt_id,
+        seed,
+        questions,
+    })
+}
+
+/// Pick and order the questions for a new exam attempt from the blueprint rows
+fn draw_exam_questions(
+    conn: &rusqlite::Connection,
+    topics: &[CreateExamTopicData],
+    shuffle_questions: bool,
+    shuffle_options: bool,
+    seed: i64,
+) -> AppResult<Vec<QuestionWithDetails>> {
+    let mut rng = SeededRng::new(seed);
+
+    // One shuffled pool per topic so a topic listed twice never repeats a question
+    let mut pools: HashMap<i64, Vec<i64>> = HashMap::new();
+    let mut question_ids = Vec::new();
+    for topic in topics {
+        let pool = match pools.entry(topic.topic_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut ids = topic_question_ids(conn, topic.topic_id)?;
+                rng.shuffle(&mut ids);
+                entry.insert(ids)
+            }
+        };
+        let take = (topic.question_count.max(0) as usize).min(pool.len());
+        question_ids.extend(pool.drain(..take));
     }
 
-    // Commit transaction
-    tx.commit().map_err(|e| e.to_string())?;
+    if shuffle_questions {
+        rng.shuffle(&mut question_ids);
+    }
 
-    // Drop the connection lock before calling get_exam
-    drop(conn);
+    let mut questions = Vec::with_capacity(question_ids.len());
+    for question_id in question_ids {
+        let mut question = fetch_question_with_details(conn, question_id)?;
+        if shuffle_options {
+            rng.shuffle(&mut question.options);
+        }
+        questions.push(question);
+    }
 
-    // Fetch and return the updated exam
-    get_exam(db, id)
+    Ok(questions)
 }
 
-#[tauri::command]
-pub fn delete_exam(db: State<DbConnection>, id: i64) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+/// Check that an exam blueprint can be filled from the question bank: the per-topic
+/// counts must add up to the exam total and every topic must have enough questions.
+/// All problems are reported together.
+fn validate_blueprint(
+    conn: &rusqlite::Connection,
+    subject_id: i64,
+    total_question_count: i32,
+    topics: &[CreateExamTopicData],
+) -> AppResult<()> {
+    let mut problems = Vec::new();
+
+    if topics.is_empty() {
+        problems.push("The exam has no topics".to_string());
+    }
 
-    conn.execute("DELETE FROM exams WHERE id = ?", [id])
-        .map_err(|e| e.to_string())?;
+    let topic_total: i32 = topics.iter().map(|t| t.question_count).sum();
+    if topic_total != total_question_count {
+        problems.push(format!(
+            "Topic question counts add up to {} but the exam has {} questions",
+            topic_total, total_question_count
+        ));
+    }
 
-    Ok(())
+    // Sum the quota per topic in case a topic is listed more than once
+    let mut required: Vec<(i64, i32)> = Vec::new();
+    for topic in topics {
+        if topic.question_count <= 0 {
+            problems.push(format!(
+                "Topic {} must draw at least one question",
+                topic.topic_id
+            ));
+        }
+        match required.iter_mut().find(|(id, _)| *id == topic.topic_id) {
+            Some((_, count)) => *count += topic.question_count,
+            None => required.push((topic.topic_id, topic.question_count)),
+        }
+    }
+
+    let mut short = Vec::new();
+    for (topic_id, count) in required {
+        let topic = conn
+            .query_row(
+                "SELECT t.name, t.subject_id, COUNT(q.id)
+                 FROM topics t
+                 LEFT JOIN questions q ON q.topic_id = t.id
+                 WHERE t.id = ?
+                 GROUP BY t.id",
+                [topic_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i32>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        match topic {
+            None => problems.push(format!("Topic {} does not exist", topic_id)),
+            Some((name, topic_subject_id, _)) if topic_subject_id != subject_id => {
+                problems.push(format!("Topic \"{}\" belongs to a different subject", name))
+            }
+            Some((name, _, available)) if available < count => {
+                short.push(format!("{} (needs {}, has {})", name, count, available))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if !short.is_empty() {
+        problems.push(format!("Not enough questions in: {}", short.join(", ")));
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let mut fields = vec!["topics"];
+    if topic_total != total_question_count {
+        fields.push("totalQuestionCount");
+    }
+    Err(AppError::validation(
+        format!("Exam blueprint cannot be filled: {}", problems.join("; ")),
+        &fields,
+    ))
+}
+
+fn topic_question_ids(conn: &rusqlite::Connection, topic_id: i64) -> AppResult<Vec<i64>> {
+    let mut stmt = conn.prepare("SELECT id FROM questions WHERE topic_id = ? ORDER BY id ASC")?;
+
+    let ids = stmt
+        .query_map([topic_id], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ids)
 }
 
 #[derive(Debug, Serialize)]
@@ -362,7 +605,7 @@ pub struct ExamAttemptWithDetails {
     pub subject_name: String,
     pub started_at: String,
     pub completed_at: String,
-    pub score: i32,
+    pub score: f64,
     pub max_score: i32,
     pub percentage: f64,
     pub time_taken_seconds: i32,
@@ -370,8 +613,8 @@ pub struct ExamAttemptWithDetails {
 }
 
 #[tauri::command]
-pub fn get_all_exam_attempts(db: State<DbConnection>) -> Result<Vec<ExamAttemptWithDetails>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_all_exam_attempts(db: State<DbConnection>) -> AppResult<Vec<ExamAttemptWithDetails>> {
+    let conn = db.0.lock()?;
 
     let mut stmt = conn.prepare(
         "SELECT
@@ -389,29 +632,30 @@ pub fn get_all_exam_attempts(db: State<DbConnection>) -> Result<Vec<ExamAttemptW
          FROM exam_attempts ea
          JOIN exams e ON ea.exam_id = e.id
          JOIN subjects s ON e.subject_id = s.id
-         ORDER BY ea.completed_at DESC"
-    ).map_err(|e| e.to_string())?;
-
-    let attempts = stmt.query_map([], |row| {
-        let percentage: f64 = row.get(9)?;
-        let passing_score: i32 = row.get(3)?;
-
-        Ok(ExamAttemptWithDetails {
-            id: row.get(0)?,
-            exam_id: row.get(1)?,
-            exam_name: row.get(2)?,
-            subject_name: row.get(4)?,
-            started_at: row.get(5)?,
-            completed_at: row.get(6)?,
-            score: row.get(7)?,
-            max_score: row.get(8)?,
-            percentage,
-            time_taken_seconds: row.get(10)?,
-            passed: percentage >= passing_score as f64,
-        })
-    }).map_err(|e| e.to_string())?
-    .collect::<Result<Vec<_>, _>>()
-    .map_err(|e| e.to_string())?;
+         WHERE ea.completed_at IS NOT NULL
+         ORDER BY ea.completed_at DESC",
+    )?;
+
+    let attempts = stmt
+        .query_map([], |row| {
+            let percentage: f64 = row.get(9)?;
+            let passing_score: i32 = row.get(3)?;
+
+            Ok(ExamAttemptWithDetails {
+                id: row.get(0)?,
+                exam_id: row.get(1)?,
+                exam_name: row.get(2)?,
+                subject_name: row.get(4)?,
+                started_at: row.get(5)?,
+                completed_at: row.get(6)?,
+                score: row.get(7)?,
+                max_score: row.get(8)?,
+                percentage,
+                time_taken_seconds: row.get(10)?,
+                passed: percentage >= passing_score as f64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(attempts)
 }
@@ -419,30 +663,190 @@ pub fn get_all_exam_attempts(db: State<DbConnection>) -> Result<Vec<ExamAttemptW
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SaveExamAttemptData {
-    pub exam_id: i64,
-    pub score: i32,
-    pub max_score: i32,
-    pub percentage: f64,
-    pub time_taken_seconds: i32,
+    pub attempt_id: i64,
+    pub responses: Vec<SubmittedResponse>,
 }
 
+/// Grade and close an attempt opened by `assemble_exam`. Served questions without a
+/// response count as unanswered.
 #[tauri::command]
-pub fn save_exam_attempt(db: State<DbConnection>, data: SaveExamAttemptData) -> Result<i64, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn save_exam_attempt(
+    db: State<DbConnection>,
+    data: SaveExamAttemptData,
+) -> AppResult<AttemptResult> {
+    let mut conn = db.0.lock()?;
+
+    let (passing_score, completed_at): (i32, Option<String>) = conn.query_row(
+        "SELECT e.passing_score_percent, ea.completed_at
+             FROM exam_attempts ea
+             JOIN exams e ON ea.exam_id = e.id
+             WHERE ea.id = ?",
+        [data.attempt_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    if completed_at.is_some() {
+        return Err(AppError::conflict(format!(
+            "Exam attempt {} is already completed",
+            data.attempt_id
+        )));
+    }
+
+    // Score the attempt from the stored questions rather than trusting the client
+    let served = served_question_ids(&conn, "exam_attempt_questions", data.attempt_id)?;
+    let graded = grade_served(
+        &conn,
+        &format!("exam attempt {}", data.attempt_id),
+        &served,
+        data.responses,
+    )?;
 
-    conn.execute(
-        "INSERT INTO exam_attempts (exam_id, score, max_score, percentage, time_taken_seconds, completed_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+    // Start transaction so the attempt and its responses are saved together
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "UPDATE exam_attempts SET score = ?1, max_score = ?2, percentage = ?3,
+         time_taken_seconds = CAST(strftime('%s', 'now') - strftime('%s', started_at) AS INTEGER),
+         completed_at = datetime('now')
+         WHERE id = ?4",
         (
-            data.exam_id,
-            data.score,
-            data.max_score,
-            data.percentage,
-            data.time_taken_seconds,
+            graded.score,
+            graded.max_score,
+            graded.percentage,
+            data.attempt_id,
         ),
-    ).map_err(|e| e.to_string())?;
+    )?;
+
+    // Insert one row per question served
+    for response in &graded.responses {
+        let response_data = serde_json::to_string(&response.answer)?;
+        tx.execute(
+            "INSERT INTO exam_responses (attempt_id, question_id, response_data, is_correct,
+             points_earned, time_spent_seconds, revision)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            (
+                data.attempt_id,
+                response.question_id,
+                &response_data,
+                response.is_correct as i32,
+                response.points_earned,
+                response.time_spent_seconds,
+                current_revision(&tx, response.question_id)?,
+            ),
+        )?;
+    }
+
+    // Answers also feed the spaced-repetition schedule
+    record_responses(&tx, &graded.responses)?;
 
-    Ok(conn.last_insert_rowid())
+    let time_taken_seconds: i32 = tx.query_row(
+        "SELECT time_taken_seconds FROM exam_attempts WHERE id = ?",
+        [data.attempt_id],
+        |row| row.get(0),
+    )?;
+
+    // Commit transaction
+    tx.commit()?;
+
+    Ok(AttemptResult {
+        attempt_id: data.attempt_id,
+        score: graded.score,
+        max_score: graded.max_score,
+        percentage: graded.percentage,
+        time_taken_seconds,
+        passed: graded.percentage >= passing_score as f64,
+        responses: graded.responses,
+    })
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExamAttemptDetail {
+    #[serde(flatten)]
+    pub attempt: ExamAttemptWithDetails,
+    pub responses: Vec<ResponseDetail>,
+}
+
+#[tauri::command]
+pub fn get_exam_attempt_detail(
+    db: State<DbConnection>,
+    attempt_id: i64,
+) -> AppResult<ExamAttemptDetail> {
+    let conn = db.0.lock()?;
+
+    let attempt = conn.query_row(
+        "SELECT
+            ea.id,
+            ea.exam_id,
+            e.name as exam_name,
+            e.passing_score_percent,
+            s.name as subject_name,
+            ea.started_at,
+            ea.completed_at,
+            ea.score,
+            ea.max_score,
+            ea.percentage,
+            ea.time_taken_seconds
+         FROM exam_attempts ea
+         JOIN exams e ON ea.exam_id = e.id
+         JOIN subjects s ON e.subject_id = s.id
+         WHERE ea.id = ? AND ea.completed_at IS NOT NULL",
+        [attempt_id],
+        |row| {
+            let percentage: f64 = row.get(9)?;
+            let passing_score: i32 = row.get(3)?;
+
+            Ok(ExamAttemptWithDetails {
+                id: row.get(0)?,
+                exam_id: row.get(1)?,
+                exam_name: row.get(2)?,
+                subject_name: row.get(4)?,
+                started_at: row.get(5)?,
+                completed_at: row.get(6)?,
+                score: row.get(7)?,
+                max_score: row.get(8)?,
+                percentage,
+                time_taken_seconds: row.get(10)?,
+                passed: percentage >= passing_score as f64,
+            })
+        },
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT question_id, response_data, is_correct, points_earned, time_spent_seconds, revision
+         FROM exam_responses WHERE attempt_id = ? ORDER BY id ASC",
+    )?;
+
+    let rows = stmt
+        .query_map([attempt_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<i32>>(2)?.unwrap_or(0) != 0,
+                row.get::<_, Option<f64>>(3)?.unwrap_or(0.0),
+                row.get::<_, Option<i32>>(4)?,
+                row.get::<_, Option<i64>>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Attach each question as it was answered, together with the answer that was given
+    let mut responses = Vec::new();
+    for (question_id, response_data, is_correct, points_earned, time_spent_seconds, revision) in
+        rows
+    {
+        let answer: Option<ResponseData> = serde_json::from_str(&response_data)?;
+        responses.push(ResponseDetail {
+            question: answered_question(&conn, question_id, revision)?,
+            revision,
+            answer,
+            is_correct,
+            points_earned,
+            time_spent_seconds,
+        });
+    }
+
+    Ok(ExamAttemptDetail { attempt, responses })
 }
 
 #[derive(Debug, Serialize)]
@@ -455,8 +859,8 @@ pub struct SubjectPerformance {
 }
 
 #[tauri::command]
-pub fn get_subject_performance(db: State<DbConnection>) -> Result<Vec<SubjectPerformance>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_subject_performance(db: State<DbConnection>) -> AppResult<Vec<SubjectPerformance>> {
+    let conn = db.0.lock()?;
 
     let mut stmt = conn.prepare(
         "SELECT
@@ -467,21 +871,22 @@ pub fn get_subject_performance(db: State<DbConnection>) -> Result<Vec<SubjectPer
          FROM subjects s
          JOIN exams e ON e.subject_id = s.id
          JOIN exam_attempts ea ON ea.exam_id = e.id
+         WHERE ea.completed_at IS NOT NULL
          GROUP BY s.id, s.name
          HAVING COUNT(ea.id) > 0
          ORDER BY average_score DESC"
-    ).map_err(|e| e.to_string())?;
-
-    let performance = stmt.query_map([], |row| {
-        Ok(SubjectPerformance {
-            subject_name: row.get(0)?,
-            attempts: row.get(1)?,
-            average_score: row.get(2)?,
-            pass_rate: row.get(3)?,
-        })
-    }).map_err(|e| e.to_string())?
-    .collect::<Result<Vec<_>, _>>()
-    .map_err(|e| e.to_string())?;
+    )?;
+
+    let performance = stmt
+        .query_map([], |row| {
+            Ok(SubjectPerformance {
+                subject_name: row.get(0)?,
+                attempts: row.get(1)?,
+                average_score: row.get(2)?,
+                pass_rate: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(performance)
 }
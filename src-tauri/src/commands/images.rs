@@ -1,89 +1,751 @@
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use tauri::{AppHandle, Manager};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::revisions::record_revision;
+use crate::commands::settings::{load_image_settings, ImageSettings};
+use crate::db::{with_transaction, DbConnection};
+use crate::error::{AppError, AppResult};
+use crate::imaging;
+
+/// Images changed more recently than this are never collected: the editor copies an
+/// image into the store before the question that uses it is saved.
+const UNUSED_IMAGE_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Largest image accepted into or read from the asset store
+pub const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Directory under the app data dir that asset paths must stay inside
+const ASSETS_DIR: &str = "assets";
+
+/// Directory under `assets` holding the thumbnails of stored images
+const THUMBNAILS_DIR: &str = "thumbnails";
+
+/// Base64 as found in `data:` URLs, with or without padding
+const DATA_URL_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Columns that hold image paths, as `(table, column)`. The `asset_references` view
+/// covers these and the paths inside question revision snapshots.
+pub const IMAGE_COLUMNS: &[(&str, &str)] = &[
+    ("questions", "question_image_path"),
+    ("question_options", "option_image_path"),
+    ("question_matches", "left_image_path"),
+    ("question_matches", "right_image_path"),
+];
+
+/// An image format recognised from the first bytes of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+    Svg,
+}
+
+impl ImageFormat {
+    /// The format of `data`, or `None` if it is not a supported image
+    pub fn sniff(data: &[u8]) -> Option<ImageFormat> {
+        match data {
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(ImageFormat::Png),
+            [0xFF, 0xD8, 0xFF, ..] => Some(ImageFormat::Jpeg),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(ImageFormat::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => {
+                Some(ImageFormat::Webp)
+            }
+            [b'B', b'M', ..] if data.len() >= 26 => Some(ImageFormat::Bmp),
+            _ if is_svg(data) => Some(ImageFormat::Svg),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+/// A relative path, as stored in the database, that is known to stay inside the
+/// `assets` directory: no `..`, no absolute or drive paths, no backslashes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetPath(String);
+
+impl AssetPath {
+    pub fn parse(relative_path: &str) -> AppResult<AssetPath> {
+        let invalid =
+            || AppError::invalid_asset_path(format!("Not an asset path: {}", relative_path));
+        if relative_path.contains(['\\', '\0']) {
+            return Err(invalid());
+        }
+
+        let mut components = Path::new(relative_path).components();
+        if components.next() != Some(Component::Normal(ASSETS_DIR.as_ref())) {
+            return Err(invalid());
+        }
+        let mut depth = 0;
+        for component in components {
+            match component {
+                Component::Normal(_) => depth += 1,
+                Component::CurDir => {}
+                _ => return Err(invalid()),
+            }
+        }
+        if depth == 0 {
+            return Err(invalid());
+        }
+
+        Ok(AssetPath(relative_path.to_string()))
+    }
+
+    /// The file this path names under `app_data_dir`. Symlinks are followed, and the
+    /// target must still be inside the assets directory.
+    pub fn resolve(&self, app_data_dir: &Path) -> AppResult<PathBuf> {
+        let not_found = |e: std::io::Error| AppError::io(format!("Failed to read image: {}", e));
+        let assets_dir = app_data_dir
+            .join(ASSETS_DIR)
+            .canonicalize()
+            .map_err(not_found)?;
+        let path = app_data_dir
+            .join(&self.0)
+            .canonicalize()
+            .map_err(not_found)?;
+
+        if !path.starts_with(&assets_dir) {
+            return Err(AppError::invalid_asset_path(format!(
+                "{} points outside the assets directory",
+                self.0
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Read the image this path names, checking its size and format
+    pub fn read_image(&self, app_data_dir: &Path) -> AppResult<(Vec<u8>, ImageFormat)> {
+        let data = read_limited(&self.resolve(app_data_dir)?, &self.0)?;
+        let format = ImageFormat::sniff(&data).ok_or_else(|| {
+            AppError::unsupported_image(format!("{} is not a supported image", self.0))
+        })?;
+        Ok((data, format))
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnusedImage {
+    /// Relative to the app data dir, like the paths stored in the database
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnusedImagesReport {
+    pub images: Vec<UnusedImage>,
+    pub total_bytes: u64,
+    /// Whether the images were deleted, or only reported
+    pub deleted: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizeImagesReport {
+    /// Images referenced by questions, options and matches
+    pub images: usize,
+    /// Images that were scaled down, stripped of metadata or re-encoded
+    pub optimized: usize,
+    pub thumbnails: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// Image path columns pointed at a new file
+    pub updated_references: usize,
+    pub failed: Vec<ImageFailure>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageFailure {
+    pub path: String,
+    pub message: String,
+}
+
+/// Copy an image chosen by the user into the asset store, processed as the image
+/// settings ask
 #[tauri::command]
 pub fn copy_image_to_assets(
     app_handle: AppHandle,
+    db: State<DbConnection>,
     source_path: String,
-) -> Result<String, String> {
-    // Get the app data directory
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
-
-    // Create assets/images directory if it doesn't exist
-    let images_dir = app_data_dir.join("assets").join("images");
-    fs::create_dir_all(&images_dir)
-        .map_err(|e| format!("Failed to create images directory: {}", e))?;
+) -> AppResult<String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let settings = load_image_settings(&*db.0.lock()?)?;
 
     // Get the source file name
     let source_path_buf = PathBuf::from(&source_path);
-    let file_stem = source_path_buf
-        .file_stem()
-        .ok_or("Invalid file path")?
+    let file_name = source_path_buf
+        .file_name()
+        .ok_or_else(|| AppError::validation("Invalid file path", &["sourcePath"]))?
         .to_str()
-        .ok_or("Invalid file name")?;
+        .ok_or_else(|| AppError::validation("Invalid file name", &["sourcePath"]))?;
 
-    let extension = source_path_buf
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("png");
+    let image_data = read_limited(&source_path_buf, file_name)?;
 
-    // Generate a unique filename using timestamp
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| format!("Failed to get timestamp: {}", e))?
-        .as_millis();
+    import_image(&app_data_dir, &settings, file_name, &image_data)
+}
 
-    let unique_filename = format!("{}_{}.{}", timestamp, file_stem, extension);
-    let dest_path = images_dir.join(&unique_filename);
+/// Image data that does not come from a file: a pasted screenshot or an image dragged
+/// from a browser. The frontend sends `{ bytes: [...] }` or `{ dataUrl: "data:..." }`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImageSource {
+    Bytes(Vec<u8>),
+    DataUrl(String),
+}
 
-    // Copy the file
-    fs::copy(&source_path, &dest_path)
-        .map_err(|e| format!("Failed to copy image: {}", e))?;
+/// Store image data from the clipboard or a drop in the asset store, the same way
+/// `copy_image_to_assets` stores a file. `file_name` only appears in error messages.
+#[tauri::command]
+pub fn import_image_data(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    source: ImageSource,
+    file_name: Option<String>,
+) -> AppResult<String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let settings = load_image_settings(&*db.0.lock()?)?;
+    let name = file_name.as_deref().unwrap_or("Pasted image");
 
-    // Return the relative path from app data dir
-    let relative_path = format!("assets/images/{}", unique_filename);
-    Ok(relative_path)
+    let image_data = match source {
+        ImageSource::Bytes(bytes) => bytes,
+        ImageSource::DataUrl(url) => decode_data_url(&url, name)?,
+    };
+    if image_data.is_empty() {
+        return Err(AppError::validation("The image is empty", &["source"]));
+    }
+
+    import_image(&app_data_dir, &settings, name, &image_data)
 }
 
+/// Inline an image as a `data:` URL, for exports that must carry their images with
+/// them. The app itself loads images through the `quizforge-asset` URI scheme.
 #[tauri::command]
-pub fn read_image_as_data_url(
+pub fn read_image_as_data_url(app_handle: AppHandle, relative_path: String) -> AppResult<String> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    let (image_data, format) = AssetPath::parse(&relative_path)?.read_image(&app_data_dir)?;
+
+    // Convert to base64
+    let base64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &image_data);
+    let data_url = format!("data:{};base64,{}", format.mime_type(), base64);
+
+    Ok(data_url)
+}
+
+/// Run every image the library uses through the image pipeline with the current
+/// settings, and remake the thumbnails. Images whose file changes are stored under
+/// their new content hash and the `*_image_path` columns are pointed at them; the old
+/// files are left for `collect_unused_images`. Revision snapshots keep their paths.
+#[tauri::command]
+pub fn optimize_existing_images(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+) -> AppResult<OptimizeImagesReport> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let mut conn = db.0.lock()?;
+    let settings = load_image_settings(&conn)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT path FROM asset_references
+         WHERE table_name != 'question_revisions' ORDER BY path",
+    )?;
+    let paths = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut report = OptimizeImagesReport::default();
+    let mut moved = Vec::new();
+    for path in paths {
+        report.images += 1;
+        match optimize_image(&app_data_dir, &settings, &path) {
+            Ok(optimized) => {
+                report.bytes_before += optimized.bytes_before;
+                report.bytes_after += optimized.bytes_after;
+                report.optimized += optimized.reencoded as usize;
+                report.thumbnails += optimized.thumbnail as usize;
+                if optimized.path != path {
+                    moved.push((path, optimized.path));
+                }
+            }
+            Err(e) => report.failed.push(ImageFailure {
+                path,
+                message: e.message().to_string(),
+            }),
+        }
+    }
+
+    report.updated_references = with_transaction(&mut conn, |tx| {
+        let mut updated = 0;
+        let mut question_ids = BTreeSet::new();
+
+        for (old_path, new_path) in &moved {
+            for (table, column) in IMAGE_COLUMNS {
+                let id_column = if *table == "questions" {
+                    "id"
+                } else {
+                    "question_id"
+                };
+                let mut stmt = tx.prepare(&format!(
+                    "SELECT {} FROM {} WHERE {} = ?",
+                    id_column, table, column
+                ))?;
+                for id in stmt.query_map([old_path], |row| row.get::<_, i64>(0))? {
+                    question_ids.insert(id?);
+                }
+
+                updated += tx.execute(
+                    &format!("UPDATE {} SET {} = ?1 WHERE {} = ?2", table, column, column),
+                    (new_path, old_path),
+                )?;
+            }
+        }
+
+        for question_id in question_ids {
+            record_revision(tx, question_id)?;
+        }
+
+        Ok(updated)
+    })?;
+
+    Ok(report)
+}
+
+/// Find the images in `assets/images` that nothing in the database refers to (see the
+/// `asset_references` view), and delete them when `delete` is set. Thumbnails of
+/// images that are no longer referenced are collected with them.
+#[tauri::command]
+pub fn collect_unused_images(
     app_handle: AppHandle,
-    relative_path: String,
-) -> Result<String, String> {
-    let app_data_dir = app_handle
+    db: State<DbConnection>,
+    delete: bool,
+) -> AppResult<UnusedImagesReport> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    // Held until the files are gone, so a question saved meanwhile cannot lose its image
+    let conn = db.0.lock()?;
+
+    let mut stmt = conn.prepare("SELECT DISTINCT path FROM asset_references")?;
+    let referenced = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<HashSet<_>, _>>()?;
+
+    let thumbnails: HashSet<String> = referenced
+        .iter()
+        .filter_map(|path| thumbnail_path(path))
+        .collect();
+
+    let mut files = Vec::new();
+    for dir in ["images", THUMBNAILS_DIR] {
+        list_files(
+            &app_data_dir.join(ASSETS_DIR).join(dir),
+            &format!("{}/{}", ASSETS_DIR, dir),
+            &mut files,
+        )?;
+    }
+
+    let cutoff = SystemTime::now() - UNUSED_IMAGE_GRACE_PERIOD;
+    let mut images = Vec::new();
+    for (path, metadata) in files {
+        let recent = metadata
+            .modified()
+            .map_or(true, |modified| modified > cutoff);
+        if referenced.contains(&path) || thumbnails.contains(&path) || recent {
+            continue;
+        }
+        images.push(UnusedImage {
+            path,
+            size: metadata.len(),
+        });
+    }
+
+    if delete {
+        for image in &images {
+            fs::remove_file(app_data_dir.join(&image.path))
+                .map_err(|e| AppError::io(format!("Failed to delete {}: {}", image.path, e)))?;
+        }
+    }
+
+    Ok(UnusedImagesReport {
+        total_bytes: images.iter().map(|image| image.size).sum(),
+        images,
+        deleted: delete,
+    })
+}
+
+/// Get the app data directory that image paths are relative to
+pub fn get_app_data_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    app_handle
         .path()
         .app_data_dir()
-        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
-
-    let full_path = app_data_dir.join(&relative_path);
-
-    // Read the file
-    let image_data = fs::read(&full_path)
-        .map_err(|e| format!("Failed to read image: {}", e))?;
-
-    // Get file extension for MIME type
-    let extension = full_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("png")
-        .to_lowercase();
-
-    let mime_type = match extension.as_str() {
-        "jpg" | "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "gif" => "image/gif",
-        "webp" => "image/webp",
-        "svg" => "image/svg+xml",
-        _ => "image/png",
+        .map_err(|e| AppError::io(format!("Failed to get app data dir: {}", e)))
+}
+
+/// Save image bytes into `assets/images` and return the relative path stored in the database.
+///
+/// Files are named by the SHA-256 of their contents, with the extension of the format
+/// sniffed from the bytes, so storing the same image twice returns the existing file.
+/// `file_name` only names the image in errors. Data that is not a supported image or is
+/// over `MAX_IMAGE_BYTES` is rejected.
+pub fn store_image(app_data_dir: &Path, file_name: &str, data: &[u8]) -> AppResult<String> {
+    check_size(file_name, data.len() as u64)?;
+    let format = ImageFormat::sniff(data).ok_or_else(|| {
+        AppError::unsupported_image(format!("{} is not a supported image", file_name))
+    })?;
+
+    // Create assets/images directory if it doesn't exist
+    let images_dir = app_data_dir.join(ASSETS_DIR).join("images");
+    fs::create_dir_all(&images_dir)
+        .map_err(|e| AppError::io(format!("Failed to create images directory: {}", e)))?;
+
+    let hash: String = Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let file_name = format!("{}.{}", hash, format.extension());
+    let file_path = images_dir.join(&file_name);
+
+    if !file_path.exists() {
+        // Write under a temporary name first, so a failed write never leaves a
+        // truncated file behind the content's hash
+        let partial_path = images_dir.join(format!("{}.partial", file_name));
+        fs::write(&partial_path, data)
+            .and_then(|_| fs::rename(&partial_path, &file_path))
+            .map_err(|e| AppError::io(format!("Failed to save image: {}", e)))?;
+    }
+
+    // Return the relative path from app data dir
+    Ok(format!("{}/images/{}", ASSETS_DIR, file_name))
+}
+
+/// Store an image from outside the app: process it as `settings` ask (see
+/// `imaging::process`), store the result and make its thumbnail
+pub fn import_image(
+    app_data_dir: &Path,
+    settings: &ImageSettings,
+    file_name: &str,
+    data: &[u8],
+) -> AppResult<String> {
+    check_size(file_name, data.len() as u64)?;
+    let format = ImageFormat::sniff(data).ok_or_else(|| {
+        AppError::unsupported_image(format!("{} is not a supported image", file_name))
+    })?;
+
+    let processed = imaging::process(data, format, settings)?;
+    let data = processed.as_deref().unwrap_or(data);
+    let path = store_image(app_data_dir, file_name, data)?;
+    store_thumbnail(app_data_dir, settings, &path, data, false)?;
+
+    Ok(path)
+}
+
+/// Where the thumbnail of an image is kept: `assets/thumbnails/<image file stem>.jpg`
+pub fn thumbnail_path(image_path: &str) -> Option<String> {
+    let stem = Path::new(image_path).file_stem()?.to_str()?;
+    Some(format!("{}/{}/{}.jpg", ASSETS_DIR, THUMBNAILS_DIR, stem))
+}
+
+/// Write the thumbnail of the image at `image_path`, unless thumbnails are turned off
+/// or it exists and `replace` is not set. Returns whether one was written.
+fn store_thumbnail(
+    app_data_dir: &Path,
+    settings: &ImageSettings,
+    image_path: &str,
+    data: &[u8],
+    replace: bool,
+) -> AppResult<bool> {
+    let Some(path) = thumbnail_path(image_path).filter(|_| settings.thumbnail_size > 0) else {
+        return Ok(false);
     };
+    let path = app_data_dir.join(path);
+    if path.exists() && !replace {
+        return Ok(false);
+    }
 
-    // Convert to base64
-    let base64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &image_data);
-    let data_url = format!("data:{};base64,{}", mime_type, base64);
+    let Some(format) = ImageFormat::sniff(data) else {
+        return Ok(false);
+    };
+    let Some(thumbnail) = imaging::thumbnail(data, format, settings.thumbnail_size)? else {
+        return Ok(false);
+    };
 
-    Ok(data_url)
+    let dir = app_data_dir.join(ASSETS_DIR).join(THUMBNAILS_DIR);
+    fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(format!("Failed to create thumbnails directory: {}", e)))?;
+    let partial_path = path.with_extension("jpg.partial");
+    fs::write(&partial_path, thumbnail)
+        .and_then(|_| fs::rename(&partial_path, &path))
+        .map_err(|e| AppError::io(format!("Failed to save thumbnail: {}", e)))?;
+
+    Ok(true)
+}
+
+struct OptimizedImage {
+    /// Where the image is stored now; its content hash when it was re-encoded
+    path: String,
+    bytes_before: u64,
+    bytes_after: u64,
+    reencoded: bool,
+    thumbnail: bool,
+}
+
+fn optimize_image(
+    app_data_dir: &Path,
+    settings: &ImageSettings,
+    path: &str,
+) -> AppResult<OptimizedImage> {
+    let (data, format) = AssetPath::parse(path)?.read_image(app_data_dir)?;
+    let processed = imaging::process(&data, format, settings)?;
+    let reencoded = processed.is_some();
+    let new_data = processed.unwrap_or_else(|| data.clone());
+
+    // Images stored before content addressing move to their hash name as well
+    let new_path = if reencoded || !is_content_addressed(path) {
+        store_image(app_data_dir, path, &new_data)?
+    } else {
+        path.to_string()
+    };
+    let thumbnail = store_thumbnail(app_data_dir, settings, &new_path, &new_data, true)?;
+
+    Ok(OptimizedImage {
+        path: new_path,
+        bytes_before: data.len() as u64,
+        bytes_after: new_data.len() as u64,
+        reencoded,
+        thumbnail,
+    })
+}
+
+/// Whether a stored file is named by the SHA-256 of its contents
+pub fn is_content_addressed(path: &str) -> bool {
+    Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.len() == 64 && stem.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Read an image by the relative path stored in the database
+pub fn read_image(app_data_dir: &Path, relative_path: &str) -> AppResult<Vec<u8>> {
+    let (data, _) = AssetPath::parse(relative_path)?.read_image(app_data_dir)?;
+    Ok(data)
+}
+
+/// Read a file of at most `MAX_IMAGE_BYTES`; `name` identifies it in errors
+fn read_limited(path: &Path, name: &str) -> AppResult<Vec<u8>> {
+    let file =
+        fs::File::open(path).map_err(|e| AppError::io(format!("Failed to read image: {}", e)))?;
+    let metadata = file
+        .metadata()
+        .map_err(|e| AppError::io(format!("Failed to read image: {}", e)))?;
+    if !metadata.is_file() {
+        return Err(AppError::unsupported_image(format!(
+            "{} is not a file",
+            name
+        )));
+    }
+    check_size(name, metadata.len())?;
+
+    // The file may grow between the check and the read
+    let mut data = Vec::with_capacity(metadata.len() as usize);
+    file.take(MAX_IMAGE_BYTES + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| AppError::io(format!("Failed to read image: {}", e)))?;
+    check_size(name, data.len() as u64)?;
+
+    Ok(data)
+}
+
+/// The bytes of a `data:[<media type>][;base64],<data>` URL. A media type other than
+/// an image is rejected; the bytes themselves are sniffed later.
+fn decode_data_url(url: &str, name: &str) -> AppResult<Vec<u8>> {
+    let malformed = || AppError::validation("Not a valid data URL", &["source"]);
+
+    let url = url.trim();
+    let rest = url
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &url[5..])
+        .ok_or_else(malformed)?;
+    let (header, payload) = rest.split_once(',').ok_or_else(malformed)?;
+
+    let mut params = header.split(';').map(str::trim);
+    let media_type = params.next().unwrap_or_default().to_ascii_lowercase();
+    let base64 = params.any(|param| param.eq_ignore_ascii_case("base64"));
+    if !media_type.is_empty() && !media_type.starts_with("image/") {
+        return Err(AppError::unsupported_image(format!(
+            "{} is {}, not an image",
+            name, media_type
+        )));
+    }
+
+    if base64 {
+        // Every 4 characters hold 3 bytes; refuse oversized data before decoding it
+        let payload: String = payload
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        check_size(name, payload.len() as u64 / 4 * 3)?;
+        DATA_URL_BASE64
+            .decode(payload.as_bytes())
+            .map_err(|_| AppError::validation("The data URL is not valid base64", &["source"]))
+    } else {
+        check_size(name, payload.len() as u64)?;
+        percent_decode(payload).ok_or_else(malformed)
+    }
+}
+
+/// Decode `%XX` escapes; `None` if an escape is malformed
+pub fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(index + 1..index + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    Some(decoded)
+}
+
+fn check_size(name: &str, size: u64) -> AppResult<()> {
+    if size > MAX_IMAGE_BYTES {
+        return Err(AppError::file_too_large(
+            format!(
+                "{} is {:.1} MB; images can be at most {} MB",
+                name,
+                size as f64 / (1024.0 * 1024.0),
+                MAX_IMAGE_BYTES / (1024 * 1024)
+            ),
+            size,
+            MAX_IMAGE_BYTES,
+        ));
+    }
+    Ok(())
+}
+
+/// SVG is text whose first element is `<svg`, possibly after an XML declaration,
+/// comments or a doctype
+fn is_svg(data: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let mut text = head.trim_start_matches('\u{FEFF}');
+
+    loop {
+        text = text.trim_start();
+        let end = if text.starts_with("<?") {
+            text.find("?>").map(|i| i + 2)
+        } else if text.starts_with("<!--") {
+            text.find("-->").map(|i| i + 3)
+        } else if text.starts_with("<!") {
+            text.find('>').map(|i| i + 1)
+        } else {
+            return text.starts_with("<svg");
+        };
+        match end {
+            Some(end) => text = &text[end..],
+            None => return false,
+        }
+    }
+}
+
+/// Every file under `dir`, with its path written as `prefix/...`
+fn list_files(dir: &Path, prefix: &str, files: &mut Vec<(String, fs::Metadata)>) -> AppResult<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(AppError::io(format!("Failed to list images: {}", e))),
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|e| AppError::io(format!("Failed to list images: {}", e)))?;
+        let metadata = entry
+            .metadata()
+            .map_err(|e| AppError::io(format!("Failed to list images: {}", e)))?;
+        let path = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        if metadata.is_dir() {
+            list_files(&entry.path(), &path, files)?;
+        } else {
+            files.push((path, metadata));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_recognised_by_their_first_bytes() {
+        let cases: &[(&[u8], Option<ImageFormat>)] = &[
+            (b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", Some(ImageFormat::Png)),
+            (b"\xFF\xD8\xFF\xE0\0\x10JFIF", Some(ImageFormat::Jpeg)),
+            (b"RIFF\x24\0\0\0WEBPVP8L", Some(ImageFormat::Webp)),
+            (b"GIF89a\x01\0\x01\0", Some(ImageFormat::Gif)),
+            (b"<?xml version=\"1.0\"?>\n<!-- drawn by hand -->\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>", Some(ImageFormat::Svg)),
+            (b"not an image at all", None),
+            (b"\x89PNG", None),
+            (b"RIFF\x24\0\0\0WAVEfmt ", None),
+            (b"<html><svg/></html>", None),
+            (b"", None),
+        ];
+        for (data, format) in cases {
+            assert_eq!(
+                ImageFormat::sniff(data),
+                *format,
+                "{:?}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+
+    #[test]
+    fn bitmaps_need_a_full_header() {
+        let mut bitmap = b"BM".to_vec();
+        assert_eq!(ImageFormat::sniff(&bitmap), None);
+        bitmap.resize(26, 0);
+        assert_eq!(ImageFormat::sniff(&bitmap), Some(ImageFormat::Bmp));
+    }
 }
@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::exams::fetch_exam_with_topics;
+use crate::commands::images::{get_app_data_dir, import_image, read_image};
+use crate::commands::questions::{
+    fetch_question_with_details, insert_question, QuestionWithDetails,
+};
+use crate::commands::settings::load_image_settings;
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use crate::formats::anki::{self, ExportDeck};
+use crate::formats::csv::{self, CsvImportOptions};
+use crate::formats::moodle_xml::{self, ExportCategory};
+use crate::formats::qti::{self, ExportSection, ExportTest};
+use crate::formats::{gift, ExportIssue, ExportReport, ImportIssue, ImportReport, ParsedQuestion};
+use rusqlite::OptionalExtension;
+use tauri::{AppHandle, State};
+
+/// Import a Moodle GIFT file into a topic. Every question that can be represented is
+/// added in one transaction; the report lists, by line, everything that was not.
+#[tauri::command]
+pub fn import_gift(
+    db: State<DbConnection>,
+    topic_id: i64,
+    path: String,
+) -> AppResult<ImportReport> {
+    let text = read_text_file(&path)?;
+
+    db.transaction(|tx| {
+        let subject_id = topic_subject_id(tx, topic_id)?;
+        let (questions, issues) = gift::parse(&text, subject_id, topic_id);
+        insert_parsed_questions(tx, questions, issues)
+    })
+}
+
+/// Import the rows of a CSV/TSV file (or a Quizlet export) into a topic.
+///
+/// With `dry_run` every row is validated and inserted inside a transaction that is
+/// then rolled back, so the report lists the rows that would fail without changing
+/// anything. Otherwise the valid rows are added in one transaction.
+#[tauri::command]
+pub fn import_csv(
+    db: State<DbConnection>,
+    topic_id: i64,
+    path: String,
+    options: CsvImportOptions,
+    dry_run: bool,
+) -> AppResult<ImportReport> {
+    if let Some(default_type) = options.default_type.as_deref() {
+        if !csv::is_known_type(default_type) {
+            return Err(AppError::validation(
+                format!("Unknown question type: {}", default_type),
+                &["defaultType"],
+            ));
+        }
+    }
+    let text = read_text_file(&path)?;
+
+    let mut conn = db.0.lock()?;
+    let tx = conn.transaction()?;
+    let subject_id = topic_subject_id(&tx, topic_id)?;
+    let (questions, issues) = csv::parse(&text, &options, subject_id, topic_id)
+        .map_err(|message| AppError::validation(message, &["path"]))?;
+    let mut report = insert_parsed_questions(&tx, questions, issues)?;
+
+    if dry_run {
+        // Dropping the transaction rolls it back; the ids were never kept
+        report.question_ids.clear();
+    } else {
+        tx.commit()?;
+    }
+
+    Ok(report)
+}
+
+/// Export the questions of a topic as a Moodle GIFT file
+#[tauri::command]
+pub fn export_gift(
+    db: State<DbConnection>,
+    topic_id: i64,
+    path: String,
+) -> AppResult<ExportReport> {
+    let conn = db.0.lock()?;
+
+    let (subject_name, topic_name): (String, String) = conn
+        .query_row(
+            "SELECT s.name, t.name FROM topics t JOIN subjects s ON t.subject_id = s.id WHERE t.id = ?",
+            [topic_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found(format!("Topic {} not found", topic_id)))?;
+
+    // Moodle files the questions under this category; `/` in a name is written as `//`
+    let mut items = vec![format!(
+        "// Exported from QuizForge\n$CATEGORY: $course$/{}/{}",
+        subject_name.replace('/', "//"),
+        topic_name.replace('/', "//")
+    )];
+    let mut report = ExportReport {
+        exported: 0,
+        issues: Vec::new(),
+    };
+
+    for question in topic_questions(&conn, topic_id)? {
+        let question_id = question.question.id;
+        match gift::write_question(&question) {
+            Ok((item, notes)) => {
+                items.push(item);
+                report.exported += 1;
+                report
+                    .issues
+                    .extend(notes.into_iter().map(|message| ExportIssue {
+                        question_id,
+                        message,
+                        skipped: false,
+                    }));
+            }
+            Err(message) => report.issues.push(ExportIssue {
+                question_id,
+                message,
+                skipped: true,
+            }),
+        }
+    }
+
+    let mut contents = items.join("\n\n");
+    contents.push('\n');
+    fs::write(&path, contents)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+
+    Ok(report)
+}
+
+/// Import a Moodle XML file. With `topic_id` every question goes into that topic;
+/// without it each question goes to the subject and topic named by its Moodle category
+/// (`Subject/Topic`), which are created when missing. Embedded images are copied into
+/// the asset store.
+#[tauri::command]
+pub fn import_moodle_xml(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    path: String,
+    topic_id: Option<i64>,
+) -> AppResult<ImportReport> {
+    let text = read_text_file(&path)?;
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let settings = load_image_settings(&*db.0.lock()?)?;
+
+    let (questions, mut issues) = moodle_xml::parse(&text, &mut |name, data| {
+        import_image(&app_data_dir, &settings, name, data)
+    })?;
+
+    db.transaction(|tx| {
+        let chosen_topic = match topic_id {
+            Some(topic_id) => Some((topic_subject_id(tx, topic_id)?, topic_id)),
+            None => None,
+        };
+        let mut topics: HashMap<Vec<String>, (i64, i64)> = HashMap::new();
+        let mut parsed = Vec::with_capacity(questions.len());
+
+        for categorized in questions {
+            let mut question = categorized.question;
+            let (subject_id, topic_id) = match chosen_topic {
+                Some(ids) => ids,
+                None if categorized.category.is_empty() => {
+                    issues.push(ImportIssue {
+                        line: question.line,
+                        message: "Question is not in a category and no topic was chosen"
+                            .to_string(),
+                        skipped: true,
+                    });
+                    continue;
+                }
+                None => match topics.get(&categorized.category) {
+                    Some(ids) => *ids,
+                    None => {
+                        let ids = find_or_create_topic(tx, &categorized.category)?;
+                        topics.insert(categorized.category, ids);
+                        ids
+                    }
+                },
+            };
+            question.data.subject_id = subject_id;
+            question.data.topic_id = topic_id;
+            parsed.push(question);
+        }
+
+        insert_parsed_questions(tx, parsed, issues)
+    })
+}
+
+/// Export a subject (one category per topic) or a single topic as Moodle XML,
+/// embedding the images the questions use
+#[tauri::command]
+pub fn export_moodle_xml(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    subject_id: Option<i64>,
+    topic_id: Option<i64>,
+    path: String,
+) -> AppResult<ExportReport> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let conn = db.0.lock()?;
+    let topics = export_topics(&conn, subject_id, topic_id)?;
+
+    let mut categories = Vec::with_capacity(topics.len());
+    for (topic_id, subject_name, topic_name) in topics {
+        categories.push(ExportCategory {
+            path: vec![subject_name, topic_name],
+            questions: topic_questions(&conn, topic_id)?,
+        });
+    }
+
+    let (xml, report) = moodle_xml::write_quiz(&categories, &mut |image_path| {
+        read_image(&app_data_dir, image_path)
+    });
+    fs::write(&path, xml).map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+
+    Ok(report)
+}
+
+/// Import the items of an IMS QTI 2.1 content package into a topic. Images the items
+/// use are copied into the asset store.
+#[tauri::command]
+pub fn import_qti_package(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    topic_id: i64,
+    path: String,
+) -> AppResult<ImportReport> {
+    let file = fs::File::open(&path)
+        .map_err(|e| AppError::io(format!("Failed to read {}: {}", path, e)))?;
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    let (subject_id, settings) = {
+        let conn = db.0.lock()?;
+        (
+            topic_subject_id(&conn, topic_id)?,
+            load_image_settings(&conn)?,
+        )
+    };
+    let (mut questions, issues) = qti::read_package(file, &mut |name, data| {
+        import_image(&app_data_dir, &settings, name, data)
+    })?;
+    for question in &mut questions {
+        question.data.subject_id = subject_id;
+        question.data.topic_id = topic_id;
+    }
+
+    db.transaction(|tx| insert_parsed_questions(tx, questions, issues))
+}
+
+/// Export a subject (one test section per topic) or a single topic as an IMS QTI 2.1
+/// content package
+#[tauri::command]
+pub fn export_qti_package(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    subject_id: Option<i64>,
+    topic_id: Option<i64>,
+    path: String,
+) -> AppResult<ExportReport> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let conn = db.0.lock()?;
+    let topics = export_topics(&conn, subject_id, topic_id)?;
+
+    let title = match topic_id {
+        Some(_) => format!("{} - {}", topics[0].1, topics[0].2),
+        None => topics[0].1.clone(),
+    };
+    let mut sections = Vec::with_capacity(topics.len());
+    for (topic_id, _, topic_name) in topics {
+        sections.push(ExportSection {
+            title: topic_name,
+            select: None,
+            questions: topic_questions(&conn, topic_id)?,
+        });
+    }
+    let test = ExportTest {
+        title,
+        time_limit_minutes: None,
+        shuffle_questions: false,
+        shuffle_choices: true,
+        sections,
+    };
+
+    write_qti_package(&app_data_dir, &test, &path)
+}
+
+/// Export an exam as an IMS QTI 2.1 assessment test. Each `exam_topics` row becomes a
+/// section that draws its question count from all of the topic's questions.
+#[tauri::command]
+pub fn export_exam_qti_package(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    exam_id: i64,
+    path: String,
+) -> AppResult<ExportReport> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let conn = db.0.lock()?;
+    let exam = fetch_exam_with_topics(&conn, exam_id)?;
+
+    let mut sections = Vec::with_capacity(exam.topics.len());
+    for topic in exam.topics {
+        sections.push(ExportSection {
+            title: topic.topic_name,
+            select: Some(topic.question_count.max(0) as usize),
+            questions: topic_questions(&conn, topic.topic_id)?,
+        });
+    }
+    let test = ExportTest {
+        title: exam.name,
+        time_limit_minutes: exam.time_limit_minutes,
+        shuffle_questions: exam.shuffle_questions,
+        shuffle_choices: exam.shuffle_options,
+        sections,
+    };
+
+    write_qti_package(&app_data_dir, &test, &path)
+}
+
+fn write_qti_package(
+    app_data_dir: &Path,
+    test: &ExportTest,
+    path: &str,
+) -> AppResult<ExportReport> {
+    let file = fs::File::create(path)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+    qti::write_package(file, test, &mut |image_path| {
+        read_image(app_data_dir, image_path)
+    })
+}
+
+/// Export a subject or a single topic as an Anki package: the subject is a deck, each
+/// topic a sub-deck and each question a note, with the images bundled as media
+#[tauri::command]
+pub fn export_anki(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    subject_id: Option<i64>,
+    topic_id: Option<i64>,
+    path: String,
+) -> AppResult<ExportReport> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let conn = db.0.lock()?;
+    let topics = export_topics(&conn, subject_id, topic_id)?;
+
+    let mut decks = Vec::with_capacity(topics.len());
+    for (topic_id, subject_name, topic_name) in topics {
+        decks.push(ExportDeck {
+            path: vec![subject_name, topic_name],
+            questions: topic_questions(&conn, topic_id)?,
+        });
+    }
+    drop(conn);
+
+    let file = fs::File::create(&path)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+    anki::write_package(file, &decks, &std::env::temp_dir(), &mut |image_path| {
+        read_image(&app_data_dir, image_path)
+    })
+}
+
+/// The topics a subject or topic export covers, as `(topic id, subject name, topic name)`
+fn export_topics(
+    conn: &rusqlite::Connection,
+    subject_id: Option<i64>,
+    topic_id: Option<i64>,
+) -> AppResult<Vec<(i64, String, String)>> {
+    if subject_id.is_none() && topic_id.is_none() {
+        return Err(AppError::validation(
+            "Either a subject or a topic is required",
+            &["subjectId", "topicId"],
+        ));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id, s.name, t.name FROM topics t
+         JOIN subjects s ON t.subject_id = s.id
+         WHERE (?1 IS NULL OR t.subject_id = ?1) AND (?2 IS NULL OR t.id = ?2)
+         ORDER BY t.week_number IS NULL, t.week_number, t.name",
+    )?;
+    let topics = stmt
+        .query_map((subject_id, topic_id), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if topics.is_empty() {
+        return Err(AppError::not_found("No topics to export"));
+    }
+    Ok(topics)
+}
+
+/// Find the topic for a `Subject/Topic` category path, creating the subject and topic
+/// when needed. Deeper paths join the remaining levels into the topic name; a
+/// single-level path uses a "General" topic.
+fn find_or_create_topic(conn: &rusqlite::Connection, category: &[String]) -> AppResult<(i64, i64)> {
+    let subject_name = &category[0];
+    let topic_name = if category.len() > 1 {
+        category[1..].join(" / ")
+    } else {
+        "General".to_string()
+    };
+
+    let subject_id = match conn
+        .query_row(
+            "SELECT id FROM subjects WHERE name = ? ORDER BY id LIMIT 1",
+            [subject_name],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+    {
+        Some(id) => id,
+        None => {
+            conn.execute("INSERT INTO subjects (name) VALUES (?)", [subject_name])?;
+            conn.last_insert_rowid()
+        }
+    };
+
+    let topic_id = match conn
+        .query_row(
+            "SELECT id FROM topics WHERE subject_id = ?1 AND name = ?2 ORDER BY id LIMIT 1",
+            (subject_id, &topic_name),
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+    {
+        Some(id) => id,
+        None => {
+            conn.execute(
+                "INSERT INTO topics (subject_id, name) VALUES (?1, ?2)",
+                (subject_id, &topic_name),
+            )?;
+            conn.last_insert_rowid()
+        }
+    };
+
+    Ok((subject_id, topic_id))
+}
+
+fn read_text_file(path: &str) -> AppResult<String> {
+    fs::read_to_string(path).map_err(|e| AppError::io(format!("Failed to read {}: {}", path, e)))
+}
+
+fn topic_subject_id(conn: &rusqlite::Connection, topic_id: i64) -> AppResult<i64> {
+    conn.query_row(
+        "SELECT subject_id FROM topics WHERE id = ?",
+        [topic_id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found(format!("Topic {} not found", topic_id)))
+}
+
+/// Every question in a topic, oldest first so exports keep the authoring order
+fn topic_questions(
+    conn: &rusqlite::Connection,
+    topic_id: i64,
+) -> AppResult<Vec<QuestionWithDetails>> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM questions WHERE topic_id = ? ORDER BY created_at ASC, id ASC")?;
+
+    let ids = stmt
+        .query_map([topic_id], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    ids.into_iter()
+        .map(|id| fetch_question_with_details(conn, id))
+        .collect()
+}
+
+/// Insert parsed questions through the same path as `create_question`. Each question
+/// goes in under its own savepoint, so one that fails (rejected by the editor's checks
+/// or by a constraint) is rolled back and reported against its line while the rest are
+/// kept.
+fn insert_parsed_questions(
+    conn: &rusqlite::Connection,
+    questions: Vec<ParsedQuestion>,
+    mut issues: Vec<ImportIssue>,
+) -> AppResult<ImportReport> {
+    let mut question_ids = Vec::with_capacity(questions.len());
+
+    for parsed in questions {
+        conn.execute_batch("SAVEPOINT import_question")?;
+        match insert_question(conn, &parsed.data) {
+            Ok(id) => {
+                conn.execute_batch("RELEASE import_question")?;
+                question_ids.push(id);
+            }
+            Err(e) => {
+                conn.execute_batch("ROLLBACK TO import_question; RELEASE import_question")?;
+                issues.push(ImportIssue {
+                    line: parsed.line,
+                    message: e.message().to_string(),
+                    skipped: true,
+                });
+            }
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.line);
+
+    Ok(ImportReport {
+        imported: question_ids.len(),
+        question_ids,
+        issues,
+    })
+}
@@ -0,0 +1,861 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, State};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::commands::images::{get_app_data_dir, read_image, store_image, MAX_IMAGE_BYTES};
+use crate::commands::tags::TagQuery;
+use crate::db::migrations::latest_version;
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+
+/// Identifies a library archive, so other zip files are rejected with a clear message
+const LIBRARY_FORMAT: &str = "quizforge-library";
+
+/// Version of the archive layout. Bump it when the manifest changes shape;
+/// schema changes are covered by `schemaVersion`.
+const LIBRARY_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// Largest manifest read back; it holds every row of the library, images aside
+const MAX_MANIFEST_BYTES: u64 = 256 * 1024 * 1024;
+
+/// A table copied into the archive.
+///
+/// `references` lists the columns holding ids of rows in other tables, which are
+/// renumbered on import; `image_columns` hold asset paths, which are re-stored.
+struct TableSpec {
+    name: &'static str,
+    references: &'static [(&'static str, &'static str)],
+    image_columns: &'static [&'static str],
+    /// Learner history, left out unless attempts are exported
+    history: bool,
+}
+
+/// Every table of the library, parents before the tables that reference them
+const TABLES: &[TableSpec] = &[
+    TableSpec {
+        name: "subjects",
+        references: &[],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "tags",
+        references: &[],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "topics",
+        references: &[("subject_id", "subjects")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "questions",
+        references: &[("subject_id", "subjects"), ("topic_id", "topics")],
+        image_columns: &["question_image_path"],
+        history: false,
+    },
+    TableSpec {
+        name: "question_options",
+        references: &[("question_id", "questions")],
+        image_columns: &["option_image_path"],
+        history: false,
+    },
+    TableSpec {
+        name: "question_blanks",
+        references: &[("question_id", "questions")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "question_matches",
+        references: &[("question_id", "questions")],
+        image_columns: &["left_image_path", "right_image_path"],
+        history: false,
+    },
+    TableSpec {
+        name: "question_order_items",
+        references: &[("question_id", "questions")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "question_tags",
+        references: &[("question_id", "questions"), ("tag_id", "tags")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "question_revisions",
+        references: &[("question_id", "questions")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "quizzes",
+        references: &[("topic_id", "topics")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "exams",
+        references: &[("subject_id", "subjects")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "exam_topics",
+        references: &[("exam_id", "exams"), ("topic_id", "topics")],
+        image_columns: &[],
+        history: false,
+    },
+    TableSpec {
+        name: "quiz_attempts",
+        references: &[("quiz_id", "quizzes")],
+        image_columns: &[],
+        history: true,
+    },
+    TableSpec {
+        name: "quiz_attempt_questions",
+        references: &[
+            ("attempt_id", "quiz_attempts"),
+            ("question_id", "questions"),
+        ],
+        image_columns: &[],
+        history: true,
+    },
+    TableSpec {
+        name: "attempt_responses",
+        references: &[
+            ("attempt_id", "quiz_attempts"),
+            ("question_id", "questions"),
+        ],
+        image_columns: &[],
+        history: true,
+    },
+    TableSpec {
+        name: "exam_attempts",
+        references: &[("exam_id", "exams")],
+        image_columns: &[],
+        history: true,
+    },
+    TableSpec {
+        name: "exam_attempt_questions",
+        references: &[
+            ("attempt_id", "exam_attempts"),
+            ("question_id", "questions"),
+        ],
+        image_columns: &[],
+        history: true,
+    },
+    TableSpec {
+        name: "exam_responses",
+        references: &[
+            ("attempt_id", "exam_attempts"),
+            ("question_id", "questions"),
+        ],
+        image_columns: &[],
+        history: true,
+    },
+    TableSpec {
+        name: "review_schedule",
+        references: &[("question_id", "questions")],
+        image_columns: &[],
+        history: true,
+    },
+];
+
+/// `manifest.json`: every row of the library keyed by table, with column names as
+/// they are in the database, and the archive entry holding each referenced image
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LibraryManifest {
+    format: String,
+    version: u32,
+    /// `PRAGMA user_version` of the database the rows came from
+    schema_version: i32,
+    exported_at: String,
+    includes_attempts: bool,
+    tables: BTreeMap<String, Vec<Map<String, Value>>>,
+    /// Image path as stored in the database -> archive entry
+    images: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryReport {
+    /// Rows written per table
+    pub rows: BTreeMap<String, usize>,
+    pub images: usize,
+    /// Image paths referenced by the library whose file could not be found
+    pub missing_images: Vec<String>,
+    /// Rows left out because a row they reference was not in the archive
+    pub skipped_rows: usize,
+}
+
+/// Write every subject, topic, question, quiz and exam, and the images they use, to a
+/// zip archive that `import_library` can read on another machine. Attempt history and
+/// review schedules are included unless `include_attempts` is `false`.
+#[tauri::command]
+pub fn export_library(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    path: String,
+    include_attempts: Option<bool>,
+) -> AppResult<LibraryReport> {
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+    let include_attempts = include_attempts.unwrap_or(true);
+    let conn = db.0.lock()?;
+
+    let file = fs::File::create(&path)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+    write_library(&conn, include_attempts, file, &mut |image_path| {
+        read_image(&app_data_dir, image_path)
+    })
+}
+
+/// Import a library archive written by `export_library`.
+///
+/// `mode` is `"replace"` to delete the current library first or `"merge"` to add the
+/// archive's contents next to it. Either way every row gets a new id, references
+/// between rows are renumbered and images are copied into the asset store byte for
+/// byte (the image settings are not applied), where an image that is already there is
+/// reused. Nothing is changed if the import fails part-way.
+#[tauri::command]
+pub fn import_library(
+    app_handle: AppHandle,
+    db: State<DbConnection>,
+    path: String,
+    mode: String,
+) -> AppResult<LibraryReport> {
+    let replace = match mode.as_str() {
+        "replace" => true,
+        "merge" => false,
+        _ => {
+            return Err(AppError::validation(
+                format!("Unknown import mode: {}", mode),
+                &["mode"],
+            ))
+        }
+    };
+    let app_data_dir = get_app_data_dir(&app_handle)?;
+
+    let file = fs::File::open(&path)
+        .map_err(|e| AppError::io(format!("Failed to read {}: {}", path, e)))?;
+
+    db.transaction(|tx| {
+        read_library(tx, replace, file, &mut |name, data| {
+            store_image(&app_data_dir, name, data)
+        })
+    })
+}
+
+fn write_library<W: Write + Seek>(
+    conn: &Connection,
+    include_attempts: bool,
+    writer: W,
+    load_image: &mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+) -> AppResult<LibraryReport> {
+    let mut manifest = LibraryManifest {
+        format: LIBRARY_FORMAT.to_string(),
+        version: LIBRARY_VERSION,
+        schema_version: conn.pragma_query_value(None, "user_version", |row| row.get(0))?,
+        exported_at: conn.query_row("SELECT datetime('now')", [], |row| row.get(0))?,
+        includes_attempts: include_attempts,
+        tables: BTreeMap::new(),
+        images: BTreeMap::new(),
+    };
+    let mut report = LibraryReport {
+        rows: BTreeMap::new(),
+        images: 0,
+        missing_images: Vec::new(),
+        skipped_rows: 0,
+    };
+    let mut zip = ZipWriter::new(writer);
+
+    for table in TABLES.iter().filter(|t| include_attempts || !t.history) {
+        let rows = table_rows(conn, table.name)?;
+
+        for row in &rows {
+            let mut image_paths: Vec<&str> = table
+                .image_columns
+                .iter()
+                .filter_map(|column| row.get(*column).and_then(Value::as_str))
+                .collect();
+            // Old revisions can use images the question no longer has
+            let snapshot = row
+                .get("snapshot")
+                .and_then(Value::as_str)
+                .and_then(|text| serde_json::from_str::<Value>(text).ok());
+            if let Some(snapshot) = &snapshot {
+                snapshot_image_paths(snapshot, &mut image_paths);
+            }
+
+            for image_path in image_paths {
+                if image_path.is_empty() || manifest.images.contains_key(image_path) {
+                    continue;
+                }
+                match load_image(image_path) {
+                    Ok(data) => {
+                        let file_name = Path::new(image_path)
+                            .file_name()
+                            .and_then(|name| name.to_str())
+                            .unwrap_or("image");
+                        // Prefix with a counter so images from different folders cannot collide
+                        let entry = format!("images/{}_{}", manifest.images.len() + 1, file_name);
+                        add_file(&mut zip, &entry, &data)?;
+                        manifest.images.insert(image_path.to_string(), entry);
+                        report.images += 1;
+                    }
+                    Err(_) => {
+                        if !report.missing_images.iter().any(|p| p == image_path) {
+                            report.missing_images.push(image_path.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        report.rows.insert(table.name.to_string(), rows.len());
+        manifest.tables.insert(table.name.to_string(), rows);
+    }
+
+    add_file(
+        &mut zip,
+        MANIFEST_NAME,
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    zip.finish()
+        .map_err(|e| AppError::io(format!("Failed to write library archive: {}", e)))?;
+
+    Ok(report)
+}
+
+fn read_library<R: Read + Seek>(
+    conn: &Connection,
+    replace: bool,
+    reader: R,
+    store_image: &mut dyn FnMut(&str, &[u8]) -> AppResult<String>,
+) -> AppResult<LibraryReport> {
+    let mut archive = ZipArchive::new(reader).map_err(|e| {
+        AppError::validation(format!("Not a valid library archive: {}", e), &["path"])
+    })?;
+    let manifest: LibraryManifest = {
+        let data = read_entry(&mut archive, MANIFEST_NAME, MAX_MANIFEST_BYTES)?;
+        serde_json::from_slice(&data).map_err(|e| {
+            AppError::validation(format!("Invalid library manifest: {}", e), &["path"])
+        })?
+    };
+
+    if manifest.format != LIBRARY_FORMAT {
+        return Err(AppError::validation(
+            "Not a QuizForge library archive",
+            &["path"],
+        ));
+    }
+    if manifest.version > LIBRARY_VERSION || manifest.schema_version > latest_version() {
+        return Err(AppError::validation(
+            "This library was exported by a newer version of QuizForge. Please update QuizForge.",
+            &["path"],
+        ));
+    }
+
+    let mut report = LibraryReport {
+        rows: BTreeMap::new(),
+        images: 0,
+        missing_images: Vec::new(),
+        skipped_rows: 0,
+    };
+
+    // Copy the images first so the rows can point at their new paths
+    let mut image_paths: HashMap<String, String> = HashMap::new();
+    for (image_path, entry) in &manifest.images {
+        match read_entry(&mut archive, entry, MAX_IMAGE_BYTES) {
+            Ok(data) => {
+                let file_name = Path::new(image_path)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("image");
+                image_paths.insert(image_path.clone(), store_image(file_name, &data)?);
+                report.images += 1;
+            }
+            Err(_) => report.missing_images.push(image_path.clone()),
+        }
+    }
+
+    if replace {
+        for table in TABLES.iter().rev() {
+            conn.execute(&format!("DELETE FROM {}", table.name), [])?;
+        }
+    }
+
+    // Old id -> new id, per table
+    let mut ids: HashMap<&str, HashMap<i64, i64>> = HashMap::new();
+    // Old question id -> question type, to renumber the ids inside stored responses
+    let mut question_types: HashMap<i64, String> = HashMap::new();
+
+    for table in TABLES {
+        let Some(rows) = manifest.tables.get(table.name) else {
+            continue;
+        };
+        let columns = table_columns(conn, table.name)?;
+        let mut table_ids = HashMap::with_capacity(rows.len());
+
+        'rows: for row in rows {
+            let old_id = row.get("id").and_then(Value::as_i64);
+            let mut values: Vec<(&str, SqlValue)> = Vec::with_capacity(row.len());
+
+            for column in &columns {
+                let Some(value) = row.get(column.as_str()) else {
+                    continue;
+                };
+                let mut value = value.clone();
+
+                if let Some((_, referenced)) = table.references.iter().find(|(c, _)| c == column) {
+                    let new_id = value
+                        .as_i64()
+                        .and_then(|old| ids.get(referenced).and_then(|m| m.get(&old)));
+                    match new_id {
+                        Some(new_id) => value = Value::from(*new_id),
+                        None => {
+                            report.skipped_rows += 1;
+                            continue 'rows;
+                        }
+                    }
+                } else if table.image_columns.contains(&column.as_str()) {
+                    if let Some(new_path) = value.as_str().and_then(|p| image_paths.get(p)) {
+                        value = Value::from(new_path.as_str());
+                    }
+                } else if column == "response_data" || column == "option_order" {
+                    let question_type = row
+                        .get("question_id")
+                        .and_then(Value::as_i64)
+                        .and_then(|id| question_types.get(&id))
+                        .map_or("", String::as_str);
+                    value = remap_response(&value, question_type, column == "option_order", &ids);
+                } else if column == "tag_query" {
+                    value = remap_tag_query(&value, &ids);
+                } else if column == "snapshot" {
+                    value = remap_snapshot(&value, &ids, &image_paths);
+                }
+
+                values.push((column.as_str(), to_sql_value(value)));
+            }
+
+            // Tag names are unique, so a merged tag joins the existing one of that name
+            let existing_tag: Option<i64> = if table.name == "tags" {
+                conn.query_row(
+                    "SELECT id FROM tags WHERE name = ?",
+                    [row.get("name").and_then(Value::as_str).unwrap_or("")],
+                    |row| row.get(0),
+                )
+                .optional()?
+            } else {
+                None
+            };
+
+            let new_id = match existing_tag {
+                Some(tag_id) => tag_id,
+                None => {
+                    let names: Vec<&str> = values.iter().map(|(name, _)| *name).collect();
+                    let placeholders = vec!["?"; values.len()].join(", ");
+                    conn.execute(
+                        &format!(
+                            "INSERT INTO {} ({}) VALUES ({})",
+                            table.name,
+                            names.join(", "),
+                            placeholders
+                        ),
+                        rusqlite::params_from_iter(values.into_iter().map(|(_, value)| value)),
+                    )?;
+                    conn.last_insert_rowid()
+                }
+            };
+
+            if let Some(old_id) = old_id {
+                table_ids.insert(old_id, new_id);
+                if table.name == "questions" {
+                    if let Some(question_type) = row.get("question_type").and_then(Value::as_str) {
+                        question_types.insert(old_id, question_type.to_string());
+                    }
+                }
+            }
+        }
+
+        report.rows.insert(table.name.to_string(), table_ids.len());
+        ids.insert(table.name, table_ids);
+    }
+
+    Ok(report)
+}
+
+/// Every row of `table`, oldest first, keyed by column name
+fn table_rows(conn: &Connection, table: &str) -> AppResult<Vec<Map<String, Value>>> {
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {} ORDER BY id", table))?;
+    let names: Vec<String> = stmt
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+    let mut rows = stmt.query([])?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut map = Map::with_capacity(names.len());
+        for (index, name) in names.iter().enumerate() {
+            let value = match row.get_ref(index)? {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(i) => Value::from(i),
+                ValueRef::Real(f) => Value::from(f),
+                ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text).into_owned()),
+                ValueRef::Blob(_) => {
+                    return Err(AppError::database(format!(
+                        "Unexpected binary value in {}.{}",
+                        table, name
+                    )))
+                }
+            };
+            map.insert(name.clone(), value);
+        }
+        out.push(map);
+    }
+
+    Ok(out)
+}
+
+/// The columns `table` has in this database, other than `id`. Columns the archive has
+/// but this schema lacks are dropped; ones the archive lacks take their defaults.
+fn table_columns(conn: &Connection, table: &str) -> AppResult<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(columns.into_iter().filter(|c| c != "id").collect())
+}
+
+/// Renumber the option, order item and match ids inside a stored response
+/// (`response_data`) or a shown option order (`option_order`). Both are JSON text.
+pub fn remap_response(
+    value: &Value,
+    question_type: &str,
+    option_order: bool,
+    ids: &HashMap<&str, HashMap<i64, i64>>,
+) -> Value {
+    let Some(text) = value.as_str() else {
+        return value.clone();
+    };
+    let Ok(mut data) = serde_json::from_str::<Value>(text) else {
+        return value.clone();
+    };
+
+    let table = match question_type {
+        _ if option_order => "question_options",
+        "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION" => "question_options",
+        "ORDERING" => "question_order_items",
+        "MATCHING" => "question_matches",
+        // Blank answers are text, not ids
+        _ => return value.clone(),
+    };
+    let Some(map) = ids.get(table) else {
+        return value.clone();
+    };
+    let remap_id = |value: &mut Value| match value {
+        Value::Number(number) => {
+            if let Some(new_id) = number.as_i64().and_then(|id| map.get(&id)) {
+                *value = Value::from(*new_id);
+            }
+        }
+        Value::String(text) => {
+            if let Some(new_id) = text.trim().parse::<i64>().ok().and_then(|id| map.get(&id)) {
+                *value = Value::from(new_id.to_string());
+            }
+        }
+        _ => {}
+    };
+
+    match &mut data {
+        Value::Array(items) => items.iter_mut().for_each(remap_id),
+        Value::Object(pairs) => {
+            let old = std::mem::take(pairs);
+            for (left, mut right) in old {
+                remap_id(&mut right);
+                let left = left
+                    .trim()
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|id| map.get(&id))
+                    .map_or(left, |new_id| new_id.to_string());
+                pairs.insert(left, right);
+            }
+        }
+        other => remap_id(other),
+    }
+
+    Value::from(data.to_string())
+}
+
+/// The image paths in a revision snapshot: every text value under a key ending in
+/// `ImagePath`, as the `asset_references` view finds them
+fn snapshot_image_paths<'a>(value: &'a Value, paths: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                match value.as_str() {
+                    Some(path) if key.ends_with("ImagePath") => paths.push(path),
+                    _ => snapshot_image_paths(value, paths),
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| snapshot_image_paths(item, paths)),
+        _ => {}
+    }
+}
+
+/// Renumber the ids inside a question revision `snapshot` (JSON text) and point its
+/// images at their new paths, so the snapshot agrees with the remapped responses that
+/// were given against it. Ids of rows the archive did not have are left as they are,
+/// as `remap_response` leaves them.
+fn remap_snapshot(
+    value: &Value,
+    ids: &HashMap<&str, HashMap<i64, i64>>,
+    image_paths: &HashMap<String, String>,
+) -> Value {
+    let Some(text) = value.as_str() else {
+        return value.clone();
+    };
+    let Ok(Value::Object(mut snapshot)) = serde_json::from_str::<Value>(text) else {
+        return value.clone();
+    };
+
+    let remap_fields = |fields: &mut Map<String, Value>, id_table: &str| {
+        for (key, value) in fields.iter_mut() {
+            let table = match key.as_str() {
+                "id" => id_table,
+                "questionId" => "questions",
+                "subjectId" => "subjects",
+                "topicId" => "topics",
+                _ => {
+                    if key.ends_with("ImagePath") {
+                        if let Some(new_path) = value.as_str().and_then(|p| image_paths.get(p)) {
+                            *value = Value::from(new_path.as_str());
+                        }
+                    }
+                    continue;
+                }
+            };
+            let new_id = value.as_i64().and_then(|old| ids.get(table)?.get(&old));
+            if let Some(new_id) = new_id {
+                *value = Value::from(*new_id);
+            }
+        }
+    };
+
+    remap_fields(&mut snapshot, "questions");
+    for (key, table) in [
+        ("options", "question_options"),
+        ("blanks", "question_blanks"),
+        ("orderItems", "question_order_items"),
+        ("matches", "question_matches"),
+        ("tags", "tags"),
+    ] {
+        if let Some(Value::Array(rows)) = snapshot.get_mut(key) {
+            for row in rows.iter_mut().filter_map(Value::as_object_mut) {
+                remap_fields(row, table);
+            }
+        }
+    }
+
+    Value::from(Value::Object(snapshot).to_string())
+}
+
+/// Renumber the tag ids inside a quiz's `tag_query` (JSON text)
+fn remap_tag_query(value: &Value, ids: &HashMap<&str, HashMap<i64, i64>>) -> Value {
+    let (Some(text), Some(tags)) = (value.as_str(), ids.get("tags")) else {
+        return value.clone();
+    };
+    let Ok(mut query) = serde_json::from_str::<TagQuery>(text) else {
+        return value.clone();
+    };
+
+    for tag_ids in [&mut query.all, &mut query.any, &mut query.none] {
+        for tag_id in tag_ids.iter_mut() {
+            if let Some(new_id) = tags.get(tag_id) {
+                *tag_id = *new_id;
+            }
+        }
+    }
+
+    serde_json::to_string(&query).map_or_else(|_| value.clone(), Value::from)
+}
+
+fn to_sql_value(value: Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(b as i64),
+        Value::Number(number) => match number.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(text) => SqlValue::Text(text),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// Read an entry of at most `limit` bytes. The size in the zip directory can be
+/// forged, so the read itself is capped as well.
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    limit: u64,
+) -> AppResult<Vec<u8>> {
+    let too_large = |size: u64| {
+        AppError::file_too_large(
+            format!("{} is larger than {} MB", name, limit / (1024 * 1024)),
+            size,
+            limit,
+        )
+    };
+
+    let entry = archive.by_name(name).map_err(|_| {
+        AppError::validation(format!("{} is missing from the archive", name), &["path"])
+    })?;
+    if entry.size() > limit {
+        return Err(too_large(entry.size()));
+    }
+    let mut data = Vec::new();
+    entry.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(too_large(data.len() as u64));
+    }
+    Ok(data)
+}
+
+fn add_file<W: Write + Seek>(zip: &mut ZipWriter<W>, path: &str, data: &[u8]) -> AppResult<()> {
+    zip.start_file(path, SimpleFileOptions::default())
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+    zip.write_all(data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::commands::questions::{apply_question_update, insert_question};
+    use crate::commands::quizzes::fetch_quiz_attempt_detail;
+    use crate::commands::revisions::current_revision;
+    use crate::db::test_connection;
+
+    #[test]
+    fn merged_library_keeps_attempts_on_edited_questions() {
+        let conn = test_connection();
+        conn.execute_batch(
+            "INSERT INTO subjects (name) VALUES ('S');
+             INSERT INTO topics (subject_id, name) VALUES (1, 'T');
+             INSERT INTO quizzes (topic_id, name, question_count) VALUES (1, 'Q', 1);",
+        )
+        .unwrap();
+
+        let question_id = insert_question(
+            &conn,
+            &serde_json::from_value(json!({
+                "subjectId": 1, "topicId": 1, "questionType": "SINGLE_CHOICE",
+                "questionText": "Before", "difficulty": "EASY", "points": 1,
+                "options": [
+                    { "optionText": "A", "optionImagePath": "images/old.png", "isCorrect": true, "displayOrder": 0 },
+                    { "optionText": "B", "isCorrect": false, "displayOrder": 1 },
+                ],
+                "blanks": [],
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let revision = current_revision(&conn, question_id).unwrap();
+        let chosen: i64 = conn
+            .query_row(
+                "SELECT id FROM question_options WHERE question_id = ? AND option_text = 'A'",
+                [question_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        conn.execute(
+            "INSERT INTO quiz_attempts
+                (quiz_id, completed_at, score, max_score, percentage, time_taken_seconds)
+             VALUES (1, datetime('now'), 1, 1, 100, 30)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO attempt_responses
+                (attempt_id, question_id, response_data, is_correct, points_earned, revision)
+             VALUES (1, ?1, ?2, 1, 1, ?3)",
+            (question_id, format!("\"{}\"", chosen), revision),
+        )
+        .unwrap();
+
+        // The edit drops the image, so only the snapshot still refers to it
+        apply_question_update(
+            &conn,
+            question_id,
+            &serde_json::from_value(json!({
+                "questionText": "After", "difficulty": "EASY", "points": 1,
+                "options": [
+                    { "optionText": "A2", "isCorrect": true, "displayOrder": 0 },
+                    { "optionText": "B2", "isCorrect": false, "displayOrder": 1 },
+                ],
+                "blanks": [],
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let mut archive = Cursor::new(Vec::new());
+        let exported =
+            write_library(&conn, true, &mut archive, &mut |_| Ok(b"png".to_vec())).unwrap();
+        assert_eq!(exported.images, 1);
+
+        archive.set_position(0);
+        read_library(&conn, false, archive, &mut |name, _| {
+            Ok(format!("images/new_{}", name))
+        })
+        .unwrap();
+
+        let imported_attempt: i64 = conn
+            .query_row("SELECT MAX(id) FROM quiz_attempts", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(imported_attempt, 1);
+        let detail = fetch_quiz_attempt_detail(&conn, imported_attempt).unwrap();
+        let response = &detail.responses[0];
+        assert_eq!(response.question.question.question_text, "Before");
+
+        let answered = response.answer.as_ref().unwrap().ids();
+        let option = response
+            .question
+            .options
+            .iter()
+            .find(|option| option.id == answered[0])
+            .expect("the answer refers to an option of the answered revision");
+        assert_eq!(option.option_text, "A");
+        assert_eq!(
+            option.option_image_path.as_deref(),
+            Some("images/new_old.png")
+        );
+    }
+}
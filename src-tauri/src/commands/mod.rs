@@ -1,6 +1,14 @@
-pub mod subjects;
-pub mod topics;
-pub mod questions;
-pub mod quizzes;
+pub mod analysis;
+pub mod duplicates;
 pub mod exams;
 pub mod images;
+pub mod interchange;
+pub mod library;
+pub mod questions;
+pub mod quizzes;
+pub mod reviews;
+pub mod revisions;
+pub mod settings;
+pub mod subjects;
+pub mod tags;
+pub mod topics;
@@ -1,6 +1,10 @@
+use crate::commands::revisions::{current_revision, record_revision};
+use crate::commands::tags::{get_question_tags, set_question_tags, Tag, TagQuery};
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use crate::formats::escape_xml;
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use crate::db::DbConnection;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -66,7 +70,7 @@ pub struct QuestionMatch {
     pub display_order: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct QuestionWithDetails {
     #[serde(flatten)]
@@ -75,6 +79,8 @@ pub struct QuestionWithDetails {
     pub blanks: Vec<QuestionBlank>,
     pub order_items: Vec<QuestionOrderItem>,
     pub matches: Vec<QuestionMatch>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -94,6 +100,9 @@ pub struct CreateQuestionData {
     pub numeric_data: Option<CreateNumericData>,
     pub order_items: Option<Vec<CreateOrderItem>>,
     pub match_pairs: Option<Vec<CreateMatchPair>>,
+    /// Tag names; tags that do not exist yet are created
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -156,25 +165,47 @@ pub struct UpdateQuestionData {
     pub numeric_data: Option<CreateNumericData>,
     pub order_items: Option<Vec<CreateOrderItem>>,
     pub match_pairs: Option<Vec<CreateMatchPair>>,
+    /// Tag names replacing the current tags; `None` leaves them as they are
+    pub tags: Option<Vec<String>>,
 }
 
+/// List the questions of a topic, of the questions matching `tags`, or of the
+/// questions of a topic that match `tags`
 #[tauri::command]
 pub fn get_questions(
     db: State<DbConnection>,
-    topic_id: i64,
-) -> Result<Vec<QuestionWithDetails>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    topic_id: Option<i64>,
+    tags: Option<TagQuery>,
+) -> AppResult<Vec<QuestionWithDetails>> {
+    let conn = db.0.lock()?;
+
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    if let Some(topic_id) = topic_id {
+        conditions.push("topic_id = ?".to_string());
+        params.push(topic_id);
+    }
+    if let Some(tags) = &tags {
+        let (condition, tag_params) = tags.condition("questions.id");
+        conditions.push(condition);
+        params.extend(tag_params);
+    }
+    if topic_id.is_none() && !tags.as_ref().is_some_and(TagQuery::includes_tags) {
+        return Err(AppError::validation(
+            "Choose a topic or at least one tag to include",
+            &["topicId", "tags"],
+        ));
+    }
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, subject_id, topic_id, question_type, question_text, question_image_path,
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, subject_id, topic_id, question_type, question_text, question_image_path,
              explanation, difficulty, points, source, created_at, updated_at
-             FROM questions WHERE topic_id = ? ORDER BY created_at DESC",
-        )
-        .map_err(|e| e.to_string())?;
+             FROM questions WHERE {} ORDER BY created_at DESC",
+        conditions.join(" AND ")
+    ))?;
 
     let questions = stmt
-        .query_map([topic_id], |row| {
+        .query_map(rusqlite::params_from_iter(params), |row| {
             Ok(Question {
                 id: row.get(0)?,
                 subject_id: row.get(1)?,
@@ -189,10 +220,8 @@ pub fn get_questions(
                 created_at: row.get(10)?,
                 updated_at: row.get(11)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     // For each question, fetch its options, blanks, order_items, and matches
     let mut questions_with_details = Vec::new();
@@ -201,12 +230,14 @@ pub fn get_questions(
         let blanks = get_question_blanks(&conn, question.id)?;
         let order_items = get_question_order_items(&conn, question.id)?;
         let matches = get_question_matches(&conn, question.id)?;
+        let tags = get_question_tags(&conn, question.id)?;
         questions_with_details.push(QuestionWithDetails {
             question,
             options,
             blanks,
             order_items,
             matches,
+            tags,
         });
     }
 
@@ -214,269 +245,337 @@ pub fn get_questions(
 }
 
 #[tauri::command]
-pub fn get_question(db: State<DbConnection>, id: i64) -> Result<QuestionWithDetails, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_question(db: State<DbConnection>, id: i64) -> AppResult<QuestionWithDetails> {
+    let conn = db.0.lock()?;
 
-    let question = conn
-        .query_row(
-            "SELECT id, subject_id, topic_id, question_type, question_text, question_image_path,
-             explanation, difficulty, points, source, created_at, updated_at
-             FROM questions WHERE id = ?",
-            [id],
+    fetch_question_with_details(&conn, id)
+}
+
+/// Search results shown when no limit is given
+const DEFAULT_SEARCH_LIMIT: i64 = 50;
+
+/// Marks matched terms in snippets; replaced by `<mark>` once the snippet is escaped
+const MATCH_START: char = '\u{E000}';
+const MATCH_END: char = '\u{E001}';
+
+/// A question matching a search, with the passage that matched
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuestionSearchResult {
+    #[serde(flatten)]
+    pub question: QuestionWithDetails,
+    /// HTML-escaped text around the best match, with matched terms in `<mark>` tags
+    pub snippet: String,
+    /// Relevance; higher is better
+    pub score: f64,
+}
+
+/// Search question text, explanations, sources, options, blank answers and match
+/// items. Every word of `query` must appear (as a word or the start of one); results
+/// are ranked with matches in the question text counting most.
+#[tauri::command]
+pub fn search_questions(
+    db: State<DbConnection>,
+    query: String,
+    subject_id: Option<i64>,
+    topic_id: Option<i64>,
+    question_type: Option<String>,
+    difficulty: Option<String>,
+    limit: Option<i64>,
+) -> AppResult<Vec<QuestionSearchResult>> {
+    let Some(match_query) = fts_query(&query) else {
+        return Ok(Vec::new());
+    };
+    let conn = db.0.lock()?;
+
+    // Column weights follow the column order of `question_search`
+    let mut stmt = conn.prepare(
+        "SELECT q.id, snippet(question_search, -1, ?7, ?8, '…', 16),
+         bm25(question_search, 10.0, 2.0, 1.0, 4.0, 4.0, 4.0) AS rank
+         FROM question_search JOIN questions q ON q.id = question_search.rowid
+         WHERE question_search MATCH ?1
+           AND (?2 IS NULL OR q.subject_id = ?2)
+           AND (?3 IS NULL OR q.topic_id = ?3)
+           AND (?4 IS NULL OR q.question_type = ?4)
+           AND (?5 IS NULL OR q.difficulty = ?5)
+         ORDER BY rank
+         LIMIT ?6",
+    )?;
+    let matches = stmt
+        .query_map(
+            rusqlite::params![
+                match_query,
+                subject_id,
+                topic_id,
+                question_type,
+                difficulty,
+                limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+                MATCH_START.to_string(),
+                MATCH_END.to_string(),
+            ],
             |row| {
-                Ok(Question {
-                    id: row.get(0)?,
-                    subject_id: row.get(1)?,
-                    topic_id: row.get(2)?,
-                    question_type: row.get(3)?,
-                    question_text: row.get(4)?,
-                    question_image_path: row.get(5)?,
-                    explanation: row.get(6)?,
-                    difficulty: row.get(7)?,
-                    points: row.get(8)?,
-                    source: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
             },
-        )
-        .map_err(|e| e.to_string())?;
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    matches
+        .into_iter()
+        .map(|(id, snippet, rank)| {
+            Ok(QuestionSearchResult {
+                question: fetch_question_with_details(&conn, id)?,
+                snippet: highlight(&snippet),
+                // bm25 is lower for better matches
+                score: -rank,
+            })
+        })
+        .collect()
+}
 
-    let options = get_question_options(&conn, id)?;
-    let blanks = get_question_blanks(&conn, id)?;
-    let order_items = get_question_order_items(&conn, id)?;
-    let matches = get_question_matches(&conn, id)?;
+/// Turn what the user typed into an FTS5 query: each word becomes a quoted prefix
+/// term, so punctuation and FTS operators in the input are taken literally
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
 
-    Ok(QuestionWithDetails {
-        question,
-        options,
-        blanks,
-        order_items,
-        matches,
-    })
+fn highlight(snippet: &str) -> String {
+    escape_xml(snippet)
+        .replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
 }
 
 #[tauri::command]
 pub fn create_question(
     db: State<DbConnection>,
     data: CreateQuestionData,
-) -> Result<QuestionWithDetails, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+) -> AppResult<QuestionWithDetails> {
+    db.transaction(|tx| {
+        let question_id = insert_question(tx, &data)?;
 
-    // Insert the question
-    conn.execute(
-        "INSERT INTO questions (subject_id, topic_id, question_type, question_text,
-         question_image_path, explanation, difficulty, points, source)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        // Fetch and return the created question with details
+        fetch_question_with_details(tx, question_id)
+    })
+}
+
+#[tauri::command]
+pub fn update_question(
+    db: State<DbConnection>,
+    id: i64,
+    data: UpdateQuestionData,
+) -> AppResult<QuestionWithDetails> {
+    db.transaction(|tx| apply_question_update(tx, id, &data))
+}
+
+/// Overwrite a question and its details, saving the result as a new revision.
+/// Call inside a transaction so a failure part-way leaves nothing behind.
+pub fn apply_question_update(
+    tx: &rusqlite::Connection,
+    id: i64,
+    data: &UpdateQuestionData,
+) -> AppResult<QuestionWithDetails> {
+    validate_question_fields(&data.question_text, &data.difficulty, data.points)?;
+
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM questions WHERE id = ?)",
+        [id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::not_found(format!("Question {} not found", id)));
+    }
+
+    // Keep the version being replaced if it predates revision tracking
+    current_revision(tx, id)?;
+
+    // Update the question
+    tx.execute(
+        "UPDATE questions SET question_text = ?1, question_image_path = ?2, explanation = ?3,
+         difficulty = ?4, points = ?5, source = ?6, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?7",
         (
-            &data.subject_id,
-            &data.topic_id,
-            &data.question_type,
             &data.question_text,
             &data.question_image_path,
             &data.explanation,
             &data.difficulty,
             &data.points,
             &data.source,
+            id,
         ),
-    )
-    .map_err(|e| e.to_string())?;
-
-    let question_id = conn.last_insert_rowid();
+    )?;
 
-    // Insert options if any
-    for option in &data.options {
-        conn.execute(
-            "INSERT INTO question_options (question_id, option_text, option_image_path, is_correct, display_order)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            (
-                question_id,
-                &option.option_text,
-                &option.option_image_path,
-                option.is_correct as i32,
-                option.display_order,
-            ),
-        )
-        .map_err(|e| e.to_string())?;
+    // Delete existing options, blanks, order_items, and matches
+    tx.execute("DELETE FROM question_options WHERE question_id = ?", [id])?;
+    tx.execute("DELETE FROM question_blanks WHERE question_id = ?", [id])?;
+    tx.execute(
+        "DELETE FROM question_order_items WHERE question_id = ?",
+        [id],
+    )?;
+    tx.execute("DELETE FROM question_matches WHERE question_id = ?", [id])?;
+
+    insert_question_details(
+        tx,
+        id,
+        &data.options,
+        &data.blanks,
+        data.numeric_data.as_ref(),
+        data.order_items.as_deref(),
+        data.match_pairs.as_deref(),
+    )?;
+
+    if let Some(tags) = &data.tags {
+        set_question_tags(tx, id, tags)?;
     }
+    record_revision(tx, id)?;
 
-    // Insert blanks if any
-    for blank in &data.blanks {
-        conn.execute(
-            "INSERT INTO question_blanks (question_id, blank_index, correct_answer, acceptable_answers,
-             is_numeric, numeric_tolerance, unit, input_type, dropdown_options)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            (
-                question_id,
-                blank.blank_index,
-                &blank.correct_answer,
-                &blank.acceptable_answers,
-                blank.is_numeric as i32,
-                blank.numeric_tolerance,
-                &blank.unit,
-                &blank.input_type,
-                &blank.dropdown_options,
-            ),
-        )
-        .map_err(|e| e.to_string())?;
-    }
+    // Fetch and return the updated question with details
+    fetch_question_with_details(tx, id)
+}
 
-    // Insert numeric data as a blank if present
-    if let Some(numeric_data) = &data.numeric_data {
-        let tolerance: f64 = numeric_data.tolerance.parse().unwrap_or(0.1);
-        conn.execute(
-            "INSERT INTO question_blanks (question_id, blank_index, correct_answer, acceptable_answers,
-             is_numeric, numeric_tolerance, unit, input_type, dropdown_options)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            (
-                question_id,
-                0,
-                &numeric_data.correct_answer,
-                None::<String>,
-                1,
-                Some(tolerance),
-                &numeric_data.unit,
-                "INPUT",
-                None::<String>,
-            ),
-        )
-        .map_err(|e| e.to_string())?;
-    }
+#[tauri::command]
+pub fn delete_question(db: State<DbConnection>, id: i64) -> AppResult<()> {
+    let conn = db.0.lock()?;
 
-    // Insert order items if any
-    if let Some(order_items) = &data.order_items {
-        for item in order_items {
-            conn.execute(
-                "INSERT INTO question_order_items (question_id, item_text, correct_position)
-                 VALUES (?1, ?2, ?3)",
-                (
-                    question_id,
-                    &item.text,
-                    item.correct_position,
-                ),
-            )
-            .map_err(|e| e.to_string())?;
-        }
-    }
+    // Enable foreign keys for cascade deletes
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
 
-    // Insert match pairs if any
-    if let Some(match_pairs) = &data.match_pairs {
-        for (index, pair) in match_pairs.iter().enumerate() {
-            conn.execute(
-                "INSERT INTO question_matches (question_id, left_item, right_item, left_image_path, right_image_path, display_order)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                (
-                    question_id,
-                    &pair.left_item,
-                    &pair.right_item,
-                    &pair.left_image_path,
-                    &pair.right_image_path,
-                    index as i32,
-                ),
-            )
-            .map_err(|e| e.to_string())?;
-        }
-    }
+    // SQLite CASCADE will handle deleting related options and blanks
+    conn.execute("DELETE FROM questions WHERE id = ?", [id])?;
 
-    // Fetch and return the created question with details
-    let question = conn
-        .query_row(
-            "SELECT id, subject_id, topic_id, question_type, question_text, question_image_path,
-             explanation, difficulty, points, source, created_at, updated_at
-             FROM questions WHERE id = ?",
-            [question_id],
-            |row| {
-                Ok(Question {
-                    id: row.get(0)?,
-                    subject_id: row.get(1)?,
-                    topic_id: row.get(2)?,
-                    question_type: row.get(3)?,
-                    question_text: row.get(4)?,
-                    question_image_path: row.get(5)?,
-                    explanation: row.get(6)?,
-                    difficulty: row.get(7)?,
-                    points: row.get(8)?,
-                    source: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    let options = get_question_options(&conn, question_id)?;
-    let blanks = get_question_blanks(&conn, question_id)?;
-    let order_items = get_question_order_items(&conn, question_id)?;
-    let matches = get_question_matches(&conn, question_id)?;
+// Helper functions
 
-    Ok(QuestionWithDetails {
-        question,
-        options,
-        blanks,
-        order_items,
-        matches,
-    })
+/// Question types accepted by the `questions.question_type` check constraint
+pub const QUESTION_TYPES: &[&str] = &[
+    "SINGLE_CHOICE",
+    "MULTIPLE_CHOICE",
+    "FILL_BLANK",
+    "FILL_BLANK_MULTIPLE",
+    "NUMERIC_INPUT",
+    "MATCHING",
+    "ORDERING",
+    "IMAGE_IDENTIFICATION",
+    "CALCULATION",
+];
+
+/// Difficulties accepted by the `questions.difficulty` check constraint
+pub const DIFFICULTIES: &[&str] = &["EASY", "MEDIUM", "HARD"];
+
+/// Reject question fields the editor must fix before saving
+fn validate_question_fields(question_text: &str, difficulty: &str, points: i32) -> AppResult<()> {
+    if question_text.trim().is_empty() {
+        return Err(AppError::validation(
+            "Question text is required",
+            &["questionText"],
+        ));
+    }
+    if !DIFFICULTIES.contains(&difficulty) {
+        return Err(AppError::validation(
+            format!("Unknown difficulty: {}", difficulty),
+            &["difficulty"],
+        ));
+    }
+    if points < 0 {
+        return Err(AppError::validation(
+            "Points cannot be negative",
+            &["points"],
+        ));
+    }
+
+    Ok(())
 }
 
-#[tauri::command]
-pub fn update_question(
-    db: State<DbConnection>,
-    id: i64,
-    data: UpdateQuestionData,
-) -> Result<QuestionWithDetails, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+/// Insert a question and its details, returning the new id.
+/// Call inside a transaction so a failure part-way leaves nothing behind.
+pub fn insert_question(conn: &rusqlite::Connection, data: &CreateQuestionData) -> AppResult<i64> {
+    if !QUESTION_TYPES.contains(&data.question_type.as_str()) {
+        return Err(AppError::validation(
+            format!("Unknown question type: {}", data.question_type),
+            &["questionType"],
+        ));
+    }
+    validate_question_fields(&data.question_text, &data.difficulty, data.points)?;
 
-    // Update the question
+    // Insert the question
     conn.execute(
-        "UPDATE questions SET question_text = ?1, question_image_path = ?2, explanation = ?3,
-         difficulty = ?4, points = ?5, source = ?6, updated_at = CURRENT_TIMESTAMP
-         WHERE id = ?7",
+        "INSERT INTO questions (subject_id, topic_id, question_type, question_text,
+         question_image_path, explanation, difficulty, points, source)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         (
+            &data.subject_id,
+            &data.topic_id,
+            &data.question_type,
             &data.question_text,
             &data.question_image_path,
             &data.explanation,
             &data.difficulty,
             &data.points,
             &data.source,
-            id,
         ),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
-    // Delete existing options, blanks, order_items, and matches
-    conn.execute("DELETE FROM question_options WHERE question_id = ?", [id])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM question_blanks WHERE question_id = ?", [id])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM question_order_items WHERE question_id = ?", [id])
-        .map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM question_matches WHERE question_id = ?", [id])
-        .map_err(|e| e.to_string())?;
-
-    // Insert new options
-    for option in &data.options {
+    let question_id = conn.last_insert_rowid();
+
+    insert_question_details(
+        conn,
+        question_id,
+        &data.options,
+        &data.blanks,
+        data.numeric_data.as_ref(),
+        data.order_items.as_deref(),
+        data.match_pairs.as_deref(),
+    )?;
+    set_question_tags(conn, question_id, &data.tags)?;
+    record_revision(conn, question_id)?;
+
+    Ok(question_id)
+}
+
+/// Insert the options, blanks, order items and match pairs of a question
+fn insert_question_details(
+    conn: &rusqlite::Connection,
+    question_id: i64,
+    options: &[CreateQuestionOption],
+    blanks: &[CreateQuestionBlank],
+    numeric_data: Option<&CreateNumericData>,
+    order_items: Option<&[CreateOrderItem]>,
+    match_pairs: Option<&[CreateMatchPair]>,
+) -> AppResult<()> {
+    // Insert options if any
+    for option in options {
         conn.execute(
             "INSERT INTO question_options (question_id, option_text, option_image_path, is_correct, display_order)
              VALUES (?1, ?2, ?3, ?4, ?5)",
             (
-                id,
+                question_id,
                 &option.option_text,
                 &option.option_image_path,
                 option.is_correct as i32,
                 option.display_order,
             ),
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
-    // Insert new blanks
-    for blank in &data.blanks {
+    // Insert blanks if any
+    for blank in blanks {
         conn.execute(
             "INSERT INTO question_blanks (question_id, blank_index, correct_answer, acceptable_answers,
              is_numeric, numeric_tolerance, unit, input_type, dropdown_options)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             (
-                id,
+                question_id,
                 blank.blank_index,
                 &blank.correct_answer,
                 &blank.acceptable_answers,
@@ -486,19 +585,18 @@ pub fn update_question(
                 &blank.input_type,
                 &blank.dropdown_options,
             ),
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     // Insert numeric data as a blank if present
-    if let Some(numeric_data) = &data.numeric_data {
+    if let Some(numeric_data) = numeric_data {
         let tolerance: f64 = numeric_data.tolerance.parse().unwrap_or(0.1);
         conn.execute(
             "INSERT INTO question_blanks (question_id, blank_index, correct_answer, acceptable_answers,
              is_numeric, numeric_tolerance, unit, input_type, dropdown_options)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             (
-                id,
+                question_id,
                 0,
                 &numeric_data.correct_answer,
                 None::<String>,
@@ -508,75 +606,70 @@ pub fn update_question(
                 "INPUT",
                 None::<String>,
             ),
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     // Insert order items if any
-    if let Some(order_items) = &data.order_items {
-        for item in order_items {
-            conn.execute(
-                "INSERT INTO question_order_items (question_id, item_text, correct_position)
-                 VALUES (?1, ?2, ?3)",
-                (
-                    id,
-                    &item.text,
-                    item.correct_position,
-                ),
-            )
-            .map_err(|e| e.to_string())?;
-        }
+    for item in order_items.unwrap_or_default() {
+        conn.execute(
+            "INSERT INTO question_order_items (question_id, item_text, correct_position)
+             VALUES (?1, ?2, ?3)",
+            (question_id, &item.text, item.correct_position),
+        )?;
     }
 
     // Insert match pairs if any
-    if let Some(match_pairs) = &data.match_pairs {
-        for (index, pair) in match_pairs.iter().enumerate() {
-            conn.execute(
-                "INSERT INTO question_matches (question_id, left_item, right_item, left_image_path, right_image_path, display_order)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                (
-                    id,
-                    &pair.left_item,
-                    &pair.right_item,
-                    &pair.left_image_path,
-                    &pair.right_image_path,
-                    index as i32,
-                ),
-            )
-            .map_err(|e| e.to_string())?;
-        }
+    for (index, pair) in match_pairs.unwrap_or_default().iter().enumerate() {
+        conn.execute(
+            "INSERT INTO question_matches (question_id, left_item, right_item, left_image_path, right_image_path, display_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                question_id,
+                &pair.left_item,
+                &pair.right_item,
+                &pair.left_image_path,
+                &pair.right_image_path,
+                index as i32,
+            ),
+        )?;
     }
 
-    // Fetch and return the updated question with details
-    let question = conn
-        .query_row(
-            "SELECT id, subject_id, topic_id, question_type, question_text, question_image_path,
+    Ok(())
+}
+
+/// Load a question together with its options, blanks, order items and matches
+pub fn fetch_question_with_details(
+    conn: &rusqlite::Connection,
+    id: i64,
+) -> AppResult<QuestionWithDetails> {
+    let question = conn.query_row(
+        "SELECT id, subject_id, topic_id, question_type, question_text, question_image_path,
              explanation, difficulty, points, source, created_at, updated_at
              FROM questions WHERE id = ?",
-            [id],
-            |row| {
-                Ok(Question {
-                    id: row.get(0)?,
-                    subject_id: row.get(1)?,
-                    topic_id: row.get(2)?,
-                    question_type: row.get(3)?,
-                    question_text: row.get(4)?,
-                    question_image_path: row.get(5)?,
-                    explanation: row.get(6)?,
-                    difficulty: row.get(7)?,
-                    points: row.get(8)?,
-                    source: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+        [id],
+        |row| {
+            Ok(Question {
+                id: row.get(0)?,
+                subject_id: row.get(1)?,
+                topic_id: row.get(2)?,
+                question_type: row.get(3)?,
+                question_text: row.get(4)?,
+                question_image_path: row.get(5)?,
+                explanation: row.get(6)?,
+                difficulty: row.get(7)?,
+                points: row.get(8)?,
+                source: row.get(9)?,
+                created_at: row.get(10)?,
+                updated_at: row.get(11)?,
+            })
+        },
+    )?;
 
-    let options = get_question_options(&conn, id)?;
-    let blanks = get_question_blanks(&conn, id)?;
-    let order_items = get_question_order_items(&conn, id)?;
-    let matches = get_question_matches(&conn, id)?;
+    let options = get_question_options(conn, id)?;
+    let blanks = get_question_blanks(conn, id)?;
+    let order_items = get_question_order_items(conn, id)?;
+    let matches = get_question_matches(conn, id)?;
+    let tags = get_question_tags(conn, id)?;
 
     Ok(QuestionWithDetails {
         question,
@@ -584,35 +677,18 @@ pub fn update_question(
         blanks,
         order_items,
         matches,
+        tags,
     })
 }
 
-#[tauri::command]
-pub fn delete_question(db: State<DbConnection>, id: i64) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-
-    // Enable foreign keys for cascade deletes
-    conn.execute("PRAGMA foreign_keys = ON", [])
-        .map_err(|e| e.to_string())?;
-
-    // SQLite CASCADE will handle deleting related options and blanks
-    conn.execute("DELETE FROM questions WHERE id = ?", [id])
-        .map_err(|e| e.to_string())?;
-
-    Ok(())
-}
-
-// Helper functions
 fn get_question_options(
     conn: &rusqlite::Connection,
     question_id: i64,
-) -> Result<Vec<QuestionOption>, String> {
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, question_id, option_text, option_image_path, is_correct, display_order
+) -> AppResult<Vec<QuestionOption>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, question_id, option_text, option_image_path, is_correct, display_order
              FROM question_options WHERE question_id = ? ORDER BY display_order ASC",
-        )
-        .map_err(|e| e.to_string())?;
+    )?;
 
     let options = stmt
         .query_map([question_id], |row| {
@@ -624,10 +700,8 @@ fn get_question_options(
                 is_correct: row.get::<_, i32>(4)? != 0,
                 display_order: row.get(5)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(options)
 }
@@ -635,14 +709,12 @@ fn get_question_options(
 fn get_question_blanks(
     conn: &rusqlite::Connection,
     question_id: i64,
-) -> Result<Vec<QuestionBlank>, String> {
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, question_id, blank_index, correct_answer, acceptable_answers,
+) -> AppResult<Vec<QuestionBlank>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, question_id, blank_index, correct_answer, acceptable_answers,
              is_numeric, numeric_tolerance, unit, input_type, dropdown_options
              FROM question_blanks WHERE question_id = ? ORDER BY blank_index ASC",
-        )
-        .map_err(|e| e.to_string())?;
+    )?;
 
     let blanks = stmt
         .query_map([question_id], |row| {
@@ -655,13 +727,13 @@ fn get_question_blanks(
                 is_numeric: row.get::<_, i32>(5)? != 0,
                 numeric_tolerance: row.get(6)?,
                 unit: row.get(7)?,
-                input_type: row.get::<_, Option<String>>(8)?.unwrap_or_else(|| "INPUT".to_string()),
+                input_type: row
+                    .get::<_, Option<String>>(8)?
+                    .unwrap_or_else(|| "INPUT".to_string()),
                 dropdown_options: row.get(9)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(blanks)
 }
@@ -669,13 +741,11 @@ fn get_question_blanks(
 fn get_question_order_items(
     conn: &rusqlite::Connection,
     question_id: i64,
-) -> Result<Vec<QuestionOrderItem>, String> {
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, question_id, item_text, correct_position
+) -> AppResult<Vec<QuestionOrderItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, question_id, item_text, correct_position
              FROM question_order_items WHERE question_id = ? ORDER BY correct_position ASC",
-        )
-        .map_err(|e| e.to_string())?;
+    )?;
 
     let items = stmt
         .query_map([question_id], |row| {
@@ -685,10 +755,8 @@ fn get_question_order_items(
                 item_text: row.get(2)?,
                 correct_position: row.get(3)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(items)
 }
@@ -696,13 +764,12 @@ fn get_question_order_items(
 fn get_question_matches(
     conn: &rusqlite::Connection,
     question_id: i64,
-) -> Result<Vec<QuestionMatch>, String> {
+) -> AppResult<Vec<QuestionMatch>> {
     let mut stmt = conn
         .prepare(
             "SELECT id, question_id, left_item, right_item, left_image_path, right_image_path, display_order
              FROM question_matches WHERE question_id = ? ORDER BY display_order ASC",
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     let matches = stmt
         .query_map([question_id], |row| {
@@ -715,10 +782,8 @@ fn get_question_matches(
                 right_image_path: row.get(5)?,
                 display_order: row.get(6)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(matches)
 }
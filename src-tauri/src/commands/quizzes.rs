@@ -1,6 +1,16 @@
+use crate::commands::questions::{fetch_question_with_details, QuestionWithDetails};
+use crate::commands::revisions::{answered_question, current_revision};
+use crate::commands::tags::{tagged_question_ids, TagQuery};
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use crate::grading::{
+    grade_served, AttemptResult, GradedResponse, ResponseData, ResponseDetail, SubmittedResponse,
+};
+use crate::random::{fresh_seed, SeededRng};
+use crate::scheduling::record_responses;
+use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use crate::db::DbConnection;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -15,6 +25,9 @@ pub struct Quiz {
     pub shuffle_options: bool,
     pub show_answers_after: String,
     pub passing_score_percent: i32,
+    /// When set, questions are drawn from every question matching these tags
+    /// instead of from the topic the quiz belongs to
+    pub tag_query: Option<TagQuery>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -31,6 +44,8 @@ pub struct CreateQuizData {
     pub shuffle_options: bool,
     pub show_answers_after: String,
     pub passing_score_percent: i32,
+    #[serde(default)]
+    pub tag_query: Option<TagQuery>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,20 +59,20 @@ pub struct UpdateQuizData {
     pub shuffle_options: bool,
     pub show_answers_after: String,
     pub passing_score_percent: i32,
+    #[serde(default)]
+    pub tag_query: Option<TagQuery>,
 }
 
 #[tauri::command]
-pub fn get_quizzes(db: State<DbConnection>, topic_id: i64) -> Result<Vec<Quiz>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_quizzes(db: State<DbConnection>, topic_id: i64) -> AppResult<Vec<Quiz>> {
+    let conn = db.0.lock()?;
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
+    let mut stmt = conn.prepare(
+        "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
              shuffle_questions, shuffle_options, show_answers_after, passing_score_percent,
-             created_at, updated_at
+             tag_query, created_at, updated_at
              FROM quizzes WHERE topic_id = ? ORDER BY created_at DESC",
-        )
-        .map_err(|e| e.to_string())?;
+    )?;
 
     let quizzes = stmt
         .query_map([topic_id], |row| {
@@ -72,58 +87,57 @@ pub fn get_quizzes(db: State<DbConnection>, topic_id: i64) -> Result<Vec<Quiz>,
                 shuffle_options: row.get::<_, i32>(7)? != 0,
                 show_answers_after: row.get(8)?,
                 passing_score_percent: row.get(9)?,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
+                tag_query: row.get(10)?,
+                created_at: row.get(11)?,
+                updated_at: row.get(12)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(quizzes)
 }
 
 #[tauri::command]
-pub fn get_quiz(db: State<DbConnection>, id: i64) -> Result<Quiz, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_quiz(db: State<DbConnection>, id: i64) -> AppResult<Quiz> {
+    let conn = db.0.lock()?;
 
-    let quiz = conn
-        .query_row(
-            "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
+    let quiz = conn.query_row(
+        "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
              shuffle_questions, shuffle_options, show_answers_after, passing_score_percent,
-             created_at, updated_at
+             tag_query, created_at, updated_at
              FROM quizzes WHERE id = ?",
-            [id],
-            |row| {
-                Ok(Quiz {
-                    id: row.get(0)?,
-                    topic_id: row.get(1)?,
-                    name: row.get(2)?,
-                    description: row.get(3)?,
-                    question_count: row.get(4)?,
-                    time_limit_minutes: row.get(5)?,
-                    shuffle_questions: row.get::<_, i32>(6)? != 0,
-                    shuffle_options: row.get::<_, i32>(7)? != 0,
-                    show_answers_after: row.get(8)?,
-                    passing_score_percent: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+        [id],
+        |row| {
+            Ok(Quiz {
+                id: row.get(0)?,
+                topic_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                question_count: row.get(4)?,
+                time_limit_minutes: row.get(5)?,
+                shuffle_questions: row.get::<_, i32>(6)? != 0,
+                shuffle_options: row.get::<_, i32>(7)? != 0,
+                show_answers_after: row.get(8)?,
+                passing_score_percent: row.get(9)?,
+                tag_query: row.get(10)?,
+                created_at: row.get(11)?,
+                updated_at: row.get(12)?,
+            })
+        },
+    )?;
 
     Ok(quiz)
 }
 
 #[tauri::command]
-pub fn create_quiz(db: State<DbConnection>, data: CreateQuizData) -> Result<Quiz, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn create_quiz(db: State<DbConnection>, data: CreateQuizData) -> AppResult<Quiz> {
+    validate_tag_query(data.tag_query.as_ref())?;
+    let conn = db.0.lock()?;
 
     conn.execute(
         "INSERT INTO quizzes (topic_id, name, description, question_count, time_limit_minutes,
-         shuffle_questions, shuffle_options, show_answers_after, passing_score_percent)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+         shuffle_questions, shuffle_options, show_answers_after, passing_score_percent, tag_query)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
         (
             data.topic_id,
             &data.name,
@@ -134,54 +148,51 @@ pub fn create_quiz(db: State<DbConnection>, data: CreateQuizData) -> Result<Quiz
             data.shuffle_options as i32,
             &data.show_answers_after,
             data.passing_score_percent,
+            &data.tag_query,
         ),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     let id = conn.last_insert_rowid();
 
-    let quiz = conn
-        .query_row(
-            "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
+    let quiz = conn.query_row(
+        "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
              shuffle_questions, shuffle_options, show_answers_after, passing_score_percent,
-             created_at, updated_at
+             tag_query, created_at, updated_at
              FROM quizzes WHERE id = ?",
-            [id],
-            |row| {
-                Ok(Quiz {
-                    id: row.get(0)?,
-                    topic_id: row.get(1)?,
-                    name: row.get(2)?,
-                    description: row.get(3)?,
-                    question_count: row.get(4)?,
-                    time_limit_minutes: row.get(5)?,
-                    shuffle_questions: row.get::<_, i32>(6)? != 0,
-                    shuffle_options: row.get::<_, i32>(7)? != 0,
-                    show_answers_after: row.get(8)?,
-                    passing_score_percent: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+        [id],
+        |row| {
+            Ok(Quiz {
+                id: row.get(0)?,
+                topic_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                question_count: row.get(4)?,
+                time_limit_minutes: row.get(5)?,
+                shuffle_questions: row.get::<_, i32>(6)? != 0,
+                shuffle_options: row.get::<_, i32>(7)? != 0,
+                show_answers_after: row.get(8)?,
+                passing_score_percent: row.get(9)?,
+                tag_query: row.get(10)?,
+                created_at: row.get(11)?,
+                updated_at: row.get(12)?,
+            })
+        },
+    )?;
 
     Ok(quiz)
 }
 
 #[tauri::command]
-pub fn update_quiz(
-    db: State<DbConnection>,
-    id: i64,
-    data: UpdateQuizData,
-) -> Result<Quiz, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn update_quiz(db: State<DbConnection>, id: i64, data: UpdateQuizData) -> AppResult<Quiz> {
+    validate_tag_query(data.tag_query.as_ref())?;
+    let conn = db.0.lock()?;
 
     conn.execute(
         "UPDATE quizzes SET name = ?1, description = ?2, question_count = ?3,
          time_limit_minutes = ?4, shuffle_questions = ?5, shuffle_options = ?6,
-         show_answers_after = ?7, passing_score_percent = ?8, updated_at = CURRENT_TIMESTAMP
-         WHERE id = ?9",
+         show_answers_after = ?7, passing_score_percent = ?8, tag_query = ?9,
+         updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?10",
         (
             &data.name,
             &data.description,
@@ -191,50 +202,47 @@ pub fn update_quiz(
             data.shuffle_options as i32,
             &data.show_answers_after,
             data.passing_score_percent,
+            &data.tag_query,
             id,
         ),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
-    let quiz = conn
-        .query_row(
-            "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
+    let quiz = conn.query_row(
+        "SELECT id, topic_id, name, description, question_count, time_limit_minutes,
              shuffle_questions, shuffle_options, show_answers_after, passing_score_percent,
-             created_at, updated_at
+             tag_query, created_at, updated_at
              FROM quizzes WHERE id = ?",
-            [id],
-            |row| {
-                Ok(Quiz {
-                    id: row.get(0)?,
-                    topic_id: row.get(1)?,
-                    name: row.get(2)?,
-                    description: row.get(3)?,
-                    question_count: row.get(4)?,
-                    time_limit_minutes: row.get(5)?,
-                    shuffle_questions: row.get::<_, i32>(6)? != 0,
-                    shuffle_options: row.get::<_, i32>(7)? != 0,
-                    show_answers_after: row.get(8)?,
-                    passing_score_percent: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+        [id],
+        |row| {
+            Ok(Quiz {
+                id: row.get(0)?,
+                topic_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                question_count: row.get(4)?,
+                time_limit_minutes: row.get(5)?,
+                shuffle_questions: row.get::<_, i32>(6)? != 0,
+                shuffle_options: row.get::<_, i32>(7)? != 0,
+                show_answers_after: row.get(8)?,
+                passing_score_percent: row.get(9)?,
+                tag_query: row.get(10)?,
+                created_at: row.get(11)?,
+                updated_at: row.get(12)?,
+            })
+        },
+    )?;
 
     Ok(quiz)
 }
 
 #[tauri::command]
-pub fn delete_quiz(db: State<DbConnection>, id: i64) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn delete_quiz(db: State<DbConnection>, id: i64) -> AppResult<()> {
+    let conn = db.0.lock()?;
 
     // Enable foreign keys for cascade deletes
-    conn.execute("PRAGMA foreign_keys = ON", [])
-        .map_err(|e| e.to_string())?;
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
 
-    conn.execute("DELETE FROM quizzes WHERE id = ?", [id])
-        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM quizzes WHERE id = ?", [id])?;
 
     Ok(())
 }
@@ -249,7 +257,7 @@ pub struct QuizAttemptWithDetails {
     pub subject_name: String,
     pub started_at: String,
     pub completed_at: String,
-    pub score: i32,
+    pub score: f64,
     pub max_score: i32,
     pub percentage: f64,
     pub time_taken_seconds: i32,
@@ -257,8 +265,8 @@ pub struct QuizAttemptWithDetails {
 }
 
 #[tauri::command]
-pub fn get_all_quiz_attempts(db: State<DbConnection>) -> Result<Vec<QuizAttemptWithDetails>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_all_quiz_attempts(db: State<DbConnection>) -> AppResult<Vec<QuizAttemptWithDetails>> {
+    let conn = db.0.lock()?;
 
     let mut stmt = conn.prepare(
         "SELECT
@@ -278,61 +286,481 @@ pub fn get_all_quiz_attempts(db: State<DbConnection>) -> Result<Vec<QuizAttemptW
          JOIN quizzes q ON qa.quiz_id = q.id
          JOIN topics t ON q.topic_id = t.id
          JOIN subjects s ON t.subject_id = s.id
-         ORDER BY qa.completed_at DESC"
-    ).map_err(|e| e.to_string())?;
-
-    let attempts = stmt.query_map([], |row| {
-        let percentage: f64 = row.get(10)?;
-        let passing_score: i32 = row.get(3)?;
-
-        Ok(QuizAttemptWithDetails {
-            id: row.get(0)?,
-            quiz_id: row.get(1)?,
-            quiz_name: row.get(2)?,
-            topic_name: row.get(4)?,
-            subject_name: row.get(5)?,
-            started_at: row.get(6)?,
-            completed_at: row.get(7)?,
-            score: row.get(8)?,
-            max_score: row.get(9)?,
-            percentage,
-            time_taken_seconds: row.get(11)?,
-            passed: percentage >= passing_score as f64,
-        })
-    }).map_err(|e| e.to_string())?
-    .collect::<Result<Vec<_>, _>>()
-    .map_err(|e| e.to_string())?;
+         WHERE qa.completed_at IS NOT NULL
+         ORDER BY qa.completed_at DESC",
+    )?;
+
+    let attempts = stmt
+        .query_map([], |row| {
+            let percentage: f64 = row.get(10)?;
+            let passing_score: i32 = row.get(3)?;
+
+            Ok(QuizAttemptWithDetails {
+                id: row.get(0)?,
+                quiz_id: row.get(1)?,
+                quiz_name: row.get(2)?,
+                topic_name: row.get(4)?,
+                subject_name: row.get(5)?,
+                started_at: row.get(6)?,
+                completed_at: row.get(7)?,
+                score: row.get(8)?,
+                max_score: row.get(9)?,
+                percentage,
+                time_taken_seconds: row.get(11)?,
+                passed: percentage >= passing_score as f64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(attempts)
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartedQuizAttempt {
+    pub attempt_id: i64,
+    pub seed: i64,
+    pub started_at: String,
+    pub questions: Vec<QuestionWithDetails>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SaveQuizAttemptData {
-    pub quiz_id: i64,
-    pub score: i32,
-    pub max_score: i32,
-    pub percentage: f64,
-    pub time_taken_seconds: i32,
+pub struct CompleteQuizAttemptData {
+    pub attempt_id: i64,
+    pub responses: Vec<SubmittedResponse>,
 }
 
+/// Start an attempt: draw and order the quiz questions from `seed` (a new one when omitted)
+/// and record exactly what was served. Passing the seed of an earlier attempt replays it
+/// from what that attempt stored, so questions added or edited since do not change the
+/// replay; questions deleted since drop out of it.
 #[tauri::command]
-pub fn save_quiz_attempt(db: State<DbConnection>, data: SaveQuizAttemptData) -> Result<i64, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn start_quiz_attempt(
+    db: State<DbConnection>,
+    quiz_id: i64,
+    seed: Option<i64>,
+) -> AppResult<StartedQuizAttempt> {
+    let mut conn = db.0.lock()?;
+
+    let (topic_id, tag_query, question_count, shuffle_questions, shuffle_options): (
+        i64,
+        Option<TagQuery>,
+        i32,
+        bool,
+        bool,
+    ) = conn.query_row(
+        "SELECT topic_id, tag_query, question_count, shuffle_questions, shuffle_options
+             FROM quizzes WHERE id = ?",
+        [quiz_id],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get::<_, i32>(3)? != 0,
+                row.get::<_, i32>(4)? != 0,
+            ))
+        },
+    )?;
+
+    let replayed: Option<i64> = match seed {
+        Some(seed) => conn
+            .query_row(
+                "SELECT id FROM quiz_attempts WHERE quiz_id = ?1 AND seed = ?2
+                 ORDER BY id ASC LIMIT 1",
+                (quiz_id, seed),
+                |row| row.get(0),
+            )
+            .optional()?,
+        None => None,
+    };
+
+    let seed = seed.unwrap_or_else(fresh_seed);
+    let questions = match replayed {
+        Some(attempt_id) => served_questions(&conn, "quiz_attempt_questions", attempt_id)?,
+        None => draw_quiz_questions(
+            &conn,
+            topic_id,
+            tag_query.as_ref(),
+            question_count,
+            shuffle_questions,
+            shuffle_options,
+            seed,
+        )?,
+    };
+    if questions.is_empty() {
+        return Err(AppError::conflict(if tag_query.is_some() {
+            "No questions match this quiz's tags"
+        } else {
+            "This quiz's topic has no questions"
+        }));
+    }
+
+    // Start transaction
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "INSERT INTO quiz_attempts (quiz_id, seed, completed_at) VALUES (?1, ?2, NULL)",
+        (quiz_id, seed),
+    )?;
+
+    let attempt_id = tx.last_insert_rowid();
+
+    // Remember what was served so completion can be checked against it
+    store_served_questions(&tx, "quiz_attempt_questions", attempt_id, &questions)?;
+
+    let started_at: String = tx.query_row(
+        "SELECT started_at FROM quiz_attempts WHERE id = ?",
+        [attempt_id],
+        |row| row.get(0),
+    )?;
+
+    // Commit transaction
+    tx.commit()?;
+
+    Ok(StartedQuizAttempt {
+        attempt_id,
+        seed,
+        started_at,
+        questions,
+    })
+}
 
-    conn.execute(
-        "INSERT INTO quiz_attempts (quiz_id, score, max_score, percentage, time_taken_seconds, completed_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+/// Grade and close an attempt opened by `start_quiz_attempt`. Served questions
+/// without a response count as unanswered.
+#[tauri::command]
+pub fn complete_quiz_attempt(
+    db: State<DbConnection>,
+    data: CompleteQuizAttemptData,
+) -> AppResult<AttemptResult> {
+    let mut conn = db.0.lock()?;
+
+    let (passing_score, completed_at): (i32, Option<String>) = conn.query_row(
+        "SELECT q.passing_score_percent, qa.completed_at
+             FROM quiz_attempts qa
+             JOIN quizzes q ON qa.quiz_id = q.id
+             WHERE qa.id = ?",
+        [data.attempt_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    if completed_at.is_some() {
+        return Err(AppError::conflict(format!(
+            "Quiz attempt {} is already completed",
+            data.attempt_id
+        )));
+    }
+
+    let served = served_question_ids(&conn, "quiz_attempt_questions", data.attempt_id)?;
+
+    let graded = grade_served(
+        &conn,
+        &format!("quiz attempt {}", data.attempt_id),
+        &served,
+        data.responses,
+    )?;
+
+    // Start transaction
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "UPDATE quiz_attempts SET score = ?1, max_score = ?2, percentage = ?3,
+         time_taken_seconds = CAST(strftime('%s', 'now') - strftime('%s', started_at) AS INTEGER),
+         completed_at = datetime('now')
+         WHERE id = ?4",
         (
-            data.quiz_id,
-            data.score,
-            data.max_score,
-            data.percentage,
-            data.time_taken_seconds,
+            graded.score,
+            graded.max_score,
+            graded.percentage,
+            data.attempt_id,
         ),
-    ).map_err(|e| e.to_string())?;
+    )?;
+
+    insert_attempt_responses(&tx, data.attempt_id, &graded.responses)?;
+    record_responses(&tx, &graded.responses)?;
+
+    let time_taken_seconds: i32 = tx.query_row(
+        "SELECT time_taken_seconds FROM quiz_attempts WHERE id = ?",
+        [data.attempt_id],
+        |row| row.get(0),
+    )?;
+
+    // Commit transaction
+    tx.commit()?;
+
+    Ok(AttemptResult {
+        attempt_id: data.attempt_id,
+        score: graded.score,
+        max_score: graded.max_score,
+        percentage: graded.percentage,
+        time_taken_seconds,
+        passed: graded.percentage >= passing_score as f64,
+        responses: graded.responses,
+    })
+}
+
+/// Pick and order the questions for a new attempt. The draw depends on `seed` and on
+/// the topic's questions, so the same seed gives the same quiz only while the bank is
+/// unchanged; replays go through `served_questions` instead.
+fn draw_quiz_questions(
+    conn: &rusqlite::Connection,
+    topic_id: i64,
+    tag_query: Option<&TagQuery>,
+    question_count: i32,
+    shuffle_questions: bool,
+    shuffle_options: bool,
+    seed: i64,
+) -> AppResult<Vec<QuestionWithDetails>> {
+    let mut question_ids = match tag_query {
+        Some(tag_query) => tagged_question_ids(conn, tag_query)?,
+        None => {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM questions WHERE topic_id = ? ORDER BY created_at DESC, id DESC",
+            )?;
+            let ids = stmt
+                .query_map([topic_id], |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            ids
+        }
+    };
+
+    let mut rng = SeededRng::new(seed);
+    if shuffle_questions {
+        rng.shuffle(&mut question_ids);
+    }
+    question_ids.truncate(question_count.max(0) as usize);
+
+    let mut questions = Vec::with_capacity(question_ids.len());
+    for question_id in question_ids {
+        let mut question = fetch_question_with_details(conn, question_id)?;
+        if shuffle_options {
+            rng.shuffle(&mut question.options);
+        }
+        questions.push(question);
+    }
+
+    Ok(questions)
+}
+
+/// Record the questions served in an attempt, with their option order, in `table`
+/// (`quiz_attempt_questions` or `exam_attempt_questions`)
+pub fn store_served_questions(
+    conn: &rusqlite::Connection,
+    table: &str,
+    attempt_id: i64,
+    questions: &[QuestionWithDetails],
+) -> AppResult<()> {
+    for (position, question) in questions.iter().enumerate() {
+        let option_order: Vec<i64> = question.options.iter().map(|o| o.id).collect();
+        conn.execute(
+            &format!(
+                "INSERT INTO {} (attempt_id, question_id, position, option_order)
+                 VALUES (?1, ?2, ?3, ?4)",
+                table
+            ),
+            (
+                attempt_id,
+                question.question.id,
+                position as i32,
+                serde_json::to_string(&option_order)?,
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Ids of the questions served in an attempt, in the order they were shown
+pub fn served_question_ids(
+    conn: &rusqlite::Connection,
+    table: &str,
+    attempt_id: i64,
+) -> AppResult<Vec<i64>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT question_id FROM {} WHERE attempt_id = ? ORDER BY position ASC",
+        table
+    ))?;
+    let ids = stmt
+        .query_map([attempt_id], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ids)
+}
+
+/// The questions an attempt was served, in the order and with the option order it
+/// stored. Options added since the attempt come last.
+pub fn served_questions(
+    conn: &rusqlite::Connection,
+    table: &str,
+    attempt_id: i64,
+) -> AppResult<Vec<QuestionWithDetails>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT question_id, option_order FROM {} WHERE attempt_id = ? ORDER BY position ASC",
+        table
+    ))?;
+    let served = stmt
+        .query_map([attempt_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut questions = Vec::with_capacity(served.len());
+    for (question_id, option_order) in served {
+        let mut question = fetch_question_with_details(conn, question_id)?;
+        if let Some(option_order) = option_order {
+            let option_order: Vec<i64> = serde_json::from_str(&option_order)?;
+            question.options.sort_by_key(|option| {
+                option_order
+                    .iter()
+                    .position(|id| *id == option.id)
+                    .unwrap_or(option_order.len())
+            });
+        }
+        questions.push(question);
+    }
+
+    Ok(questions)
+}
+
+/// A tag quiz must include questions by tag; excluding tags alone would draw from the
+/// whole library
+fn validate_tag_query(tag_query: Option<&TagQuery>) -> AppResult<()> {
+    if tag_query.is_some_and(|query| !query.includes_tags()) {
+        return Err(AppError::validation(
+            "Choose at least one tag to draw questions from",
+            &["tagQuery"],
+        ));
+    }
 
-    Ok(conn.last_insert_rowid())
+    Ok(())
+}
+
+fn insert_attempt_responses(
+    conn: &rusqlite::Connection,
+    attempt_id: i64,
+    responses: &[GradedResponse],
+) -> AppResult<()> {
+    for response in responses {
+        let response_data = serde_json::to_string(&response.answer)?;
+        conn.execute(
+            "INSERT INTO attempt_responses (attempt_id, question_id, response_data, is_correct,
+             points_earned, time_spent_seconds, revision)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            (
+                attempt_id,
+                response.question_id,
+                &response_data,
+                response.is_correct as i32,
+                response.points_earned,
+                response.time_spent_seconds,
+                current_revision(conn, response.question_id)?,
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuizAttemptDetail {
+    #[serde(flatten)]
+    pub attempt: QuizAttemptWithDetails,
+    pub responses: Vec<ResponseDetail>,
+}
+
+#[tauri::command]
+pub fn get_quiz_attempt_detail(
+    db: State<DbConnection>,
+    attempt_id: i64,
+) -> AppResult<QuizAttemptDetail> {
+    let conn = db.0.lock()?;
+
+    fetch_quiz_attempt_detail(&conn, attempt_id)
+}
+
+/// A completed attempt with every response and the question as it was answered
+pub fn fetch_quiz_attempt_detail(
+    conn: &rusqlite::Connection,
+    attempt_id: i64,
+) -> AppResult<QuizAttemptDetail> {
+    let attempt = conn.query_row(
+        "SELECT
+            qa.id,
+            qa.quiz_id,
+            q.name as quiz_name,
+            q.passing_score_percent,
+            t.name as topic_name,
+            s.name as subject_name,
+            qa.started_at,
+            qa.completed_at,
+            qa.score,
+            qa.max_score,
+            qa.percentage,
+            qa.time_taken_seconds
+         FROM quiz_attempts qa
+         JOIN quizzes q ON qa.quiz_id = q.id
+         JOIN topics t ON q.topic_id = t.id
+         JOIN subjects s ON t.subject_id = s.id
+         WHERE qa.id = ? AND qa.completed_at IS NOT NULL",
+        [attempt_id],
+        |row| {
+            let percentage: f64 = row.get(10)?;
+            let passing_score: i32 = row.get(3)?;
+
+            Ok(QuizAttemptWithDetails {
+                id: row.get(0)?,
+                quiz_id: row.get(1)?,
+                quiz_name: row.get(2)?,
+                topic_name: row.get(4)?,
+                subject_name: row.get(5)?,
+                started_at: row.get(6)?,
+                completed_at: row.get(7)?,
+                score: row.get(8)?,
+                max_score: row.get(9)?,
+                percentage,
+                time_taken_seconds: row.get(11)?,
+                passed: percentage >= passing_score as f64,
+            })
+        },
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT question_id, response_data, is_correct, points_earned, time_spent_seconds, revision
+         FROM attempt_responses WHERE attempt_id = ? ORDER BY id ASC",
+    )?;
+
+    let rows = stmt
+        .query_map([attempt_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<i32>>(2)?.unwrap_or(0) != 0,
+                row.get::<_, Option<f64>>(3)?.unwrap_or(0.0),
+                row.get::<_, Option<i32>>(4)?,
+                row.get::<_, Option<i64>>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Attach each question as it was answered, together with the answer that was given
+    let mut responses = Vec::new();
+    for (question_id, response_data, is_correct, points_earned, time_spent_seconds, revision) in
+        rows
+    {
+        let answer: Option<ResponseData> = serde_json::from_str(&response_data)?;
+        responses.push(ResponseDetail {
+            question: answered_question(conn, question_id, revision)?,
+            revision,
+            answer,
+            is_correct,
+            points_earned,
+            time_spent_seconds,
+        });
+    }
+
+    Ok(QuizAttemptDetail { attempt, responses })
 }
 
 #[derive(Debug, Serialize)]
@@ -346,8 +774,8 @@ pub struct TopicPerformance {
 }
 
 #[tauri::command]
-pub fn get_topic_performance(db: State<DbConnection>) -> Result<Vec<TopicPerformance>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_topic_performance(db: State<DbConnection>) -> AppResult<Vec<TopicPerformance>> {
+    let conn = db.0.lock()?;
 
     let mut stmt = conn.prepare(
         "SELECT
@@ -360,22 +788,23 @@ pub fn get_topic_performance(db: State<DbConnection>) -> Result<Vec<TopicPerform
          JOIN subjects s ON t.subject_id = s.id
          JOIN quizzes q ON q.topic_id = t.id
          JOIN quiz_attempts qa ON qa.quiz_id = q.id
+         WHERE qa.completed_at IS NOT NULL
          GROUP BY t.id, t.name, s.name
          HAVING COUNT(qa.id) > 0
          ORDER BY average_score DESC"
-    ).map_err(|e| e.to_string())?;
-
-    let performance = stmt.query_map([], |row| {
-        Ok(TopicPerformance {
-            topic_name: row.get(0)?,
-            subject_name: row.get(1)?,
-            attempts: row.get(2)?,
-            average_score: row.get(3)?,
-            pass_rate: row.get(4)?,
-        })
-    }).map_err(|e| e.to_string())?
-    .collect::<Result<Vec<_>, _>>()
-    .map_err(|e| e.to_string())?;
+    )?;
+
+    let performance = stmt
+        .query_map([], |row| {
+            Ok(TopicPerformance {
+                topic_name: row.get(0)?,
+                subject_name: row.get(1)?,
+                attempts: row.get(2)?,
+                average_score: row.get(3)?,
+                pass_rate: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(performance)
 }
@@ -0,0 +1,124 @@
+use crate::commands::questions::{fetch_question_with_details, QuestionWithDetails};
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use crate::scheduling::record_grade;
+use serde::Serialize;
+use tauri::State;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewSchedule {
+    pub question_id: i64,
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub lapses: i32,
+    pub due_at: String,
+    pub last_reviewed_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DueReview {
+    pub question: QuestionWithDetails,
+    /// `None` for a question that has never been reviewed
+    pub schedule: Option<ReviewSchedule>,
+}
+
+/// Questions due for review in a subject or topic, most overdue first.
+/// Questions that have never been answered come after the overdue ones.
+#[tauri::command]
+pub fn get_due_reviews(
+    db: State<DbConnection>,
+    subject_id: Option<i64>,
+    topic_id: Option<i64>,
+    limit: Option<i32>,
+) -> AppResult<Vec<DueReview>> {
+    let conn = db.0.lock()?;
+
+    if subject_id.is_none() && topic_id.is_none() {
+        return Err(AppError::validation(
+            "Either a subject or a topic is required",
+            &["subjectId", "topicId"],
+        ));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT q.id, rs.question_id, rs.ease_factor, rs.interval_days, rs.repetitions,
+             rs.lapses, rs.due_at, rs.last_reviewed_at
+             FROM questions q
+             LEFT JOIN review_schedule rs ON rs.question_id = q.id
+             WHERE (?1 IS NULL OR q.subject_id = ?1)
+               AND (?2 IS NULL OR q.topic_id = ?2)
+               AND (rs.due_at IS NULL OR rs.due_at <= datetime('now'))
+             ORDER BY rs.due_at IS NULL, rs.due_at ASC, q.id ASC
+             LIMIT ?3",
+    )?;
+
+    let rows = stmt
+        .query_map((subject_id, topic_id, limit.unwrap_or(-1)), |row| {
+            let schedule = match row.get::<_, Option<i64>>(1)? {
+                Some(question_id) => Some(ReviewSchedule {
+                    question_id,
+                    ease_factor: row.get(2)?,
+                    interval_days: row.get(3)?,
+                    repetitions: row.get(4)?,
+                    lapses: row.get(5)?,
+                    due_at: row.get(6)?,
+                    last_reviewed_at: row.get(7)?,
+                }),
+                None => None,
+            };
+            Ok((row.get::<_, i64>(0)?, schedule))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut reviews = Vec::with_capacity(rows.len());
+    for (question_id, schedule) in rows {
+        reviews.push(DueReview {
+            question: fetch_question_with_details(&conn, question_id)?,
+            schedule,
+        });
+    }
+
+    Ok(reviews)
+}
+
+/// Record a study review. `grade` is the SM-2 recall quality from 0 (forgot) to 5 (perfect).
+#[tauri::command]
+pub fn record_review(
+    db: State<DbConnection>,
+    question_id: i64,
+    grade: i32,
+) -> AppResult<ReviewSchedule> {
+    let conn = db.0.lock()?;
+
+    if !(0..=5).contains(&grade) {
+        return Err(AppError::validation(
+            format!("Review grade must be between 0 and 5, got {}", grade),
+            &["grade"],
+        ));
+    }
+
+    record_grade(&conn, question_id, grade)?;
+
+    let schedule = conn
+        .query_row(
+            "SELECT question_id, ease_factor, interval_days, repetitions, lapses, due_at, last_reviewed_at
+             FROM review_schedule WHERE question_id = ?",
+            [question_id],
+            |row| {
+                Ok(ReviewSchedule {
+                    question_id: row.get(0)?,
+                    ease_factor: row.get(1)?,
+                    interval_days: row.get(2)?,
+                    repetitions: row.get(3)?,
+                    lapses: row.get(4)?,
+                    due_at: row.get(5)?,
+                    last_reviewed_at: row.get(6)?,
+                })
+            },
+        )?;
+
+    Ok(schedule)
+}
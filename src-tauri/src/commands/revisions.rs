@@ -0,0 +1,345 @@
+use crate::commands::questions::{
+    apply_question_update, fetch_question_with_details, CreateMatchPair, CreateOrderItem,
+    CreateQuestionBlank, CreateQuestionOption, QuestionWithDetails, UpdateQuestionData,
+};
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::State;
+
+/// Fields left out of diffs: row ids and timestamps change on every save
+const IGNORED_FIELDS: &[&str] = &["id", "questionId", "createdAt", "updatedAt"];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuestionRevisionSummary {
+    pub revision: i64,
+    pub question_text: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuestionRevision {
+    pub question_id: i64,
+    pub revision: i64,
+    pub created_at: String,
+    pub snapshot: QuestionWithDetails,
+}
+
+/// One field that differs between two revisions. `field` is a path such as
+/// `questionText`, `options[1].isCorrect` or `tags[0].name`; a value that only exists on
+/// one side is `null` on the other.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// Every revision of a question, newest first
+#[tauri::command]
+pub fn get_question_revisions(
+    db: State<DbConnection>,
+    question_id: i64,
+) -> AppResult<Vec<QuestionRevisionSummary>> {
+    let conn = db.0.lock()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT revision, json_extract(snapshot, '$.questionText'), created_at
+             FROM question_revisions WHERE question_id = ? ORDER BY revision DESC",
+    )?;
+
+    let revisions = stmt
+        .query_map([question_id], |row| {
+            Ok(QuestionRevisionSummary {
+                revision: row.get(0)?,
+                question_text: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                created_at: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(revisions)
+}
+
+#[tauri::command]
+pub fn get_question_revision(
+    db: State<DbConnection>,
+    question_id: i64,
+    revision: i64,
+) -> AppResult<QuestionRevision> {
+    let conn = db.0.lock()?;
+
+    fetch_revision(&conn, question_id, revision)
+}
+
+/// What changed from revision `from` to revision `to` of a question
+#[tauri::command]
+pub fn diff_question_revisions(
+    db: State<DbConnection>,
+    question_id: i64,
+    from: i64,
+    to: i64,
+) -> AppResult<Vec<FieldChange>> {
+    let conn = db.0.lock()?;
+
+    let before = fetch_revision(&conn, question_id, from)?;
+    let after = fetch_revision(&conn, question_id, to)?;
+
+    diff_snapshots(&before.snapshot, &after.snapshot)
+}
+
+/// Make a question look like one of its earlier revisions. The restore is saved as a
+/// new revision, so nothing in the history is lost.
+#[tauri::command]
+pub fn restore_question_revision(
+    db: State<DbConnection>,
+    question_id: i64,
+    revision: i64,
+) -> AppResult<QuestionWithDetails> {
+    db.transaction(|tx| {
+        let snapshot = fetch_revision(tx, question_id, revision)?.snapshot;
+        apply_question_update(tx, question_id, &update_from_snapshot(snapshot))
+    })
+}
+
+/// Save the current state of a question as its next revision, unless it matches the
+/// latest one. Returns the revision the question is now at.
+///
+/// Responses refer to options, blanks, items and pairs by id, so a save that re-creates
+/// them counts as a change even when the diff is empty. Otherwise a response given at
+/// the latest revision could hold ids the question no longer has.
+pub fn record_revision(conn: &rusqlite::Connection, question_id: i64) -> AppResult<i64> {
+    let question = fetch_question_with_details(conn, question_id)?;
+
+    let latest: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT revision, snapshot FROM question_revisions
+             WHERE question_id = ? ORDER BY revision DESC LIMIT 1",
+            [question_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    if let Some((revision, snapshot)) = &latest {
+        let previous: QuestionWithDetails = serde_json::from_str(snapshot)?;
+        if diff_snapshots(&previous, &question)?.is_empty()
+            && child_ids(&previous) == child_ids(&question)
+        {
+            return Ok(*revision);
+        }
+    }
+
+    let revision = latest.map_or(1, |(revision, _)| revision + 1);
+    conn.execute(
+        "INSERT INTO question_revisions (question_id, revision, snapshot) VALUES (?1, ?2, ?3)",
+        (question_id, revision, serde_json::to_string(&question)?),
+    )?;
+
+    Ok(revision)
+}
+
+/// The revision a question is at. Questions last saved before revisions were tracked
+/// get their current state recorded as revision 1.
+pub fn current_revision(conn: &rusqlite::Connection, question_id: i64) -> AppResult<i64> {
+    let revision: Option<i64> = conn.query_row(
+        "SELECT MAX(revision) FROM question_revisions WHERE question_id = ?",
+        [question_id],
+        |row| row.get(0),
+    )?;
+
+    match revision {
+        Some(revision) => Ok(revision),
+        None => record_revision(conn, question_id),
+    }
+}
+
+/// The question as it was when a response was given: the stored revision when the
+/// question has changed since, otherwise the question as it is now
+pub fn answered_question(
+    conn: &rusqlite::Connection,
+    question_id: i64,
+    revision: Option<i64>,
+) -> AppResult<QuestionWithDetails> {
+    let question = fetch_question_with_details(conn, question_id)?;
+    let Some(revision) = revision else {
+        return Ok(question);
+    };
+
+    let latest: Option<i64> = conn.query_row(
+        "SELECT MAX(revision) FROM question_revisions WHERE question_id = ?",
+        [question_id],
+        |row| row.get(0),
+    )?;
+    if latest == Some(revision) {
+        return Ok(question);
+    }
+
+    match fetch_revision(conn, question_id, revision) {
+        Ok(revision) => Ok(revision.snapshot),
+        Err(AppError::NotFound(_)) => Ok(question),
+        Err(e) => Err(e),
+    }
+}
+
+fn fetch_revision(
+    conn: &rusqlite::Connection,
+    question_id: i64,
+    revision: i64,
+) -> AppResult<QuestionRevision> {
+    let (snapshot, created_at): (String, String) = conn
+        .query_row(
+            "SELECT snapshot, created_at FROM question_revisions
+             WHERE question_id = ?1 AND revision = ?2",
+            (question_id, revision),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| {
+            AppError::not_found(format!(
+                "Revision {} of question {} not found",
+                revision, question_id
+            ))
+        })?;
+
+    let mut snapshot: QuestionWithDetails = serde_json::from_str(&snapshot)?;
+    // Snapshots carried over from another library keep the id they had there
+    snapshot.question.id = question_id;
+
+    Ok(QuestionRevision {
+        question_id,
+        revision,
+        created_at,
+        snapshot,
+    })
+}
+
+/// Ids of the rows a response can refer to
+fn child_ids(question: &QuestionWithDetails) -> [Vec<i64>; 4] {
+    [
+        question.options.iter().map(|option| option.id).collect(),
+        question.blanks.iter().map(|blank| blank.id).collect(),
+        question.order_items.iter().map(|item| item.id).collect(),
+        question.matches.iter().map(|pair| pair.id).collect(),
+    ]
+}
+
+fn diff_snapshots(
+    before: &QuestionWithDetails,
+    after: &QuestionWithDetails,
+) -> AppResult<Vec<FieldChange>> {
+    let mut changes = Vec::new();
+    diff_values(
+        "",
+        &serde_json::to_value(before)?,
+        &serde_json::to_value(after)?,
+        &mut changes,
+    );
+    Ok(changes)
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, changes: &mut Vec<FieldChange>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            let keys = before
+                .keys()
+                .chain(after.keys().filter(|key| !before.contains_key(*key)));
+            for key in keys.filter(|key| !IGNORED_FIELDS.contains(&key.as_str())) {
+                let field = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_values(
+                    &field,
+                    before.get(key).unwrap_or(&Value::Null),
+                    after.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            for index in 0..before.len().max(after.len()) {
+                diff_values(
+                    &format!("{}[{}]", path, index),
+                    before.get(index).unwrap_or(&Value::Null),
+                    after.get(index).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        _ if before != after => changes.push(FieldChange {
+            field: path.to_string(),
+            before: before.clone(),
+            after: after.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// The update that turns a question back into `snapshot`. Numeric answers are stored
+/// as numeric blanks, so they come back through `blanks` unchanged.
+fn update_from_snapshot(snapshot: QuestionWithDetails) -> UpdateQuestionData {
+    let question = snapshot.question;
+
+    UpdateQuestionData {
+        question_text: question.question_text,
+        question_image_path: question.question_image_path,
+        explanation: question.explanation,
+        difficulty: question.difficulty,
+        points: question.points,
+        source: question.source,
+        options: snapshot
+            .options
+            .into_iter()
+            .map(|option| CreateQuestionOption {
+                option_text: option.option_text,
+                option_image_path: option.option_image_path,
+                is_correct: option.is_correct,
+                display_order: option.display_order,
+            })
+            .collect(),
+        blanks: snapshot
+            .blanks
+            .into_iter()
+            .map(|blank| CreateQuestionBlank {
+                blank_index: blank.blank_index,
+                correct_answer: blank.correct_answer,
+                acceptable_answers: blank.acceptable_answers,
+                is_numeric: blank.is_numeric,
+                numeric_tolerance: blank.numeric_tolerance,
+                unit: blank.unit,
+                input_type: blank.input_type,
+                dropdown_options: blank.dropdown_options,
+            })
+            .collect(),
+        numeric_data: None,
+        order_items: Some(
+            snapshot
+                .order_items
+                .into_iter()
+                .map(|item| CreateOrderItem {
+                    text: item.item_text,
+                    correct_position: item.correct_position,
+                })
+                .collect(),
+        ),
+        match_pairs: Some(
+            snapshot
+                .matches
+                .into_iter()
+                .map(|pair| CreateMatchPair {
+                    left_item: pair.left_item,
+                    right_item: pair.right_item,
+                    left_image_path: pair.left_image_path,
+                    right_image_path: pair.right_image_path,
+                })
+                .collect(),
+        ),
+        tags: Some(snapshot.tags.into_iter().map(|tag| tag.name).collect()),
+    }
+}
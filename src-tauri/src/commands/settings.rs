@@ -0,0 +1,115 @@
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use rusqlite::OptionalExtension;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+const IMAGE_SETTINGS_KEY: &str = "images";
+
+/// Formats imported images can be re-encoded to. `ORIGINAL` keeps each image's own
+/// format, except BMP, which becomes PNG. `WEBP` is written lossless, which suits
+/// screenshots and diagrams but is usually larger than a photo's JPEG; an image that
+/// would grow stays in its own format.
+pub const IMAGE_OUTPUT_FORMATS: &[&str] = &["ORIGINAL", "JPEG", "WEBP"];
+
+/// How images are processed when they enter the asset store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageSettings {
+    /// Longest side in pixels; larger images are scaled down. 0 keeps every size.
+    pub max_dimension: u32,
+    /// One of `IMAGE_OUTPUT_FORMATS`; `WEBP` output is lossless
+    pub format: String,
+    /// 1-100, used when writing JPEG
+    pub jpeg_quality: u8,
+    /// Longest side of the thumbnails made for list views; 0 makes none
+    pub thumbnail_size: u32,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        ImageSettings {
+            max_dimension: 2048,
+            format: "ORIGINAL".to_string(),
+            jpeg_quality: 85,
+            thumbnail_size: 320,
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_image_settings(db: State<DbConnection>) -> AppResult<ImageSettings> {
+    let conn = db.0.lock()?;
+
+    load_image_settings(&conn)
+}
+
+/// Save the image settings. They apply to images imported from now on; run
+/// `optimize_existing_images` to apply them to the current library.
+#[tauri::command]
+pub fn update_image_settings(
+    db: State<DbConnection>,
+    settings: ImageSettings,
+) -> AppResult<ImageSettings> {
+    let conn = db.0.lock()?;
+
+    if !IMAGE_OUTPUT_FORMATS.contains(&settings.format.as_str()) {
+        return Err(AppError::validation(
+            format!("Unknown image format: {}", settings.format),
+            &["format"],
+        ));
+    }
+    if settings.max_dimension != 0 && !(64..=16384).contains(&settings.max_dimension) {
+        return Err(AppError::validation(
+            "Maximum dimension must be between 64 and 16384 pixels, or 0 for no limit",
+            &["maxDimension"],
+        ));
+    }
+    if !(1..=100).contains(&settings.jpeg_quality) {
+        return Err(AppError::validation(
+            "JPEG quality must be between 1 and 100",
+            &["jpegQuality"],
+        ));
+    }
+    if settings.thumbnail_size != 0 && !(32..=1024).contains(&settings.thumbnail_size) {
+        return Err(AppError::validation(
+            "Thumbnail size must be between 32 and 1024 pixels, or 0 for none",
+            &["thumbnailSize"],
+        ));
+    }
+
+    put_setting(&conn, IMAGE_SETTINGS_KEY, &settings)?;
+
+    Ok(settings)
+}
+
+/// The saved image settings, or the defaults if none were saved
+pub fn load_image_settings(conn: &rusqlite::Connection) -> AppResult<ImageSettings> {
+    Ok(get_setting(conn, IMAGE_SETTINGS_KEY)?.unwrap_or_default())
+}
+
+fn get_setting<T: DeserializeOwned>(
+    conn: &rusqlite::Connection,
+    key: &str,
+) -> AppResult<Option<T>> {
+    let value: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = ?", [key], |row| {
+            row.get(0)
+        })
+        .optional()?;
+
+    Ok(value
+        .map(|value| serde_json::from_str(&value))
+        .transpose()?)
+}
+
+fn put_setting<T: Serialize>(conn: &rusqlite::Connection, key: &str, value: &T) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        (key, serde_json::to_string(value)?),
+    )?;
+
+    Ok(())
+}
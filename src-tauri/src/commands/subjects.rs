@@ -1,6 +1,7 @@
+use crate::db::DbConnection;
+use crate::error::AppResult;
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use crate::db::DbConnection;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -33,12 +34,11 @@ pub struct UpdateSubjectData {
 }
 
 #[tauri::command]
-pub fn get_subjects(db: State<DbConnection>) -> Result<Vec<Subject>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_subjects(db: State<DbConnection>) -> AppResult<Vec<Subject>> {
+    let conn = db.0.lock()?;
 
     let mut stmt = conn
-        .prepare("SELECT id, name, description, color, icon, created_at, updated_at FROM subjects ORDER BY created_at DESC")
-        .map_err(|e| e.to_string())?;
+        .prepare("SELECT id, name, description, color, icon, created_at, updated_at FROM subjects ORDER BY created_at DESC")?;
 
     let subjects = stmt
         .query_map([], |row| {
@@ -51,17 +51,15 @@ pub fn get_subjects(db: State<DbConnection>) -> Result<Vec<Subject>, String> {
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(subjects)
 }
 
 #[tauri::command]
-pub fn get_subject(db: State<DbConnection>, id: i64) -> Result<Subject, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_subject(db: State<DbConnection>, id: i64) -> AppResult<Subject> {
+    let conn = db.0.lock()?;
 
     let subject = conn
         .query_row(
@@ -78,21 +76,19 @@ pub fn get_subject(db: State<DbConnection>, id: i64) -> Result<Subject, String>
                     updated_at: row.get(6)?,
                 })
             },
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     Ok(subject)
 }
 
 #[tauri::command]
-pub fn create_subject(db: State<DbConnection>, data: CreateSubjectData) -> Result<Subject, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn create_subject(db: State<DbConnection>, data: CreateSubjectData) -> AppResult<Subject> {
+    let conn = db.0.lock()?;
 
     conn.execute(
         "INSERT INTO subjects (name, description, color, icon) VALUES (?1, ?2, ?3, ?4)",
         (&data.name, &data.description, &data.color, &data.icon),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     let id = conn.last_insert_rowid();
 
@@ -111,8 +107,7 @@ pub fn create_subject(db: State<DbConnection>, data: CreateSubjectData) -> Resul
                     updated_at: row.get(6)?,
                 })
             },
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     Ok(subject)
 }
@@ -122,14 +117,13 @@ pub fn update_subject(
     db: State<DbConnection>,
     id: i64,
     data: UpdateSubjectData,
-) -> Result<Subject, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+) -> AppResult<Subject> {
+    let conn = db.0.lock()?;
 
     conn.execute(
         "UPDATE subjects SET name = ?1, description = ?2, color = ?3, icon = ?4, updated_at = CURRENT_TIMESTAMP WHERE id = ?5",
         (&data.name, &data.description, &data.color, &data.icon, id),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     let subject = conn
         .query_row(
@@ -146,22 +140,19 @@ pub fn update_subject(
                     updated_at: row.get(6)?,
                 })
             },
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     Ok(subject)
 }
 
 #[tauri::command]
-pub fn delete_subject(db: State<DbConnection>, id: i64) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn delete_subject(db: State<DbConnection>, id: i64) -> AppResult<()> {
+    let conn = db.0.lock()?;
 
     // Enable foreign keys for cascade deletes
-    conn.execute("PRAGMA foreign_keys = ON", [])
-        .map_err(|e| e.to_string())?;
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
 
-    conn.execute("DELETE FROM subjects WHERE id = ?", [id])
-        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM subjects WHERE id = ?", [id])?;
 
     Ok(())
 }
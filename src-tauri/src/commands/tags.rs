@@ -0,0 +1,298 @@
+use crate::db::DbConnection;
+use crate::error::{AppError, AppResult};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub color: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagWithCount {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub question_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTagData {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateTagData {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+/// Which tags a question must carry, by tag id. A question matches when it has every
+/// tag in `all`, at least one tag in `any` (if given) and no tag in `none`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagQuery {
+    #[serde(default)]
+    pub all: Vec<i64>,
+    #[serde(default)]
+    pub any: Vec<i64>,
+    #[serde(default)]
+    pub none: Vec<i64>,
+}
+
+impl TagQuery {
+    /// Whether the query selects questions by tag, rather than only excluding some
+    pub fn includes_tags(&self) -> bool {
+        !self.all.is_empty() || !self.any.is_empty()
+    }
+
+    /// An SQL condition on the question id in `column`, with its parameters in order
+    pub fn condition(&self, column: &str) -> (String, Vec<i64>) {
+        let has_tag = |tags: &[i64]| {
+            format!(
+                "EXISTS (SELECT 1 FROM question_tags WHERE question_id = {} AND tag_id IN ({}))",
+                column,
+                vec!["?"; tags.len()].join(", ")
+            )
+        };
+
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        for tag_id in &self.all {
+            conditions.push(has_tag(&[*tag_id]));
+            params.push(*tag_id);
+        }
+        if !self.any.is_empty() {
+            conditions.push(has_tag(&self.any));
+            params.extend(&self.any);
+        }
+        if !self.none.is_empty() {
+            conditions.push(format!("NOT {}", has_tag(&self.none)));
+            params.extend(&self.none);
+        }
+
+        if conditions.is_empty() {
+            ("1".to_string(), params)
+        } else {
+            (conditions.join(" AND "), params)
+        }
+    }
+}
+
+// Quizzes store their tag query as JSON text
+impl FromSql for TagQuery {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        serde_json::from_str(value.as_str()?).map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for TagQuery {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        serde_json::to_string(self)
+            .map(ToSqlOutput::from)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+}
+
+#[tauri::command]
+pub fn get_tags(db: State<DbConnection>) -> AppResult<Vec<TagWithCount>> {
+    let conn = db.0.lock()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.name, t.color, t.created_at, t.updated_at,
+             (SELECT COUNT(*) FROM question_tags qt WHERE qt.tag_id = t.id)
+             FROM tags t ORDER BY t.name COLLATE NOCASE ASC",
+    )?;
+
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(TagWithCount {
+                tag: Tag {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    color: row.get(2)?,
+                    created_at: row.get(3)?,
+                    updated_at: row.get(4)?,
+                },
+                question_count: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(tags)
+}
+
+#[tauri::command]
+pub fn create_tag(db: State<DbConnection>, data: CreateTagData) -> AppResult<Tag> {
+    let conn = db.0.lock()?;
+
+    let name = validate_tag_name(&conn, &data.name, None)?;
+    conn.execute(
+        "INSERT INTO tags (name, color) VALUES (?1, ?2)",
+        (&name, &data.color),
+    )?;
+
+    fetch_tag(&conn, conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub fn update_tag(db: State<DbConnection>, id: i64, data: UpdateTagData) -> AppResult<Tag> {
+    let conn = db.0.lock()?;
+
+    let name = validate_tag_name(&conn, &data.name, Some(id))?;
+    let updated = conn.execute(
+        "UPDATE tags SET name = ?1, color = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+        (&name, &data.color, id),
+    )?;
+    if updated == 0 {
+        return Err(AppError::not_found(format!("Tag {} not found", id)));
+    }
+
+    fetch_tag(&conn, id)
+}
+
+/// Delete a tag and remove it from every question. Quizzes that draw from the tag
+/// keep their query but no longer match through it.
+#[tauri::command]
+pub fn delete_tag(db: State<DbConnection>, id: i64) -> AppResult<()> {
+    let conn = db.0.lock()?;
+
+    // Enable foreign keys for cascade deletes
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+
+    conn.execute("DELETE FROM tags WHERE id = ?", [id])?;
+
+    Ok(())
+}
+
+/// Replace the tags of a question. Tags are matched by name, ignoring case, and
+/// created when they do not exist yet.
+pub fn set_question_tags(
+    conn: &rusqlite::Connection,
+    question_id: i64,
+    names: &[String],
+) -> AppResult<()> {
+    conn.execute(
+        "DELETE FROM question_tags WHERE question_id = ?",
+        [question_id],
+    )?;
+
+    for name in names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+    {
+        let existing: Option<i64> = conn
+            .query_row("SELECT id FROM tags WHERE name = ?", [name], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let tag_id = match existing {
+            Some(tag_id) => tag_id,
+            None => {
+                conn.execute("INSERT INTO tags (name) VALUES (?)", [name])?;
+                conn.last_insert_rowid()
+            }
+        };
+
+        conn.execute(
+            "INSERT OR IGNORE INTO question_tags (question_id, tag_id) VALUES (?1, ?2)",
+            (question_id, tag_id),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn get_question_tags(conn: &rusqlite::Connection, question_id: i64) -> AppResult<Vec<Tag>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.name, t.color, t.created_at, t.updated_at
+             FROM question_tags qt JOIN tags t ON qt.tag_id = t.id
+             WHERE qt.question_id = ? ORDER BY t.name COLLATE NOCASE ASC",
+    )?;
+
+    let tags = stmt
+        .query_map([question_id], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(tags)
+}
+
+/// Ids of the questions matching `query`, newest first like a topic's questions
+pub fn tagged_question_ids(conn: &rusqlite::Connection, query: &TagQuery) -> AppResult<Vec<i64>> {
+    let (condition, params) = query.condition("q.id");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT q.id FROM questions q WHERE {} ORDER BY q.created_at DESC, q.id DESC",
+        condition
+    ))?;
+
+    let ids = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            row.get::<_, i64>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ids)
+}
+
+fn fetch_tag(conn: &rusqlite::Connection, id: i64) -> AppResult<Tag> {
+    conn.query_row(
+        "SELECT id, name, color, created_at, updated_at FROM tags WHERE id = ?",
+        [id],
+        |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        },
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found(format!("Tag {} not found", id)))
+}
+
+/// The trimmed name, if it is not empty and no other tag has it
+fn validate_tag_name(
+    conn: &rusqlite::Connection,
+    name: &str,
+    id: Option<i64>,
+) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::validation("Tag name is required", &["name"]));
+    }
+
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM tags WHERE name = ?1 AND id IS NOT ?2)",
+        (name, id),
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(AppError::conflict(format!(
+            "A tag named \"{}\" already exists",
+            name
+        )));
+    }
+
+    Ok(name.to_string())
+}
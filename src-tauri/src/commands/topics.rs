@@ -1,6 +1,7 @@
+use crate::db::DbConnection;
+use crate::error::AppResult;
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use crate::db::DbConnection;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -32,12 +33,11 @@ pub struct UpdateTopicData {
 }
 
 #[tauri::command]
-pub fn get_topics(db: State<DbConnection>, subject_id: i64) -> Result<Vec<Topic>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_topics(db: State<DbConnection>, subject_id: i64) -> AppResult<Vec<Topic>> {
+    let conn = db.0.lock()?;
 
     let mut stmt = conn
-        .prepare("SELECT id, subject_id, name, description, week_number, created_at, updated_at FROM topics WHERE subject_id = ? ORDER BY week_number ASC, created_at DESC")
-        .map_err(|e| e.to_string())?;
+        .prepare("SELECT id, subject_id, name, description, week_number, created_at, updated_at FROM topics WHERE subject_id = ? ORDER BY week_number ASC, created_at DESC")?;
 
     let topics = stmt
         .query_map([subject_id], |row| {
@@ -50,17 +50,15 @@ pub fn get_topics(db: State<DbConnection>, subject_id: i64) -> Result<Vec<Topic>
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(topics)
 }
 
 #[tauri::command]
-pub fn get_topic(db: State<DbConnection>, id: i64) -> Result<Topic, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn get_topic(db: State<DbConnection>, id: i64) -> AppResult<Topic> {
+    let conn = db.0.lock()?;
 
     let topic = conn
         .query_row(
@@ -77,21 +75,24 @@ pub fn get_topic(db: State<DbConnection>, id: i64) -> Result<Topic, String> {
                     updated_at: row.get(6)?,
                 })
             },
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     Ok(topic)
 }
 
 #[tauri::command]
-pub fn create_topic(db: State<DbConnection>, data: CreateTopicData) -> Result<Topic, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn create_topic(db: State<DbConnection>, data: CreateTopicData) -> AppResult<Topic> {
+    let conn = db.0.lock()?;
 
     conn.execute(
         "INSERT INTO topics (subject_id, name, description, week_number) VALUES (?1, ?2, ?3, ?4)",
-        (&data.subject_id, &data.name, &data.description, &data.week_number),
-    )
-    .map_err(|e| e.to_string())?;
+        (
+            &data.subject_id,
+            &data.name,
+            &data.description,
+            &data.week_number,
+        ),
+    )?;
 
     let id = conn.last_insert_rowid();
 
@@ -110,21 +111,19 @@ pub fn create_topic(db: State<DbConnection>, data: CreateTopicData) -> Result<To
                     updated_at: row.get(6)?,
                 })
             },
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     Ok(topic)
 }
 
 #[tauri::command]
-pub fn update_topic(db: State<DbConnection>, id: i64, data: UpdateTopicData) -> Result<Topic, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn update_topic(db: State<DbConnection>, id: i64, data: UpdateTopicData) -> AppResult<Topic> {
+    let conn = db.0.lock()?;
 
     conn.execute(
         "UPDATE topics SET name = ?1, description = ?2, week_number = ?3, updated_at = CURRENT_TIMESTAMP WHERE id = ?4",
         (&data.name, &data.description, &data.week_number, id),
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     let topic = conn
         .query_row(
@@ -141,22 +140,19 @@ pub fn update_topic(db: State<DbConnection>, id: i64, data: UpdateTopicData) ->
                     updated_at: row.get(6)?,
                 })
             },
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     Ok(topic)
 }
 
 #[tauri::command]
-pub fn delete_topic(db: State<DbConnection>, id: i64) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+pub fn delete_topic(db: State<DbConnection>, id: i64) -> AppResult<()> {
+    let conn = db.0.lock()?;
 
     // Enable foreign keys for cascade deletes
-    conn.execute("PRAGMA foreign_keys = ON", [])
-        .map_err(|e| e.to_string())?;
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
 
-    conn.execute("DELETE FROM topics WHERE id = ?", [id])
-        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM topics WHERE id = ?", [id])?;
 
     Ok(())
 }
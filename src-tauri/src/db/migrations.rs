@@ -0,0 +1,296 @@
+use rusqlite::{Connection, Transaction};
+
+/// A schema change that is applied once, in version order, inside its own transaction.
+///
+/// The applied version is tracked in `PRAGMA user_version`. Databases created before
+/// versioning report version 0; the early migrations are written to be safe on them
+/// (`IF NOT EXISTS`, column checks) so they are brought up to date without data loss.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// Every migration, oldest first. Append new ones with the next version number;
+/// never edit or reorder a migration that has shipped.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        apply: |tx| tx.execute_batch(include_str!("migrations/0001_initial.sql")),
+    },
+    Migration {
+        version: 2,
+        name: "blank input types and match images",
+        apply: |tx| {
+            add_column_if_missing(
+                tx,
+                "question_blanks",
+                "input_type",
+                "TEXT DEFAULT 'INPUT' CHECK (input_type IN ('INPUT', 'DROPDOWN'))",
+            )?;
+            add_column_if_missing(tx, "question_blanks", "dropdown_options", "TEXT")?;
+            add_column_if_missing(tx, "question_matches", "left_image_path", "TEXT")?;
+            add_column_if_missing(tx, "question_matches", "right_image_path", "TEXT")
+        },
+    },
+    Migration {
+        version: 3,
+        name: "reproducible quiz attempts",
+        apply: |tx| {
+            add_column_if_missing(tx, "quiz_attempts", "seed", "INTEGER")?;
+            tx.execute_batch(include_str!("migrations/0003_quiz_attempt_questions.sql"))
+        },
+    },
+    Migration {
+        version: 4,
+        name: "review schedule",
+        apply: |tx| tx.execute_batch(include_str!("migrations/0004_review_schedule.sql")),
+    },
+    Migration {
+        version: 5,
+        name: "question full-text search",
+        apply: |tx| tx.execute_batch(include_str!("migrations/0005_question_search.sql")),
+    },
+    Migration {
+        version: 6,
+        name: "question tags and tag quizzes",
+        apply: |tx| {
+            tx.execute_batch(include_str!("migrations/0006_tags.sql"))?;
+            add_column_if_missing(tx, "quizzes", "tag_query", "TEXT")
+        },
+    },
+    Migration {
+        version: 7,
+        name: "question revisions",
+        apply: |tx| {
+            tx.execute_batch(include_str!("migrations/0007_question_revisions.sql"))?;
+            add_column_if_missing(tx, "attempt_responses", "revision", "INTEGER")?;
+            add_column_if_missing(tx, "exam_responses", "revision", "INTEGER")
+        },
+    },
+    Migration {
+        version: 8,
+        name: "asset references",
+        apply: |tx| tx.execute_batch(include_str!("migrations/0008_asset_references.sql")),
+    },
+    Migration {
+        version: 9,
+        name: "settings",
+        apply: |tx| tx.execute_batch(include_str!("migrations/0009_settings.sql")),
+    },
+    Migration {
+        version: 10,
+        name: "served exam questions",
+        apply: |tx| {
+            add_column_if_missing(tx, "exam_attempts", "seed", "INTEGER")?;
+            tx.execute_batch(include_str!("migrations/0010_exam_attempt_questions.sql"))
+        },
+    },
+];
+
+/// The schema version this build of the app expects
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Bring the database up to the latest schema version.
+///
+/// Refuses to touch a database written by a newer version of the app, and stops at
+/// the first migration that fails, leaving the database at the last good version.
+pub fn run_migrations(conn: &mut Connection) -> Result<(), String> {
+    let current: i32 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this app supports ({}). Please update QuizForge.",
+            current, latest
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start migration {}: {}", migration.version, e))?;
+
+        (migration.apply)(&tx)
+            .and_then(|_| tx.pragma_update(None, "user_version", migration.version))
+            .and_then(|_| tx.commit())
+            .map_err(|e| {
+                format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.name, e
+                )
+            })?;
+
+        println!(
+            "Applied migration {} ({})",
+            migration.version, migration.name
+        );
+    }
+
+    Ok(())
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(conn: &Connection) -> i32 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    /// Every table, index, view and trigger with its SQL
+    fn schema(conn: &Connection) -> Vec<(String, Option<String>)> {
+        let mut stmt = conn
+            .prepare("SELECT name, sql FROM sqlite_master ORDER BY type, name")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    /// Question ids whose search row matches `query`
+    fn search(conn: &Connection, query: &str) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT rowid FROM question_search WHERE question_search MATCH ? ORDER BY rowid",
+            )
+            .unwrap();
+        stmt.query_map([query], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn baseline_database_is_migrated_to_latest_and_rerunning_is_a_noop() {
+        // A database the app created before schema versioning: the same tables, version 0
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
+        conn.execute_batch(include_str!("migrations/0001_initial.sql"))
+            .unwrap();
+        conn.execute_batch(
+            "INSERT INTO subjects (id, name) VALUES (1, 'Chemistry');
+             INSERT INTO topics (id, subject_id, name) VALUES (1, 1, 'Elements');
+             INSERT INTO questions (id, subject_id, topic_id, question_type, question_text, difficulty, points)
+             VALUES (1, 1, 1, 'SINGLE_CHOICE', 'Which element is a noble gas?', 'EASY', 1);
+             INSERT INTO question_options (question_id, option_text, is_correct) VALUES (1, 'Argon', 1);",
+        )
+        .unwrap();
+        assert_eq!(user_version(&conn), 0);
+
+        run_migrations(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), latest_version());
+        // Rows from before the search index are indexed when it is created
+        assert_eq!(search(&conn, "argon"), [1]);
+
+        let migrated = schema(&conn);
+        run_migrations(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), latest_version());
+        assert_eq!(schema(&conn), migrated);
+        let questions: i64 = conn
+            .query_row("SELECT COUNT(*) FROM questions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(questions, 1);
+    }
+
+    #[test]
+    fn database_from_a_newer_app_is_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+
+        let error = run_migrations(&mut conn).unwrap_err();
+        assert!(error.contains("newer than this app supports"), "{}", error);
+        assert!(schema(&conn).is_empty());
+    }
+
+    #[test]
+    fn search_index_follows_questions_and_their_parts() {
+        let conn = crate::db::test_connection();
+        conn.execute_batch(
+            "INSERT INTO subjects (id, name) VALUES (1, 'Geography');
+             INSERT INTO topics (id, subject_id, name) VALUES (1, 1, 'Capitals');
+             INSERT INTO questions (id, subject_id, topic_id, question_type, question_text, difficulty, points)
+             VALUES (1, 1, 1, 'SINGLE_CHOICE', 'What is the capital of France?', 'EASY', 1);",
+        )
+        .unwrap();
+        assert_eq!(search(&conn, "france"), [1]);
+
+        conn.execute(
+            "INSERT INTO question_options (id, question_id, option_text, is_correct) VALUES (1, 1, 'Paris', 1)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(search(&conn, "paris"), [1]);
+
+        conn.execute(
+            "UPDATE question_options SET option_text = 'Lyon' WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        assert!(search(&conn, "paris").is_empty());
+        assert_eq!(search(&conn, "lyon"), [1]);
+
+        conn.execute("DELETE FROM question_options WHERE id = 1", [])
+            .unwrap();
+        assert!(search(&conn, "lyon").is_empty());
+
+        conn.execute(
+            "INSERT INTO question_blanks (question_id, blank_index, correct_answer, acceptable_answers)
+             VALUES (1, 0, 'Paris', 'Paree')",
+            [],
+        )
+        .unwrap();
+        assert_eq!(search(&conn, "answers:paree"), [1]);
+
+        conn.execute(
+            "INSERT INTO question_matches (question_id, left_item, right_item) VALUES (1, 'Japan', 'Tokyo')",
+            [],
+        )
+        .unwrap();
+        assert_eq!(search(&conn, "matches:tokyo"), [1]);
+
+        // Accents are folded, so either spelling finds the question
+        conn.execute("UPDATE questions SET question_text = 'Quelle est la capitale de la Côte d''Ivoire ?' WHERE id = 1", [])
+            .unwrap();
+        assert!(search(&conn, "france").is_empty());
+        assert_eq!(search(&conn, "cote"), [1]);
+
+        conn.execute("DELETE FROM questions WHERE id = 1", [])
+            .unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM question_search", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 0);
+    }
+}
@@ -0,0 +1,12 @@
+-- Questions served in a quiz attempt, in the order they were shown
+CREATE TABLE IF NOT EXISTS quiz_attempt_questions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    attempt_id INTEGER NOT NULL,
+    question_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    option_order TEXT,
+    FOREIGN KEY (attempt_id) REFERENCES quiz_attempts(id) ON DELETE CASCADE,
+    FOREIGN KEY (question_id) REFERENCES questions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_attempt_questions_attempt ON quiz_attempt_questions(attempt_id);
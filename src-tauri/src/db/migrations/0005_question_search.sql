@@ -0,0 +1,98 @@
+-- Full-text index over each question and the text of its options, blanks and matches.
+-- The rowid is the question id. Triggers rebuild a question's row whenever it or one of
+-- its parts changes, so the index never needs a manual refresh.
+CREATE VIRTUAL TABLE IF NOT EXISTS question_search USING fts5(
+    question_text,
+    explanation,
+    source,
+    options,
+    answers,
+    matches,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- What gets indexed for each question
+CREATE VIEW IF NOT EXISTS question_search_source AS
+SELECT
+    q.id,
+    q.question_text,
+    q.explanation,
+    q.source,
+    (SELECT group_concat(option_text, ' ') FROM question_options WHERE question_id = q.id) AS options,
+    (SELECT group_concat(correct_answer || ' ' || coalesce(acceptable_answers, ''), ' ')
+     FROM question_blanks WHERE question_id = q.id) AS answers,
+    (SELECT group_concat(left_item || ' ' || right_item, ' ')
+     FROM question_matches WHERE question_id = q.id) AS matches
+FROM questions q;
+
+INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+SELECT * FROM question_search_source;
+
+CREATE TRIGGER IF NOT EXISTS question_search_insert AFTER INSERT ON questions BEGIN
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_update AFTER UPDATE ON questions BEGIN
+    DELETE FROM question_search WHERE rowid = OLD.id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_delete AFTER DELETE ON questions BEGIN
+    DELETE FROM question_search WHERE rowid = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_options_insert AFTER INSERT ON question_options BEGIN
+    DELETE FROM question_search WHERE rowid = NEW.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_options_update AFTER UPDATE ON question_options BEGIN
+    DELETE FROM question_search WHERE rowid = NEW.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_options_delete AFTER DELETE ON question_options BEGIN
+    DELETE FROM question_search WHERE rowid = OLD.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = OLD.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_blanks_insert AFTER INSERT ON question_blanks BEGIN
+    DELETE FROM question_search WHERE rowid = NEW.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_blanks_update AFTER UPDATE ON question_blanks BEGIN
+    DELETE FROM question_search WHERE rowid = NEW.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_blanks_delete AFTER DELETE ON question_blanks BEGIN
+    DELETE FROM question_search WHERE rowid = OLD.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = OLD.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_matches_insert AFTER INSERT ON question_matches BEGIN
+    DELETE FROM question_search WHERE rowid = NEW.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_matches_update AFTER UPDATE ON question_matches BEGIN
+    DELETE FROM question_search WHERE rowid = NEW.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = NEW.question_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS question_search_matches_delete AFTER DELETE ON question_matches BEGIN
+    DELETE FROM question_search WHERE rowid = OLD.question_id;
+    INSERT INTO question_search (rowid, question_text, explanation, source, options, answers, matches)
+    SELECT * FROM question_search_source WHERE id = OLD.question_id;
+END;
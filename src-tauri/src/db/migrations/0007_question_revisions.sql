@@ -0,0 +1,11 @@
+-- Snapshots of a question (as `QuestionWithDetails` JSON) after each change,
+-- numbered from 1 per question
+CREATE TABLE IF NOT EXISTS question_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    question_id INTEGER NOT NULL,
+    revision INTEGER NOT NULL,
+    snapshot TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (question_id, revision),
+    FOREIGN KEY (question_id) REFERENCES questions(id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- Every image path stored in the database, with the row that uses it. Revision
+-- snapshots count too, so restoring an old revision never finds its images gone.
+CREATE VIEW IF NOT EXISTS asset_references (table_name, row_id, column_name, path) AS
+    SELECT 'questions', id, 'question_image_path', question_image_path
+    FROM questions WHERE question_image_path IS NOT NULL AND question_image_path != ''
+    UNION ALL
+    SELECT 'question_options', id, 'option_image_path', option_image_path
+    FROM question_options WHERE option_image_path IS NOT NULL AND option_image_path != ''
+    UNION ALL
+    SELECT 'question_matches', id, 'left_image_path', left_image_path
+    FROM question_matches WHERE left_image_path IS NOT NULL AND left_image_path != ''
+    UNION ALL
+    SELECT 'question_matches', id, 'right_image_path', right_image_path
+    FROM question_matches WHERE right_image_path IS NOT NULL AND right_image_path != ''
+    UNION ALL
+    SELECT 'question_revisions', r.id, 'snapshot', j.value
+    FROM question_revisions r, json_tree(r.snapshot) j
+    WHERE j.key LIKE '%ImagePath' AND j.type = 'text' AND j.value != '';
@@ -1,14 +1,40 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, Result, Transaction};
 use std::fs;
 use std::sync::Mutex;
 use tauri::{AppHandle, Manager};
 
-pub mod models;
+use crate::error::AppResult;
 
-const SCHEMA_SQL: &str = include_str!("schema.sql");
+pub mod migrations;
+pub mod models;
 
 pub struct DbConnection(pub Mutex<Connection>);
 
+impl DbConnection {
+    /// Run `f` inside a transaction on the shared connection.
+    ///
+    /// Commits when `f` returns `Ok`; on `Err` the transaction is dropped and
+    /// rolled back, so commands that write several rows never leave partial data.
+    pub fn transaction<T, F>(&self, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&Transaction) -> AppResult<T>,
+    {
+        let mut conn = self.0.lock()?;
+        with_transaction(&mut conn, f)
+    }
+}
+
+/// Run `f` inside a transaction on `conn`, committing only if it succeeds
+pub fn with_transaction<T, F>(conn: &mut Connection, f: F) -> AppResult<T>
+where
+    F: FnOnce(&Transaction) -> AppResult<T>,
+{
+    let tx = conn.transaction()?;
+    let value = f(&tx)?;
+    tx.commit()?;
+    Ok(value)
+}
+
 /// Get the path to the database file
 pub fn get_db_path(app: &AppHandle) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
     let app_data_dir = app.path().app_data_dir()?;
@@ -26,42 +52,13 @@ pub async fn init_database(app: &AppHandle) -> Result<(), Box<dyn std::error::Er
     println!("Database path: {:?}", db_path);
 
     // Open connection
-    let conn = Connection::open(&db_path)?;
+    let mut conn = Connection::open(&db_path)?;
 
     // Enable foreign key constraints (required for CASCADE deletes)
     conn.execute("PRAGMA foreign_keys = ON", [])?;
 
-    // Execute schema
-    let statements: Vec<&str> = SCHEMA_SQL
-        .split(';')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect();
-
-    for statement in statements {
-        conn.execute(statement, [])?;
-    }
-
-    // Run migrations to add new columns if they don't exist
-    // Migration for input_type and dropdown_options in question_blanks
-    let _ = conn.execute(
-        "ALTER TABLE question_blanks ADD COLUMN input_type TEXT DEFAULT 'INPUT' CHECK (input_type IN ('INPUT', 'DROPDOWN'))",
-        [],
-    );
-    let _ = conn.execute(
-        "ALTER TABLE question_blanks ADD COLUMN dropdown_options TEXT",
-        [],
-    );
-
-    // Migration for image paths in question_matches
-    let _ = conn.execute(
-        "ALTER TABLE question_matches ADD COLUMN left_image_path TEXT",
-        [],
-    );
-    let _ = conn.execute(
-        "ALTER TABLE question_matches ADD COLUMN right_image_path TEXT",
-        [],
-    );
+    // Bring the schema up to date
+    migrations::run_migrations(&mut conn)?;
 
     println!("Database initialized successfully");
 
@@ -71,4 +68,14 @@ pub async fn init_database(app: &AppHandle) -> Result<(), Box<dyn std::error::Er
     Ok(())
 }
 
+/// A migrated in-memory database with foreign keys on, for tests
+#[cfg(test)]
+pub fn test_connection() -> Connection {
+    let mut conn = Connection::open_in_memory().expect("in-memory database");
+    conn.execute("PRAGMA foreign_keys = ON", [])
+        .expect("foreign keys");
+    migrations::run_migrations(&mut conn).expect("migrations");
+    conn
+}
+
 // Note: get_connection helper is not needed since commands use State<DbConnection> directly
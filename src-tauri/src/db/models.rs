@@ -65,7 +65,7 @@ pub struct QuizAttempt {
     pub quiz_id: i64,
     pub started_at: String,
     pub completed_at: Option<String>,
-    pub score: Option<i32>,
+    pub score: Option<f64>,
     pub max_score: Option<i32>,
     pub percentage: Option<f64>,
     pub time_taken_seconds: Option<i32>,
@@ -122,7 +122,7 @@ pub struct ExamAttempt {
     pub exam_id: i64,
     pub started_at: String,
     pub completed_at: Option<String>,
-    pub score: Option<i32>,
+    pub score: Option<f64>,
     pub max_score: Option<i32>,
     pub percentage: Option<f64>,
     pub time_taken_seconds: Option<i32>,
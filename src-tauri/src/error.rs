@@ -0,0 +1,177 @@
+use std::fmt;
+use std::sync::PoisonError;
+
+use rusqlite::ErrorCode;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+
+/// Error returned by every Tauri command.
+///
+/// Serialises to `{ kind, message, details }` so the frontend can branch on `kind`
+/// instead of parsing messages. `details` is `null` unless noted per variant.
+#[derive(Debug)]
+pub enum AppError {
+    /// The requested row does not exist
+    NotFound(String),
+    /// Input was rejected. `fields` name the offending inputs in camelCase,
+    /// matching the command arguments; `details` is `{ "fields": [...] }`.
+    Validation {
+        message: String,
+        fields: Vec<String>,
+    },
+    /// The write clashes with existing data, e.g. a unique or foreign key constraint
+    Conflict(String),
+    /// Reading or writing a file failed
+    Io(String),
+    /// An image path that does not point inside the app's `assets` directory
+    InvalidAssetPath(String),
+    /// A file that is not an image in one of the supported formats
+    UnsupportedImage(String),
+    /// A file over the size limit. `details` is `{ "size": ..., "limit": ... }` in bytes.
+    FileTooLarge {
+        message: String,
+        size: u64,
+        limit: u64,
+    },
+    /// Any other SQLite failure. `details` is `{ "code": "..." }` when SQLite gave one.
+    Database {
+        message: String,
+        code: Option<String>,
+    },
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound(message.into())
+    }
+
+    pub fn validation(message: impl Into<String>, fields: &[&str]) -> Self {
+        AppError::Validation {
+            message: message.into(),
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict(message.into())
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        AppError::Io(message.into())
+    }
+
+    pub fn invalid_asset_path(message: impl Into<String>) -> Self {
+        AppError::InvalidAssetPath(message.into())
+    }
+
+    pub fn unsupported_image(message: impl Into<String>) -> Self {
+        AppError::UnsupportedImage(message.into())
+    }
+
+    pub fn file_too_large(message: impl Into<String>, size: u64, limit: u64) -> Self {
+        AppError::FileTooLarge {
+            message: message.into(),
+            size,
+            limit,
+        }
+    }
+
+    pub fn database(message: impl Into<String>) -> Self {
+        AppError::Database {
+            message: message.into(),
+            code: None,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "NotFound",
+            AppError::Validation { .. } => "Validation",
+            AppError::Conflict(_) => "Conflict",
+            AppError::Io(_) => "Io",
+            AppError::InvalidAssetPath(_) => "InvalidAssetPath",
+            AppError::UnsupportedImage(_) => "UnsupportedImage",
+            AppError::FileTooLarge { .. } => "FileTooLarge",
+            AppError::Database { .. } => "Database",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Conflict(message)
+            | AppError::Io(message)
+            | AppError::InvalidAssetPath(message)
+            | AppError::UnsupportedImage(message)
+            | AppError::Validation { message, .. }
+            | AppError::FileTooLarge { message, .. }
+            | AppError::Database { message, .. } => message,
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            AppError::Validation { fields, .. } => json!({ "fields": fields }),
+            AppError::FileTooLarge { size, limit, .. } => json!({ "size": size, "limit": limit }),
+            AppError::Database {
+                code: Some(code), ..
+            } => json!({ "code": code }),
+            _ => Value::Null,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", self.message())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        match &error {
+            rusqlite::Error::QueryReturnedNoRows => AppError::not_found("Record not found"),
+            rusqlite::Error::SqliteFailure(failure, _) => match failure.code {
+                ErrorCode::ConstraintViolation => AppError::conflict(error.to_string()),
+                code => AppError::Database {
+                    message: error.to_string(),
+                    code: Some(format!("{:?}", code)),
+                },
+            },
+            _ => AppError::database(error.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        AppError::io(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::database(format!("Stored data is not valid JSON: {}", error))
+    }
+}
+
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(error: PoisonError<T>) -> Self {
+        AppError::database(format!("Database connection is unavailable: {}", error))
+    }
+}
@@ -0,0 +1,802 @@
+use std::collections::HashMap;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde_json::json;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::commands::questions::{QuestionBlank, QuestionWithDetails};
+use crate::error::{AppError, AppResult};
+use crate::formats::{
+    escape_xml, format_number, html_to_text, split_list, text_to_html, ExportIssue, ExportReport,
+};
+
+/// Fixed so that exporting again updates the same note type in Anki instead of adding a copy
+const MODEL_ID: i64 = 1_712_345_678_901;
+
+/// Anki's separator between the fields of a note
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+const CARD_CSS: &str = ".card { font-family: arial; font-size: 20px; text-align: left; color: black; background-color: white; }
+.correct { color: #15803d; font-weight: bold; }
+.explanation { margin-top: 1em; color: #555; }
+img { max-width: 100%; }";
+
+/// Schema of an Anki 2.1 collection (`collection.anki2`, schema version 11)
+const COLLECTION_SCHEMA: &str = "
+CREATE TABLE col (
+    id integer PRIMARY KEY, crt integer NOT NULL, mod integer NOT NULL, scm integer NOT NULL,
+    ver integer NOT NULL, dty integer NOT NULL, usn integer NOT NULL, ls integer NOT NULL,
+    conf text NOT NULL, models text NOT NULL, decks text NOT NULL, dconf text NOT NULL, tags text NOT NULL
+);
+CREATE TABLE notes (
+    id integer PRIMARY KEY, guid text NOT NULL, mid integer NOT NULL, mod integer NOT NULL,
+    usn integer NOT NULL, tags text NOT NULL, flds text NOT NULL, sfld integer NOT NULL,
+    csum integer NOT NULL, flags integer NOT NULL, data text NOT NULL
+);
+CREATE TABLE cards (
+    id integer PRIMARY KEY, nid integer NOT NULL, did integer NOT NULL, ord integer NOT NULL,
+    mod integer NOT NULL, usn integer NOT NULL, type integer NOT NULL, queue integer NOT NULL,
+    due integer NOT NULL, ivl integer NOT NULL, factor integer NOT NULL, reps integer NOT NULL,
+    lapses integer NOT NULL, left integer NOT NULL, odue integer NOT NULL, odid integer NOT NULL,
+    flags integer NOT NULL, data text NOT NULL
+);
+CREATE TABLE revlog (
+    id integer PRIMARY KEY, cid integer NOT NULL, usn integer NOT NULL, ease integer NOT NULL,
+    ivl integer NOT NULL, lastIvl integer NOT NULL, factor integer NOT NULL, time integer NOT NULL,
+    type integer NOT NULL
+);
+CREATE TABLE graves (usn integer NOT NULL, oid integer NOT NULL, type integer NOT NULL);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+/// Questions of one topic, written to the deck `path` (`["Subject", "Topic"]`)
+pub struct ExportDeck {
+    pub path: Vec<String>,
+    pub questions: Vec<QuestionWithDetails>,
+}
+
+/// Write an Anki package: a zip holding `collection.anki2`, the `media` map and the
+/// media files. Every deck path level becomes a deck and every question one note with
+/// a Question and an Answer field. `load_image` reads an image by its stored path;
+/// images that cannot be read are reported and left out.
+///
+/// The collection is built in a SQLite file under `scratch_dir`, which is removed again.
+pub fn write_package<W: Write + Seek>(
+    writer: W,
+    decks: &[ExportDeck],
+    scratch_dir: &Path,
+    load_image: &mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+) -> AppResult<ExportReport> {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| AppError::io(format!("Failed to get timestamp: {}", e)))?
+        .as_millis() as i64;
+
+    let collection_path = scratch_dir.join(format!("quizforge-{}.anki2", now_ms));
+    let result = write_collection(&collection_path, decks, now_ms, load_image).and_then(
+        |(report, media_files)| {
+            let collection = std::fs::read(&collection_path)?;
+            let mut zip = ZipWriter::new(writer);
+            add_file(&mut zip, "collection.anki2", &collection)?;
+
+            // Media files are stored as "0", "1", ...; the map gives their real names
+            let mut names = serde_json::Map::new();
+            for (index, (name, data)) in media_files.iter().enumerate() {
+                add_file(&mut zip, &index.to_string(), data)?;
+                names.insert(index.to_string(), json!(name));
+            }
+            add_file(&mut zip, "media", serde_json::to_string(&names)?.as_bytes())?;
+
+            zip.finish()
+                .map_err(|e| AppError::io(format!("Failed to write package: {}", e)))?;
+            Ok(report)
+        },
+    );
+    let _ = std::fs::remove_file(&collection_path);
+
+    result
+}
+
+fn add_file<W: Write + Seek>(zip: &mut ZipWriter<W>, path: &str, data: &[u8]) -> AppResult<()> {
+    zip.start_file(path, SimpleFileOptions::default())
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+    zip.write_all(data)?;
+    Ok(())
+}
+
+fn write_collection(
+    path: &Path,
+    decks: &[ExportDeck],
+    now_ms: i64,
+    load_image: &mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+) -> AppResult<(ExportReport, Vec<MediaFile>)> {
+    let now = now_ms / 1000;
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(COLLECTION_SCHEMA)?;
+    let tx = conn.transaction()?;
+
+    let mut report = ExportReport {
+        exported: 0,
+        issues: Vec::new(),
+    };
+    let mut media = Media {
+        load_image,
+        files: Vec::new(),
+        names: HashMap::new(),
+    };
+
+    // Every level of every path is a deck; Anki needs the parents to exist
+    let mut deck_ids: HashMap<String, i64> = HashMap::new();
+    let mut deck_json = serde_json::Map::new();
+    deck_json.insert("1".to_string(), deck("Default", 1, now));
+    for export_deck in decks {
+        for depth in 1..=export_deck.path.len() {
+            let name = deck_name(&export_deck.path[..depth]);
+            if !deck_ids.contains_key(&name) {
+                let id = now_ms + deck_ids.len() as i64 + 1;
+                deck_json.insert(id.to_string(), deck(&name, id, now));
+                deck_ids.insert(name, id);
+            }
+        }
+    }
+
+    let mut position = 0;
+    for export_deck in decks {
+        let deck_id = deck_ids[&deck_name(&export_deck.path)];
+        for question in &export_deck.questions {
+            let question_id = question.question.id;
+            let mut notes = Vec::new();
+            let (front, back) = match note_fields(question, &mut media, &mut notes) {
+                Ok(fields) => fields,
+                Err(message) => {
+                    report.issues.push(ExportIssue {
+                        question_id,
+                        message,
+                        skipped: true,
+                    });
+                    continue;
+                }
+            };
+
+            position += 1;
+            let note_id = now_ms + position;
+            let sort_field = html_to_text(&front);
+            tx.execute(
+                "INSERT INTO notes (id, guid, mid, mod, usn, tags, flds, sfld, csum, flags, data)
+                 VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+                (
+                    note_id,
+                    format!("quizforge-{}", question_id),
+                    MODEL_ID,
+                    now,
+                    format!(
+                        " quizforge {} ",
+                        question.question.difficulty.to_lowercase()
+                    ),
+                    format!("{}{}{}", front, FIELD_SEPARATOR, back),
+                    &sort_field,
+                    field_checksum(&sort_field),
+                ),
+            )?;
+            // A new card: type 0, queue 0, shown in export order
+            tx.execute(
+                "INSERT INTO cards (id, nid, did, ord, mod, usn, type, queue, due, ivl, factor,
+                 reps, lapses, left, odue, odid, flags, data)
+                 VALUES (?1, ?2, ?3, 0, ?4, -1, 0, 0, ?5, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+                (note_id, note_id, deck_id, now, position),
+            )?;
+
+            report.exported += 1;
+            report
+                .issues
+                .extend(notes.into_iter().map(|message| ExportIssue {
+                    question_id,
+                    message,
+                    skipped: false,
+                }));
+        }
+    }
+
+    let default_deck = decks
+        .first()
+        .map(|d| deck_ids[&deck_name(&d.path)])
+        .unwrap_or(1);
+    tx.execute(
+        "INSERT INTO col (id, crt, mod, scm, ver, dty, usn, ls, conf, models, decks, dconf, tags)
+         VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        (
+            now - now % 86_400,
+            now_ms,
+            collection_config(default_deck).to_string(),
+            json!({ MODEL_ID.to_string(): note_type(default_deck, now) }).to_string(),
+            serde_json::Value::Object(deck_json).to_string(),
+            json!({ "1": deck_options() }).to_string(),
+        ),
+    )?;
+    tx.commit()?;
+    drop(conn);
+
+    Ok((report, media.files))
+}
+
+/// Anki nests decks with `::`, so a `::` inside a name is flattened
+fn deck_name(path: &[String]) -> String {
+    path.iter()
+        .map(|part| part.replace("::", ":"))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Anki's duplicate check: the first 8 hex digits of the SHA-1 of the sort field
+fn field_checksum(text: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(text).digest().bytes();
+    i64::from(u32::from_be_bytes([
+        digest[0], digest[1], digest[2], digest[3],
+    ]))
+}
+
+/// File name the cards refer to, and contents
+type MediaFile = (String, Vec<u8>);
+
+/// Images copied into the package
+struct Media<'a> {
+    load_image: &'a mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+    files: Vec<MediaFile>,
+    /// Stored image path to media file name
+    names: HashMap<String, String>,
+}
+
+impl Media<'_> {
+    /// An `<img>` for a stored image, copied into the package on first use
+    fn image(&mut self, image_path: Option<&str>, notes: &mut Vec<String>) -> String {
+        let Some(image_path) = image_path else {
+            return String::new();
+        };
+        if let Some(name) = self.names.get(image_path) {
+            return format!("<img src=\"{}\">", escape_xml(name));
+        }
+
+        match (self.load_image)(image_path) {
+            Ok(data) => {
+                let base = image_path.rsplit('/').next().unwrap_or(image_path);
+                let mut name = base.to_string();
+                let mut counter = 1;
+                while self.files.iter().any(|(existing, _)| *existing == name) {
+                    name = format!("{}_{}", counter, base);
+                    counter += 1;
+                }
+                self.files.push((name.clone(), data));
+                self.names.insert(image_path.to_string(), name.clone());
+                format!("<img src=\"{}\">", escape_xml(&name))
+            }
+            Err(e) => {
+                notes.push(format!("Image {} was not exported: {}", image_path, e));
+                String::new()
+            }
+        }
+    }
+}
+
+/// The Question and Answer fields of a note, as HTML
+fn note_fields(
+    question: &QuestionWithDetails,
+    media: &mut Media,
+    notes: &mut Vec<String>,
+) -> Result<(String, String), String> {
+    let q = &question.question;
+    let mut front = text_to_html(&q.question_text);
+    let image = media.image(q.question_image_path.as_deref(), notes);
+    if !image.is_empty() {
+        front.push_str("<br>");
+        front.push_str(&image);
+    }
+
+    let mut back = match q.question_type.as_str() {
+        "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION" => {
+            if question.options.is_empty() {
+                return Err("Question has no options".to_string());
+            }
+            let mut html = String::from("<ol type=\"A\">");
+            for option in &question.options {
+                let image = media.image(option.option_image_path.as_deref(), notes);
+                let text = format!("{} {}", text_to_html(&option.option_text), image);
+                if option.is_correct {
+                    html.push_str(&format!(
+                        "<li class=\"correct\">&#10004; {}</li>",
+                        text.trim()
+                    ));
+                } else {
+                    html.push_str(&format!("<li>{}</li>", text.trim()));
+                }
+            }
+            html.push_str("</ol>");
+            html
+        }
+        "FILL_BLANK" | "FILL_BLANK_MULTIPLE" | "NUMERIC_INPUT" | "CALCULATION" => {
+            if question.blanks.is_empty() {
+                return Err("Question has no answer".to_string());
+            }
+            let answers: Vec<String> = question.blanks.iter().map(blank_answer).collect();
+            if let [answer] = answers.as_slice() {
+                format!("<div class=\"correct\">{}</div>", answer)
+            } else {
+                let items: String = answers.iter().map(|a| format!("<li>{}</li>", a)).collect();
+                format!("<ol class=\"correct\">{}</ol>", items)
+            }
+        }
+        "ORDERING" => {
+            if question.order_items.is_empty() {
+                return Err("Ordering question has no items".to_string());
+            }
+            let mut items: Vec<_> = question.order_items.iter().collect();
+            items.sort_by_key(|item| item.correct_position);
+            let items: String = items
+                .iter()
+                .map(|item| format!("<li>{}</li>", text_to_html(&item.item_text)))
+                .collect();
+            format!("<ol class=\"correct\">{}</ol>", items)
+        }
+        "MATCHING" => {
+            if question.matches.is_empty() {
+                return Err("Matching question has no pairs".to_string());
+            }
+            let mut html = String::from("<table>");
+            for pair in &question.matches {
+                let left = format!(
+                    "{} {}",
+                    text_to_html(&pair.left_item),
+                    media.image(pair.left_image_path.as_deref(), notes)
+                );
+                let right = format!(
+                    "{} {}",
+                    text_to_html(&pair.right_item),
+                    media.image(pair.right_image_path.as_deref(), notes)
+                );
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>&rarr;</td><td class=\"correct\">{}</td></tr>",
+                    left.trim(),
+                    right.trim()
+                ));
+            }
+            html.push_str("</table>");
+            html
+        }
+        other => return Err(format!("Unknown question type: {}", other)),
+    };
+
+    if let Some(explanation) = q
+        .explanation
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+    {
+        back.push_str(&format!(
+            "<div class=\"explanation\">{}</div>",
+            text_to_html(explanation)
+        ));
+    }
+
+    Ok((front, back))
+}
+
+/// A blank's answer with its accepted alternatives, tolerance and unit
+fn blank_answer(blank: &QuestionBlank) -> String {
+    let mut answer = text_to_html(blank.correct_answer.trim());
+    if blank.is_numeric {
+        if let Some(tolerance) = blank.numeric_tolerance.filter(|t| *t > 0.0) {
+            answer.push_str(&format!(" &plusmn; {}", format_number(tolerance)));
+        }
+    }
+    if let Some(unit) = blank
+        .unit
+        .as_deref()
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+    {
+        answer.push_str(&format!(" {}", text_to_html(unit)));
+    }
+    let alternatives: Vec<&str> = split_list(blank.acceptable_answers.as_deref()).collect();
+    if !alternatives.is_empty() {
+        answer.push_str(&format!(
+            " (also: {})",
+            text_to_html(&alternatives.join(", "))
+        ));
+    }
+    answer
+}
+
+fn deck(name: &str, id: i64, now: i64) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": name,
+        "desc": "",
+        "mod": now,
+        "usn": -1,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "dyn": 0,
+        "conf": 1,
+        "extendNew": 0,
+        "extendRev": 0,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+fn note_type(deck_id: i64, now: i64) -> serde_json::Value {
+    let field = |name: &str, ord: i32| {
+        json!({
+            "name": name,
+            "ord": ord,
+            "sticky": false,
+            "rtl": false,
+            "font": "Arial",
+            "size": 20,
+            "media": [],
+        })
+    };
+
+    json!({
+        "id": MODEL_ID,
+        "name": "QuizForge Question",
+        "type": 0,
+        "mod": now,
+        "usn": -1,
+        "sortf": 0,
+        "did": deck_id,
+        "tmpls": [{
+            "name": "Card 1",
+            "ord": 0,
+            "qfmt": "{{Question}}",
+            "afmt": "{{FrontSide}}<hr id=\"answer\">{{Answer}}",
+            "bqfmt": "",
+            "bafmt": "",
+            "did": null,
+        }],
+        "flds": [field("Question", 0), field("Answer", 1)],
+        "css": CARD_CSS,
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}",
+        "latexsvg": false,
+        "req": [[0, "any", [0]]],
+        "tags": [],
+        "vers": [],
+    })
+}
+
+fn collection_config(current_deck: i64) -> serde_json::Value {
+    json!({
+        "activeDecks": [current_deck],
+        "curDeck": current_deck,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": MODEL_ID.to_string(),
+        "nextPos": 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    })
+}
+
+/// Anki's default deck options
+fn deck_options() -> serde_json::Value {
+    json!({
+        "id": 1,
+        "name": "Default",
+        "mod": 0,
+        "usn": 0,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": {
+            "bury": true,
+            "delays": [1, 10],
+            "initialFactor": 2500,
+            "ints": [1, 4, 7],
+            "order": 1,
+            "perDay": 20,
+            "separate": true,
+        },
+        "lapse": {
+            "delays": [10],
+            "leechAction": 0,
+            "leechFails": 8,
+            "minInt": 1,
+            "mult": 0,
+        },
+        "rev": {
+            "bury": true,
+            "ease4": 1.3,
+            "fuzz": 0.05,
+            "ivlFct": 1,
+            "maxIvl": 36500,
+            "minSpace": 1,
+            "perDay": 100,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::formats::fixtures::question;
+
+    const TYPES: [&str; 9] = [
+        "SINGLE_CHOICE",
+        "MULTIPLE_CHOICE",
+        "IMAGE_IDENTIFICATION",
+        "FILL_BLANK",
+        "FILL_BLANK_MULTIPLE",
+        "NUMERIC_INPUT",
+        "CALCULATION",
+        "ORDERING",
+        "MATCHING",
+    ];
+
+    /// A package written and opened again: its notes, decks, media map and media files
+    struct Package {
+        notes: Vec<(String, String, String)>,
+        decks: Vec<String>,
+        media: serde_json::Map<String, serde_json::Value>,
+        files: Vec<String>,
+        report: ExportReport,
+    }
+
+    fn export(decks: &[ExportDeck], name: &str) -> Package {
+        let scratch =
+            std::env::temp_dir().join(format!("quizforge-anki-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&scratch).unwrap();
+        let mut package = Cursor::new(Vec::new());
+        let report = write_package(&mut package, decks, &scratch, &mut |path| {
+            if path.starts_with("missing/") {
+                Err(AppError::io("gone"))
+            } else {
+                Ok(path.as_bytes().to_vec())
+            }
+        })
+        .unwrap();
+        assert_eq!(
+            std::fs::read_dir(&scratch).unwrap().count(),
+            0,
+            "scratch collection left behind"
+        );
+
+        let mut archive = ZipArchive::new(package).unwrap();
+        let mut read = |entry: &str| {
+            let mut data = Vec::new();
+            archive
+                .by_name(entry)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            data
+        };
+        let collection_path = scratch.join("collection.anki2");
+        std::fs::write(&collection_path, read("collection.anki2")).unwrap();
+        let collection = Connection::open(&collection_path).unwrap();
+        let (notes, decks) = (notes(&collection), deck_names(&collection));
+        drop(collection);
+        let media: serde_json::Map<_, _> = serde_json::from_slice(&read("media")).unwrap();
+        let files = (0..media.len())
+            .map(|index| String::from_utf8(read(&index.to_string())).unwrap())
+            .collect();
+        std::fs::remove_dir_all(&scratch).unwrap();
+
+        Package {
+            notes,
+            decks,
+            media,
+            files,
+            report,
+        }
+    }
+
+    /// (guid, Question field, Answer field) of every note in card order
+    fn notes(collection: &Connection) -> Vec<(String, String, String)> {
+        let mut stmt = collection
+            .prepare(
+                "SELECT n.guid, n.flds FROM notes n JOIN cards c ON c.nid = n.id ORDER BY c.due",
+            )
+            .unwrap();
+        stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .unwrap()
+        .map(|row| {
+            let (guid, fields) = row.unwrap();
+            let (front, back) = fields.split_once(FIELD_SEPARATOR).unwrap();
+            (guid, front.to_string(), back.to_string())
+        })
+        .collect()
+    }
+
+    fn deck_names(collection: &Connection) -> Vec<String> {
+        let decks: String = collection
+            .query_row("SELECT decks FROM col", [], |row| row.get(0))
+            .unwrap();
+        let decks: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&decks).unwrap();
+        let mut names: Vec<String> = decks
+            .values()
+            .map(|d| d["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn every_type_is_written_as_a_note() {
+        let questions = TYPES
+            .iter()
+            .enumerate()
+            .map(|(index, question_type)| {
+                let mut question = question(question_type);
+                question.question.id = index as i64 + 1;
+                question
+            })
+            .collect();
+        let package = export(
+            &[ExportDeck {
+                path: vec!["Science".to_string()],
+                questions,
+            }],
+            "types",
+        );
+
+        assert_eq!(package.report.exported, TYPES.len());
+        assert!(
+            package.report.issues.is_empty(),
+            "{:?}",
+            package.report.issues
+        );
+
+        let expected = [
+            "<ol type=\"A\"><li class=\"correct\">&#10004; Paris</li><li>Lyon</li><li>Nice</li></ol>",
+            "<ol type=\"A\"><li class=\"correct\">&#10004; 2</li><li class=\"correct\">&#10004; 3</li><li>4</li></ol>",
+            "<ol type=\"A\"><li class=\"correct\">&#10004; Mitochondrion</li><li>Ribosome</li></ol>",
+            "<div class=\"correct\">Au (also: au)</div>",
+            "<ol class=\"correct\"><li>Helium (also: He)</li><li>Neon</li></ol>",
+            "<div class=\"correct\">9.81 &plusmn; 0.05</div>",
+            "<ol class=\"correct\"><li>12</li><li>5 &plusmn; 0.1</li></ol>",
+            "<ol class=\"correct\"><li>Mercury</li><li>Venus</li><li>Earth</li></ol>",
+            "<table><tr><td>France</td><td>&rarr;</td><td class=\"correct\">Paris</td></tr><tr><td>Japan</td><td>&rarr;</td><td class=\"correct\">Tokyo</td></tr><tr><td>Kenya</td><td>&rarr;</td><td class=\"correct\">Nairobi</td></tr></table>",
+        ];
+        assert_eq!(package.notes.len(), expected.len());
+        for ((index, (guid, front, back)), answer) in package.notes.iter().enumerate().zip(expected)
+        {
+            let original = question(TYPES[index]);
+            assert_eq!(guid, &format!("quizforge-{}", index + 1));
+            assert_eq!(front, &text_to_html(&original.question.question_text));
+            assert_eq!(
+                back,
+                &format!("{}<div class=\"explanation\">Worked answer.</div>", answer),
+                "{}",
+                TYPES[index]
+            );
+        }
+        assert_eq!(package.decks, ["Default", "Science"]);
+    }
+
+    #[test]
+    fn decks_nest_and_images_are_copied_once() {
+        let mut first = question("SINGLE_CHOICE");
+        first.question.question_image_path = Some("images/map.png".to_string());
+        first.options[0].option_image_path = Some("other/map.png".to_string());
+        first.options[1].option_image_path = Some("missing/lyon.png".to_string());
+        let mut second = question("MATCHING");
+        second.question.id = 2;
+        second.matches[0].left_image_path = Some("images/map.png".to_string());
+
+        let package = export(
+            &[
+                ExportDeck {
+                    path: vec!["Geography".to_string(), "Europe".to_string()],
+                    questions: vec![first],
+                },
+                ExportDeck {
+                    path: vec!["Geography".to_string(), "Asia::Pacific".to_string()],
+                    questions: vec![second],
+                },
+            ],
+            "media",
+        );
+
+        assert_eq!(package.report.exported, 2);
+        let issues: Vec<_> = package
+            .report
+            .issues
+            .iter()
+            .map(|i| (i.message.as_str(), i.skipped))
+            .collect();
+        assert_eq!(
+            issues,
+            [("Image missing/lyon.png was not exported: gone", false)]
+        );
+        assert_eq!(
+            package.decks,
+            [
+                "Default",
+                "Geography",
+                "Geography::Asia:Pacific",
+                "Geography::Europe"
+            ]
+        );
+
+        assert_eq!(
+            package.media,
+            *json!({ "0": "map.png", "1": "1_map.png" })
+                .as_object()
+                .unwrap()
+        );
+        assert_eq!(package.files, ["images/map.png", "other/map.png"]);
+        let notes = &package.notes;
+        assert!(
+            notes[0].1.ends_with("<br><img src=\"map.png\">"),
+            "{}",
+            notes[0].1
+        );
+        assert!(
+            notes[0].2.contains("Paris <img src=\"1_map.png\">"),
+            "{}",
+            notes[0].2
+        );
+        assert!(
+            notes[1].2.contains("<td>France <img src=\"map.png\"></td>"),
+            "{}",
+            notes[1].2
+        );
+    }
+
+    #[test]
+    fn questions_without_an_answer_are_skipped() {
+        let mut no_options = question("SINGLE_CHOICE");
+        no_options.options.clear();
+        let mut no_items = question("ORDERING");
+        no_items.question.id = 2;
+        no_items.order_items.clear();
+
+        let package = export(
+            &[ExportDeck {
+                path: vec!["Science".to_string()],
+                questions: vec![no_options, no_items, question("FILL_BLANK")],
+            }],
+            "skipped",
+        );
+
+        assert_eq!(package.report.exported, 1);
+        let issues: Vec<_> = package
+            .report
+            .issues
+            .iter()
+            .map(|i| (i.question_id, i.message.as_str(), i.skipped))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (1, "Question has no options", true),
+                (2, "Ordering question has no items", true),
+            ]
+        );
+        assert_eq!(package.notes.len(), 1);
+    }
+}
@@ -0,0 +1,790 @@
+use serde::Deserialize;
+
+use crate::commands::questions::{
+    CreateMatchPair, CreateNumericData, CreateOrderItem, CreateQuestionBlank, CreateQuestionData,
+    CreateQuestionOption, QUESTION_TYPES,
+};
+use crate::formats::{empty_question, format_number, ImportIssue, ParsedQuestion};
+
+/// How the rows of a CSV/TSV file map onto questions
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvImportOptions {
+    /// Field separator; detected from the first line when missing
+    pub delimiter: Option<char>,
+    /// Skip the first row
+    #[serde(default)]
+    pub has_header: bool,
+    /// Read a Quizlet export: term, definition. `columns` is ignored.
+    #[serde(default)]
+    pub quizlet: bool,
+    #[serde(default)]
+    pub columns: CsvColumns,
+    /// Type used for rows without a type column or cell; inferred from the row when missing
+    pub default_type: Option<String>,
+}
+
+/// 0-based column indexes. Only `question_text` is required.
+///
+/// The `correct` cell depends on the question type:
+/// - SINGLE_CHOICE / MULTIPLE_CHOICE / IMAGE_IDENTIFICATION: 1-based option numbers or
+///   letters (`2`, `B`, `1;3`, `A,C`)
+/// - TRUE_FALSE: `true` or `false`; imported as a SINGLE_CHOICE question
+/// - FILL_BLANK / FILL_BLANK_MULTIPLE: one answer per blank separated by `;`, with
+///   accepted alternatives separated by `|` (`Paris|paris;Berlin`)
+/// - NUMERIC_INPUT / CALCULATION: a number with an optional tolerance (`9.81 ± 0.05`,
+///   `9.81 +- 0.05`)
+/// - ORDERING: unused; the option cells hold the items in their correct order
+/// - MATCHING: unused; the option cells hold `left -> right` pairs
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvColumns {
+    pub question_text: usize,
+    pub question_type: Option<usize>,
+    #[serde(default)]
+    pub options: Vec<usize>,
+    pub correct: Option<usize>,
+    pub explanation: Option<usize>,
+    pub difficulty: Option<usize>,
+    pub points: Option<usize>,
+    pub source: Option<usize>,
+    pub unit: Option<usize>,
+}
+
+/// Read every row of a CSV/TSV file.
+///
+/// Rows that cannot be turned into a question are left out and reported with their
+/// line; the rest still go through the same validation as `create_question` when
+/// they are inserted.
+pub fn parse(
+    text: &str,
+    options: &CsvImportOptions,
+    subject_id: i64,
+    topic_id: i64,
+) -> Result<(Vec<ParsedQuestion>, Vec<ImportIssue>), String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let delimiter = match options.delimiter {
+        Some(delimiter) if delimiter.is_ascii() => delimiter as u8,
+        Some(delimiter) => return Err(format!("Unsupported delimiter: {}", delimiter)),
+        None if options.quizlet => b'\t',
+        None => detect_delimiter(text),
+    };
+
+    // Quizlet writes terms and definitions as they are, quotes included
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .quoting(!options.quizlet)
+        .from_reader(text.as_bytes());
+
+    let mut questions = Vec::new();
+    let mut issues = Vec::new();
+
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Failed to read the file: {}", e))?;
+        let line = record.position().map_or(index + 1, |p| p.line() as usize);
+        if index == 0 && options.has_header {
+            continue;
+        }
+        let cells: Vec<&str> = record.iter().map(str::trim).collect();
+        if cells.iter().all(|cell| cell.is_empty()) {
+            continue;
+        }
+
+        let row = if options.quizlet {
+            read_quizlet_row(&cells, subject_id, topic_id)
+        } else {
+            read_row(&cells, options, subject_id, topic_id)
+        };
+        match row {
+            Ok(data) => questions.push(ParsedQuestion { line, data }),
+            Err(message) => issues.push(ImportIssue {
+                line,
+                message,
+                skipped: true,
+            }),
+        }
+    }
+
+    Ok((questions, issues))
+}
+
+/// Tab when the first line has one, otherwise whichever of `,` and `;` it has more of
+fn detect_delimiter(text: &str) -> u8 {
+    let first_line = text.lines().next().unwrap_or("");
+    if first_line.contains('\t') {
+        b'\t'
+    } else if first_line.matches(';').count() > first_line.matches(',').count() {
+        b';'
+    } else {
+        b','
+    }
+}
+
+/// `term, definition`: the term is asked and the definition typed in
+fn read_quizlet_row(
+    cells: &[&str],
+    subject_id: i64,
+    topic_id: i64,
+) -> Result<CreateQuestionData, String> {
+    let term = cells.first().copied().unwrap_or("");
+    let definition = cells.get(1).copied().unwrap_or("");
+    if term.is_empty() || definition.is_empty() {
+        return Err("Row needs both a term and a definition".to_string());
+    }
+
+    let mut data = empty_question(subject_id, topic_id, "FILL_BLANK", term.to_string());
+    data.blanks = vec![input_blank(0, definition.to_string(), Vec::new())];
+    Ok(data)
+}
+
+fn read_row(
+    cells: &[&str],
+    options: &CsvImportOptions,
+    subject_id: i64,
+    topic_id: i64,
+) -> Result<CreateQuestionData, String> {
+    let columns = &options.columns;
+    let cell = |column: Option<usize>| {
+        column
+            .and_then(|column| cells.get(column).copied())
+            .filter(|cell| !cell.is_empty())
+    };
+
+    let question_text = cell(Some(columns.question_text)).unwrap_or("").to_string();
+    let choices: Vec<&str> = columns
+        .options
+        .iter()
+        .filter_map(|c| cell(Some(*c)))
+        .collect();
+    let correct = cell(columns.correct);
+
+    let question_type = match cell(columns.question_type).or(options.default_type.as_deref()) {
+        Some(question_type) => normalize_type(question_type),
+        None => infer_type(&choices, correct),
+    };
+
+    let mut data = match question_type.as_str() {
+        "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION" => {
+            let mut data = empty_question(subject_id, topic_id, &question_type, question_text);
+            data.options = read_choices(&question_type, &choices, correct)?;
+            data
+        }
+        "TRUE_FALSE" => {
+            let answer = correct.ok_or("Row has no correct answer")?;
+            let answer =
+                parse_bool(answer).ok_or_else(|| format!("Not true or false: {}", answer))?;
+            let mut data = empty_question(subject_id, topic_id, "SINGLE_CHOICE", question_text);
+            data.options = ["True", "False"]
+                .iter()
+                .enumerate()
+                .map(|(index, text)| CreateQuestionOption {
+                    option_text: text.to_string(),
+                    option_image_path: None,
+                    is_correct: (index == 0) == answer,
+                    display_order: index as i32,
+                })
+                .collect();
+            data
+        }
+        "FILL_BLANK" | "FILL_BLANK_MULTIPLE" => {
+            let mut data = empty_question(subject_id, topic_id, &question_type, question_text);
+            data.blanks = read_blanks(correct.ok_or("Row has no correct answer")?)?;
+            data
+        }
+        "NUMERIC_INPUT" => {
+            let (value, tolerance) = parse_numeric(correct.ok_or("Row has no correct answer")?)?;
+            let mut data = empty_question(subject_id, topic_id, &question_type, question_text);
+            data.numeric_data = Some(CreateNumericData {
+                correct_answer: format_number(value),
+                tolerance: tolerance.map_or_else(|| "0.1".to_string(), format_number),
+                unit: cell(columns.unit).map(str::to_string),
+            });
+            data
+        }
+        "CALCULATION" => {
+            let (value, tolerance) = parse_numeric(correct.ok_or("Row has no correct answer")?)?;
+            let mut data = empty_question(subject_id, topic_id, &question_type, question_text);
+            data.blanks = vec![CreateQuestionBlank {
+                is_numeric: true,
+                numeric_tolerance: tolerance,
+                unit: cell(columns.unit).map(str::to_string),
+                ..input_blank(0, format_number(value), Vec::new())
+            }];
+            data
+        }
+        "ORDERING" => {
+            if choices.len() < 2 {
+                return Err("Ordering question needs at least two items".to_string());
+            }
+            let mut data = empty_question(subject_id, topic_id, &question_type, question_text);
+            data.order_items = Some(
+                choices
+                    .iter()
+                    .enumerate()
+                    .map(|(index, text)| CreateOrderItem {
+                        text: text.to_string(),
+                        correct_position: index as i32 + 1,
+                    })
+                    .collect(),
+            );
+            data
+        }
+        "MATCHING" => {
+            if choices.len() < 2 {
+                return Err("Matching question needs at least two pairs".to_string());
+            }
+            let pairs = choices
+                .iter()
+                .map(|choice| {
+                    let (left, right) = choice.split_once("->").ok_or_else(|| {
+                        format!("Match pair is not written as left -> right: {}", choice)
+                    })?;
+                    Ok(CreateMatchPair {
+                        left_item: left.trim().to_string(),
+                        right_item: right.trim().to_string(),
+                        left_image_path: None,
+                        right_image_path: None,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let mut data = empty_question(subject_id, topic_id, &question_type, question_text);
+            data.match_pairs = Some(pairs);
+            data
+        }
+        other => return Err(format!("Unknown question type: {}", other)),
+    };
+
+    data.explanation = cell(columns.explanation).map(str::to_string);
+    data.source = cell(columns.source).map(str::to_string);
+    if let Some(difficulty) = cell(columns.difficulty) {
+        data.difficulty = difficulty.to_uppercase();
+    }
+    if let Some(points) = cell(columns.points) {
+        data.points = points
+            .parse()
+            .map_err(|_| format!("Points must be a whole number: {}", points))?;
+    }
+
+    Ok(data)
+}
+
+/// `single choice`, `Multiple-Choice` and `MULTIPLE_CHOICE` all name the same type
+fn normalize_type(question_type: &str) -> String {
+    let normalized = question_type.trim().to_uppercase().replace([' ', '-'], "_");
+    match normalized.as_str() {
+        "MCQ" | "CHOICE" => "SINGLE_CHOICE".to_string(),
+        "TF" | "TRUE/FALSE" | "TRUEFALSE" | "BOOLEAN" => "TRUE_FALSE".to_string(),
+        "NUMERIC" => "NUMERIC_INPUT".to_string(),
+        _ => normalized,
+    }
+}
+
+/// Rows with options are choice questions, rows with a numeric answer are numeric
+/// questions and anything else is a fill in the blank
+fn infer_type(choices: &[&str], correct: Option<&str>) -> String {
+    if !choices.is_empty() {
+        let correct_count = correct.map_or(0, |correct| split_indexes(correct).count());
+        if correct_count > 1 {
+            "MULTIPLE_CHOICE".to_string()
+        } else {
+            "SINGLE_CHOICE".to_string()
+        }
+    } else if correct.is_some_and(|correct| parse_numeric(correct).is_ok()) {
+        "NUMERIC_INPUT".to_string()
+    } else {
+        "FILL_BLANK".to_string()
+    }
+}
+
+fn read_choices(
+    question_type: &str,
+    choices: &[&str],
+    correct: Option<&str>,
+) -> Result<Vec<CreateQuestionOption>, String> {
+    if choices.len() < 2 {
+        return Err("Choice question needs at least two options".to_string());
+    }
+    let correct = correct.ok_or("Row has no correct answer")?;
+
+    let mut correct_indexes = Vec::new();
+    for token in split_indexes(correct) {
+        let index = parse_index(token)
+            .ok_or_else(|| format!("Not an option number or letter: {}", token))?;
+        if index >= choices.len() {
+            return Err(format!(
+                "Correct answer {} is not one of the {} options",
+                token,
+                choices.len()
+            ));
+        }
+        correct_indexes.push(index);
+    }
+    if correct_indexes.is_empty() {
+        return Err("Row has no correct answer".to_string());
+    }
+    if question_type != "MULTIPLE_CHOICE" && correct_indexes.len() > 1 {
+        return Err(format!(
+            "{} question can only have one correct answer",
+            question_type
+        ));
+    }
+
+    Ok(choices
+        .iter()
+        .enumerate()
+        .map(|(index, text)| CreateQuestionOption {
+            option_text: text.to_string(),
+            option_image_path: None,
+            is_correct: correct_indexes.contains(&index),
+            display_order: index as i32,
+        })
+        .collect())
+}
+
+fn split_indexes(correct: &str) -> impl Iterator<Item = &str> {
+    correct
+        .split([',', ';', '|', ' '])
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// A 1-based option number or an option letter, as a 0-based index
+fn parse_index(token: &str) -> Option<usize> {
+    if let Ok(number) = token.parse::<usize>() {
+        return number.checked_sub(1);
+    }
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_alphabetic() => {
+            Some((letter.to_ascii_uppercase() as u8 - b'A') as usize)
+        }
+        _ => None,
+    }
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.to_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Some(true),
+        "false" | "f" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// `Paris|paris;Berlin`: blanks separated by `;`, alternatives by `|`
+fn read_blanks(correct: &str) -> Result<Vec<CreateQuestionBlank>, String> {
+    correct
+        .split(';')
+        .map(str::trim)
+        .filter(|answer| !answer.is_empty())
+        .enumerate()
+        .map(|(index, answer)| {
+            let mut accepted = answer.split('|').map(str::trim).filter(|a| !a.is_empty());
+            let correct_answer = accepted.next().unwrap_or("").to_string();
+            let alternatives: Vec<String> = accepted.map(str::to_string).collect();
+            // Alternatives are stored as a comma-separated list
+            if let Some(alternative) = alternatives.iter().find(|a| a.contains(',')) {
+                return Err(format!(
+                    "Alternative answer \"{}\" contains a comma",
+                    alternative
+                ));
+            }
+            Ok(input_blank(index as i32, correct_answer, alternatives))
+        })
+        .collect()
+}
+
+fn input_blank(
+    blank_index: i32,
+    correct_answer: String,
+    alternatives: Vec<String>,
+) -> CreateQuestionBlank {
+    CreateQuestionBlank {
+        blank_index,
+        correct_answer,
+        acceptable_answers: (!alternatives.is_empty()).then(|| alternatives.join(", ")),
+        is_numeric: false,
+        numeric_tolerance: None,
+        unit: None,
+        input_type: "INPUT".to_string(),
+        dropdown_options: None,
+    }
+}
+
+/// `9.81`, `9.81 ± 0.05` or `9.81 +- 0.05`
+fn parse_numeric(text: &str) -> Result<(f64, Option<f64>), String> {
+    let invalid = || format!("Not a number with an optional ± tolerance: {}", text);
+    let (value, tolerance) = match text.split_once('±').or_else(|| text.split_once("+-")) {
+        Some((value, tolerance)) => (value, Some(tolerance)),
+        None => (text, None),
+    };
+
+    let value: f64 = value.trim().parse().map_err(|_| invalid())?;
+    let tolerance = match tolerance {
+        Some(tolerance) => Some(
+            tolerance
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| invalid())?
+                .abs(),
+        ),
+        None => None,
+    };
+    if !value.is_finite() || tolerance.is_some_and(|t| !t.is_finite()) {
+        return Err(invalid());
+    }
+    Ok((value, tolerance))
+}
+
+/// Whether `question_type` is one the importer understands
+pub fn is_known_type(question_type: &str) -> bool {
+    let normalized = normalize_type(question_type);
+    normalized == "TRUE_FALSE" || QUESTION_TYPES.contains(&normalized.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::fixtures::{parsed_content, question, stored_content};
+
+    /// type, text, four option columns, correct, explanation, unit
+    fn full_columns() -> CsvImportOptions {
+        CsvImportOptions {
+            columns: CsvColumns {
+                question_type: Some(0),
+                question_text: 1,
+                options: vec![2, 3, 4, 5],
+                correct: Some(6),
+                explanation: Some(7),
+                unit: Some(8),
+                ..CsvColumns::default()
+            },
+            ..CsvImportOptions::default()
+        }
+    }
+
+    /// Tab-separated rows, which need no quoting
+    fn tsv(rows: &[[&str; 9]]) -> String {
+        rows.iter()
+            .map(|row| row.join("\t"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn every_type_is_read_from_its_documented_cells() {
+        let rows = [
+            [
+                "SINGLE_CHOICE",
+                "What is the capital of France?",
+                "Paris",
+                "Lyon",
+                "Nice",
+                "",
+                "1",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "MULTIPLE_CHOICE",
+                "Which of these numbers are prime?",
+                "2",
+                "3",
+                "4",
+                "",
+                "A;B",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "IMAGE_IDENTIFICATION",
+                "Which organelle is shown?",
+                "Mitochondrion",
+                "Ribosome",
+                "",
+                "",
+                "a",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "FILL_BLANK",
+                "The chemical symbol for gold is ___.",
+                "",
+                "",
+                "",
+                "",
+                "Au|au",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "FILL_BLANK_MULTIPLE",
+                "___ and ___ are noble gases.",
+                "",
+                "",
+                "",
+                "",
+                "Helium|He;Neon",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "NUMERIC_INPUT",
+                "How fast does an object fall after one second, in m/s?",
+                "",
+                "",
+                "",
+                "",
+                "9.81 ± 0.05",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "CALCULATION",
+                "A rectangle is 3 by 4. Its area is ___ and its diagonal ___.",
+                "",
+                "",
+                "",
+                "",
+                "12 +- 0",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "ORDERING",
+                "Order the planets by distance from the Sun.",
+                "Mercury",
+                "Venus",
+                "Earth",
+                "",
+                "",
+                "Worked answer.",
+                "",
+            ],
+            [
+                "MATCHING",
+                "Match each country to its capital.",
+                "France -> Paris",
+                "Japan -> Tokyo",
+                "Kenya -> Nairobi",
+                "",
+                "",
+                "Worked answer.",
+                "",
+            ],
+        ];
+        let (parsed, issues) = parse(&tsv(&rows), &full_columns(), 1, 2).unwrap();
+
+        assert!(issues.is_empty(), "{:?}", issues);
+        assert_eq!(parsed.len(), rows.len());
+        for (row, parsed) in rows.iter().zip(&parsed) {
+            let mut expected = question(row[0]);
+            // A row holds one numeric answer
+            if row[0] == "CALCULATION" {
+                expected.blanks.truncate(1);
+            }
+            assert_eq!(
+                parsed_content(&parsed.data),
+                stored_content(&expected),
+                "{}",
+                row[0]
+            );
+            assert_eq!((parsed.data.subject_id, parsed.data.topic_id), (1, 2));
+        }
+        let lines: Vec<_> = parsed.iter().map(|p| p.line).collect();
+        assert_eq!(lines, (1..=rows.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn true_false_rows_become_single_choice() {
+        let text = "tf,The Earth is flat.,,,,,no\nTrue/False,Water is wet.,,,,,yes";
+        let (parsed, issues) = parse(text, &full_columns(), 1, 1).unwrap();
+
+        assert!(issues.is_empty(), "{:?}", issues);
+        let answers: Vec<Vec<_>> = parsed
+            .iter()
+            .map(|p| {
+                assert_eq!(p.data.question_type, "SINGLE_CHOICE");
+                p.data
+                    .options
+                    .iter()
+                    .map(|o| (o.option_text.as_str(), o.is_correct))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            answers,
+            [
+                vec![("True", false), ("False", true)],
+                vec![("True", true), ("False", false)],
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_types_are_rejected() {
+        assert!(is_known_type("multiple-choice"));
+        assert!(is_known_type("Numeric"));
+        assert!(is_known_type("true/false"));
+        assert!(!is_known_type("ESSAY"));
+
+        let text = "Essay;Discuss the causes.;;;;;\nsingle choice;Pick one;a;b;;;B";
+        let (parsed, issues) = parse(text, &full_columns(), 1, 1).unwrap();
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].data.question_type, "SINGLE_CHOICE");
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].skipped), (1, true));
+        assert_eq!(issues[0].message, "Unknown question type: ESSAY");
+    }
+
+    #[test]
+    fn rows_that_do_not_fit_their_type_are_reported_by_line() {
+        let rows = [
+            [
+                "SINGLE_CHOICE",
+                "Only one option",
+                "a",
+                "",
+                "",
+                "",
+                "1",
+                "",
+                "",
+            ],
+            [
+                "SINGLE_CHOICE",
+                "Answer out of range",
+                "a",
+                "b",
+                "",
+                "",
+                "3",
+                "",
+                "",
+            ],
+            [
+                "SINGLE_CHOICE",
+                "Two answers",
+                "a",
+                "b",
+                "",
+                "",
+                "1;2",
+                "",
+                "",
+            ],
+            [
+                "FILL_BLANK",
+                "Comma in an alternative ___",
+                "",
+                "",
+                "",
+                "",
+                "a|b, c",
+                "",
+                "",
+            ],
+            [
+                "NUMERIC_INPUT",
+                "Not a number",
+                "",
+                "",
+                "",
+                "",
+                "ten",
+                "",
+                "",
+            ],
+            [
+                "MATCHING", "No arrow", "a - b", "c -> d", "", "", "", "", "",
+            ],
+        ];
+        let (parsed, issues) = parse(&tsv(&rows), &full_columns(), 1, 1).unwrap();
+
+        assert!(parsed.is_empty());
+        let issues: Vec<_> = issues
+            .iter()
+            .map(|i| (i.line, i.message.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (1, "Choice question needs at least two options"),
+                (2, "Correct answer 3 is not one of the 2 options"),
+                (3, "SINGLE_CHOICE question can only have one correct answer"),
+                (4, "Alternative answer \"b, c\" contains a comma"),
+                (5, "Not a number with an optional ± tolerance: ten"),
+                (6, "Match pair is not written as left -> right: a - b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn types_are_inferred_when_there_is_no_type_column() {
+        let options = CsvImportOptions {
+            has_header: true,
+            columns: CsvColumns {
+                question_text: 0,
+                options: vec![1, 2, 3],
+                correct: Some(4),
+                ..CsvColumns::default()
+            },
+            ..CsvImportOptions::default()
+        };
+        let text = "\u{feff}Question,A,B,C,Answer\nPick one,x,y,z,B\nPick two,x,y,z,\"A,C\"\n\"Speed, in m/s\",,,,3\nCapital of Peru?,,,,Lima\n,,,,\n";
+        let (parsed, issues) = parse(text, &options, 1, 1).unwrap();
+
+        assert!(issues.is_empty(), "{:?}", issues);
+        let types: Vec<_> = parsed
+            .iter()
+            .map(|p| (p.line, p.data.question_type.as_str()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                (2, "SINGLE_CHOICE"),
+                (3, "MULTIPLE_CHOICE"),
+                (4, "NUMERIC_INPUT"),
+                (5, "FILL_BLANK")
+            ]
+        );
+        assert_eq!(parsed[2].data.question_text, "Speed, in m/s");
+        // A missing tolerance gets the editor's default
+        assert_eq!(
+            parsed[2].data.numeric_data.as_ref().unwrap().tolerance,
+            "0.1"
+        );
+    }
+
+    #[test]
+    fn quizlet_rows_keep_quotes_and_become_fill_in_the_blank() {
+        let options = CsvImportOptions {
+            quizlet: true,
+            ..CsvImportOptions::default()
+        };
+        let (parsed, issues) = parse("\"cat\"\tle chat\nlonely term\t", &options, 1, 1).unwrap();
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].data.question_text, "\"cat\"");
+        assert_eq!(parsed[0].data.blanks[0].correct_answer, "le chat");
+        assert_eq!(issues[0].message, "Row needs both a term and a definition");
+    }
+
+    #[test]
+    fn delimiter_is_detected_from_the_first_line() {
+        assert_eq!(detect_delimiter("a\tb,c,d"), b'\t');
+        assert_eq!(detect_delimiter("a;b;c,d\ne,f,g,h"), b';');
+        assert_eq!(detect_delimiter("a,b;c"), b',');
+        let options = CsvImportOptions {
+            delimiter: Some('é'),
+            ..CsvImportOptions::default()
+        };
+        assert_eq!(
+            parse("a", &options, 1, 1).unwrap_err(),
+            "Unsupported delimiter: é"
+        );
+    }
+}
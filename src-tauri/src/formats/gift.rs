@@ -0,0 +1,897 @@
+use crate::commands::questions::{
+    CreateMatchPair, CreateNumericData, CreateQuestionBlank, CreateQuestionData,
+    CreateQuestionOption, QuestionBlank, QuestionWithDetails,
+};
+use crate::formats::{empty_question, find_blank, format_number, ImportIssue, ParsedQuestion};
+
+/// Characters GIFT reserves; they need a backslash when used as text
+const RESERVED: &[char] = &['~', '=', '#', '{', '}', ':', '\\'];
+
+/// Text format prefixes a GIFT question may start with
+const FORMAT_PREFIXES: &[&str] = &["[html]", "[moodle]", "[plain]", "[markdown]"];
+
+/// One answer inside a `{ ... }` block
+struct Answer {
+    /// `=` or `~`
+    marker: char,
+    /// Credit in percent: 100 for `=`, 0 for a bare `~`, otherwise the `%n%` weight
+    weight: f64,
+    /// Still escaped; run through `unescape` before storing
+    text: String,
+    has_feedback: bool,
+}
+
+impl Answer {
+    fn is_correct(&self) -> bool {
+        self.weight > 0.0
+    }
+}
+
+/// Read every question in a GIFT file.
+///
+/// Items that cannot be represented are left out and reported with the line they
+/// start on; items that lose part of their content are imported and reported too.
+pub fn parse(
+    text: &str,
+    subject_id: i64,
+    topic_id: i64,
+) -> (Vec<ParsedQuestion>, Vec<ImportIssue>) {
+    let mut questions = Vec::new();
+    let mut issues = Vec::new();
+
+    for (line, item) in split_items(text) {
+        match parse_item(&item, subject_id, topic_id) {
+            Ok(Some((data, notes))) => {
+                issues.extend(notes.into_iter().map(|message| ImportIssue {
+                    line,
+                    message,
+                    skipped: false,
+                }));
+                questions.push(ParsedQuestion { line, data });
+            }
+            Ok(None) => {}
+            Err(message) => issues.push(ImportIssue {
+                line,
+                message,
+                skipped: true,
+            }),
+        }
+    }
+
+    (questions, issues)
+}
+
+/// Group the file into items separated by blank lines, dropping `//` comments.
+/// Each item comes with the 1-based line it starts on.
+fn split_items(text: &str) -> Vec<(usize, String)> {
+    let mut items = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("//") {
+            continue;
+        }
+        if trimmed.is_empty() {
+            items.extend(current.take());
+            continue;
+        }
+        match current.as_mut() {
+            Some((_, item)) => {
+                item.push('\n');
+                item.push_str(line);
+            }
+            None => current = Some((index + 1, line.to_string())),
+        }
+    }
+    items.extend(current);
+
+    items
+}
+
+/// Parse one item. `Ok(None)` means it holds no question (e.g. only `$CATEGORY`),
+/// `Err` gives the reason it cannot be imported.
+fn parse_item(
+    item: &str,
+    subject_id: i64,
+    topic_id: i64,
+) -> Result<Option<(CreateQuestionData, Vec<String>)>, String> {
+    // Categories are Moodle's folders; the caller already chose the topic
+    let item: String = item
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("$CATEGORY:"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut rest = item.trim();
+    if rest.is_empty() {
+        return Ok(None);
+    }
+
+    let mut title = None;
+    if let Some(after) = rest.strip_prefix("::") {
+        let end = find_unescaped(after, "::").ok_or("Question title is missing its closing ::")?;
+        title = Some(unescape(&after[..end]));
+        rest = after[end + 2..].trim_start();
+    }
+
+    for prefix in FORMAT_PREFIXES {
+        if let Some(after) = rest.strip_prefix(prefix) {
+            rest = after;
+            break;
+        }
+    }
+
+    let open = find_unescaped(rest, "{")
+        .ok_or("Description items without an answer block are not supported")?;
+    let close =
+        open + find_unescaped(&rest[open..], "}").ok_or("Answer block is missing its closing }")?;
+    let before = &rest[..open];
+    let after = &rest[close + 1..];
+    if find_unescaped(after, "{").is_some() {
+        return Err("Questions with more than one answer block are not supported".to_string());
+    }
+
+    let (body, general_feedback) = match find_unescaped(&rest[open + 1..close], "####") {
+        Some(index) => {
+            let body = &rest[open + 1..open + 1 + index];
+            (body, Some(unescape(&rest[open + 1 + index + 4..close])))
+        }
+        None => (&rest[open + 1..close], None),
+    };
+    let body = body.trim();
+
+    // Text after the block makes this a missing-word question
+    let missing_word = !after.trim().is_empty();
+    let question_text = if missing_word {
+        format!("{}___{}", unescape(before), unescape(after))
+            .trim()
+            .to_string()
+    } else {
+        unescape(before).trim().to_string()
+    };
+    if question_text.is_empty() || question_text == "___" {
+        return Err("Question has no text".to_string());
+    }
+
+    if body.is_empty() {
+        return Err("Essay questions are not supported".to_string());
+    }
+
+    let mut notes = Vec::new();
+    let mut data = if let Some(numeric) = body.strip_prefix('#') {
+        parse_numeric(
+            numeric,
+            missing_word,
+            subject_id,
+            topic_id,
+            question_text,
+            &mut notes,
+        )?
+    } else if let Some(correct) = parse_true_false(body) {
+        if missing_word {
+            return Err("True/false answers cannot fill a blank".to_string());
+        }
+        let mut data = empty_question(subject_id, topic_id, "SINGLE_CHOICE", question_text);
+        data.options = vec![
+            choice_option("True", correct, 0),
+            choice_option("False", !correct, 1),
+        ];
+        data
+    } else {
+        let answers = parse_answers(body)?;
+        if answers.iter().any(|a| a.has_feedback) {
+            notes.push("Per-answer feedback was not imported".to_string());
+        }
+        if answers
+            .iter()
+            .any(|a| find_unescaped(&a.text, "->").is_some())
+        {
+            if missing_word {
+                return Err("Matching answers cannot fill a blank".to_string());
+            }
+            parse_matching(&answers, subject_id, topic_id, question_text, &mut notes)?
+        } else if answers.iter().all(|a| a.marker == '=') {
+            parse_short_answer(&answers, subject_id, topic_id, question_text, &mut notes)?
+        } else if missing_word {
+            parse_missing_word_choice(&answers, subject_id, topic_id, question_text)?
+        } else {
+            parse_choice(&answers, subject_id, topic_id, question_text)?
+        }
+    };
+
+    data.source = title
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+    data.explanation = general_feedback
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty());
+    Ok(Some((data, notes)))
+}
+
+fn parse_true_false(body: &str) -> Option<bool> {
+    // Feedback for a true/false item follows the answer as `#wrong#right`
+    let answer = match find_unescaped(body, "#") {
+        Some(index) => &body[..index],
+        None => body,
+    };
+    match answer.trim().to_uppercase().as_str() {
+        "T" | "TRUE" => Some(true),
+        "F" | "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+/// Split a block body on unescaped `=` and `~` markers
+fn parse_answers(body: &str) -> Result<Vec<Answer>, String> {
+    let mut raw = Vec::new();
+    let mut start: Option<(char, usize)> = None;
+    let mut escaped = false;
+
+    for (index, c) in body.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '=' | '~' => {
+                match start {
+                    Some((marker, from)) => raw.push((marker, &body[from..index])),
+                    None if !body[..index].trim().is_empty() => {
+                        return Err(format!("Unrecognised answer block: {{{}}}", body));
+                    }
+                    None => {}
+                }
+                start = Some((c, index + 1));
+            }
+            _ => {}
+        }
+    }
+    match start {
+        Some((marker, from)) => raw.push((marker, &body[from..])),
+        None => return Err(format!("Unrecognised answer block: {{{}}}", body)),
+    }
+
+    raw.into_iter()
+        .map(|(marker, text)| {
+            let text = text.trim();
+            let (weight, text) = match text.strip_prefix('%') {
+                Some(after) => {
+                    let end = after
+                        .find('%')
+                        .ok_or("Answer weight is missing its closing %")?;
+                    let weight: f64 = after[..end]
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid answer weight: %{}%", &after[..end]))?;
+                    (weight, &after[end + 1..])
+                }
+                None if marker == '=' => (100.0, text),
+                None => (0.0, text),
+            };
+            let (text, has_feedback) = match find_unescaped(text, "#") {
+                Some(index) => (&text[..index], true),
+                None => (text, false),
+            };
+            Ok(Answer {
+                marker,
+                weight,
+                text: text.trim().to_string(),
+                has_feedback,
+            })
+        })
+        .collect()
+}
+
+fn parse_choice(
+    answers: &[Answer],
+    subject_id: i64,
+    topic_id: i64,
+    question_text: String,
+) -> Result<CreateQuestionData, String> {
+    let correct = answers.iter().filter(|a| a.is_correct()).count();
+    if correct == 0 {
+        return Err("Multiple choice question has no correct answer".to_string());
+    }
+
+    // A single `=` answer is single choice; weighted answers allow several
+    let single = correct == 1 && answers.iter().all(|a| a.weight == 100.0 || a.weight == 0.0);
+    let question_type = if single {
+        "SINGLE_CHOICE"
+    } else {
+        "MULTIPLE_CHOICE"
+    };
+
+    let mut data = empty_question(subject_id, topic_id, question_type, question_text);
+    data.options = answers
+        .iter()
+        .enumerate()
+        .map(|(index, answer)| {
+            choice_option(&unescape(&answer.text), answer.is_correct(), index as i32)
+        })
+        .collect();
+    Ok(data)
+}
+
+/// `The capital of France is {=Paris ~Lyon ~Nice}.` becomes a dropdown blank
+fn parse_missing_word_choice(
+    answers: &[Answer],
+    subject_id: i64,
+    topic_id: i64,
+    question_text: String,
+) -> Result<CreateQuestionData, String> {
+    let correct: Vec<&Answer> = answers.iter().filter(|a| a.is_correct()).collect();
+    if correct.len() != 1 {
+        return Err("A missing-word choice needs exactly one correct answer".to_string());
+    }
+
+    let options: Vec<String> = answers.iter().map(|a| unescape(&a.text)).collect();
+    if options.iter().any(|o| o.contains(',')) {
+        return Err("Dropdown choices containing commas are not supported".to_string());
+    }
+
+    let mut data = empty_question(subject_id, topic_id, "FILL_BLANK", question_text);
+    data.blanks = vec![CreateQuestionBlank {
+        blank_index: 0,
+        correct_answer: unescape(&correct[0].text),
+        acceptable_answers: None,
+        is_numeric: false,
+        numeric_tolerance: None,
+        unit: None,
+        input_type: "DROPDOWN".to_string(),
+        dropdown_options: Some(options.join(", ")),
+    }];
+    Ok(data)
+}
+
+/// `{=Paris =paris}`: the first full-credit answer is the correct one, the rest are
+/// accepted alternatives
+fn parse_short_answer(
+    answers: &[Answer],
+    subject_id: i64,
+    topic_id: i64,
+    question_text: String,
+    notes: &mut Vec<String>,
+) -> Result<CreateQuestionData, String> {
+    let mut accepted = Vec::new();
+    for answer in answers {
+        let text = unescape(&answer.text);
+        if answer.weight < 100.0 {
+            notes.push(format!("Partial-credit answer \"{}\" was dropped", text));
+        } else if !accepted.is_empty() && text.contains(',') {
+            notes.push(format!(
+                "Alternative answer \"{}\" contains a comma and was dropped",
+                text
+            ));
+        } else {
+            accepted.push(text);
+        }
+    }
+    if accepted.is_empty() {
+        return Err("Short answer question has no full-credit answer".to_string());
+    }
+
+    let correct_answer = accepted.remove(0);
+    let mut data = empty_question(subject_id, topic_id, "FILL_BLANK", question_text);
+    data.blanks = vec![CreateQuestionBlank {
+        blank_index: 0,
+        correct_answer,
+        acceptable_answers: (!accepted.is_empty()).then(|| accepted.join(", ")),
+        is_numeric: false,
+        numeric_tolerance: None,
+        unit: None,
+        input_type: "INPUT".to_string(),
+        dropdown_options: None,
+    }];
+    Ok(data)
+}
+
+fn parse_matching(
+    answers: &[Answer],
+    subject_id: i64,
+    topic_id: i64,
+    question_text: String,
+    notes: &mut Vec<String>,
+) -> Result<CreateQuestionData, String> {
+    let mut pairs = Vec::new();
+    for answer in answers {
+        let arrow = find_unescaped(&answer.text, "->")
+            .ok_or_else(|| format!("Matching answer \"{}\" has no ->", unescape(&answer.text)))?;
+        let left_item = unescape(&answer.text[..arrow]).trim().to_string();
+        let right_item = unescape(&answer.text[arrow + 2..]).trim().to_string();
+        if left_item.is_empty() {
+            notes.push(format!("Extra distractor \"{}\" was dropped", right_item));
+            continue;
+        }
+        pairs.push(CreateMatchPair {
+            left_item,
+            right_item,
+            left_image_path: None,
+            right_image_path: None,
+        });
+    }
+    if pairs.is_empty() {
+        return Err("Matching question has no pairs".to_string());
+    }
+
+    let mut data = empty_question(subject_id, topic_id, "MATCHING", question_text);
+    data.match_pairs = Some(pairs);
+    Ok(data)
+}
+
+/// `{#3.14:0.01}`, `{#1..5}` or `{#=3:0.1 =%50%3:1}`. Only the full-credit answer is kept.
+fn parse_numeric(
+    body: &str,
+    missing_word: bool,
+    subject_id: i64,
+    topic_id: i64,
+    question_text: String,
+    notes: &mut Vec<String>,
+) -> Result<CreateQuestionData, String> {
+    let body = body.trim();
+    let answers = if body.starts_with('=') {
+        parse_answers(body)?
+    } else {
+        vec![Answer {
+            marker: '=',
+            weight: 100.0,
+            text: match find_unescaped(body, "#") {
+                Some(index) => body[..index].trim().to_string(),
+                None => body.to_string(),
+            },
+            has_feedback: find_unescaped(body, "#").is_some(),
+        }]
+    };
+    if answers.iter().any(|a| a.has_feedback) {
+        notes.push("Per-answer feedback was not imported".to_string());
+    }
+
+    let mut value = None;
+    for answer in &answers {
+        let parsed = parse_numeric_answer(&answer.text)
+            .ok_or_else(|| format!("Invalid numeric answer: {}", answer.text))?;
+        if answer.weight < 100.0 || value.is_some() {
+            notes.push(format!("Numeric answer {} was dropped", answer.text));
+        } else {
+            value = Some(parsed);
+        }
+    }
+    let (answer, tolerance) = value.ok_or("Numeric question has no full-credit answer")?;
+
+    if missing_word {
+        let mut data = empty_question(subject_id, topic_id, "FILL_BLANK", question_text);
+        data.blanks = vec![CreateQuestionBlank {
+            blank_index: 0,
+            correct_answer: format_number(answer),
+            acceptable_answers: None,
+            is_numeric: true,
+            numeric_tolerance: Some(tolerance),
+            unit: None,
+            input_type: "INPUT".to_string(),
+            dropdown_options: None,
+        }];
+        return Ok(data);
+    }
+
+    let mut data = empty_question(subject_id, topic_id, "NUMERIC_INPUT", question_text);
+    data.numeric_data = Some(CreateNumericData {
+        correct_answer: format_number(answer),
+        tolerance: format_number(tolerance),
+        unit: None,
+    });
+    Ok(data)
+}
+
+/// `value:tolerance`, `min..max` or a bare value, as (answer, tolerance)
+fn parse_numeric_answer(text: &str) -> Option<(f64, f64)> {
+    let text = text.trim();
+    if let Some((min, max)) = text.split_once("..") {
+        let min: f64 = min.trim().parse().ok()?;
+        let max: f64 = max.trim().parse().ok()?;
+        return Some(((min + max) / 2.0, (max - min).abs() / 2.0));
+    }
+    if let Some((value, tolerance)) = text.split_once(':') {
+        return Some((
+            value.trim().parse().ok()?,
+            tolerance.trim().parse::<f64>().ok()?.abs(),
+        ));
+    }
+    Some((text.parse().ok()?, 0.0))
+}
+
+fn choice_option(text: &str, is_correct: bool, display_order: i32) -> CreateQuestionOption {
+    CreateQuestionOption {
+        option_text: text.to_string(),
+        option_image_path: None,
+        is_correct,
+        display_order,
+    }
+}
+
+/// Write one question as a GIFT item. `Err` gives the reason it cannot be written;
+/// the notes list anything left out of a question that was written.
+pub fn write_question(question: &QuestionWithDetails) -> Result<(String, Vec<String>), String> {
+    let mut notes = Vec::new();
+    let q = &question.question;
+
+    if q.question_image_path.is_some()
+        || question
+            .options
+            .iter()
+            .any(|o| o.option_image_path.is_some())
+        || question
+            .matches
+            .iter()
+            .any(|m| m.left_image_path.is_some() || m.right_image_path.is_some())
+    {
+        notes.push("Images were not exported".to_string());
+    }
+
+    // `after` is `Some` when the block replaces a blank inside the text
+    let (before, answers, after) = match q.question_type.as_str() {
+        "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION" => {
+            (q.question_text.as_str(), write_choices(question)?, None)
+        }
+        "FILL_BLANK" | "FILL_BLANK_MULTIPLE" => {
+            if question.blanks.len() != 1 {
+                return Err("GIFT supports only one blank per question".to_string());
+            }
+            let answers = write_blank(&question.blanks[0], &mut notes)?;
+            match find_blank(&q.question_text) {
+                Some((index, marker)) => (
+                    &q.question_text[..index],
+                    answers,
+                    Some(&q.question_text[index + marker.len()..]),
+                ),
+                None => (q.question_text.as_str(), answers, None),
+            }
+        }
+        "NUMERIC_INPUT" | "CALCULATION" => {
+            let blank = question
+                .blanks
+                .first()
+                .ok_or("Numeric question has no answer")?;
+            // GIFT holds one numeric answer per question
+            for dropped in &question.blanks[1..] {
+                notes.push(format!(
+                    "Blank {} (answer {}) was dropped",
+                    dropped.blank_index + 1,
+                    dropped.correct_answer
+                ));
+            }
+            (
+                q.question_text.as_str(),
+                write_blank(blank, &mut notes)?,
+                None,
+            )
+        }
+        "MATCHING" => {
+            if question.matches.is_empty() {
+                return Err("Matching question has no pairs".to_string());
+            }
+            let pairs = question
+                .matches
+                .iter()
+                .map(|m| format!("={} -> {}", escape(&m.left_item), escape(&m.right_item)))
+                .collect::<Vec<_>>()
+                .join("\n\t");
+            (q.question_text.as_str(), pairs, None)
+        }
+        "ORDERING" => return Err("GIFT has no ordering questions".to_string()),
+        other => return Err(format!("Unknown question type: {}", other)),
+    };
+
+    let mut item = String::new();
+    if let Some(source) = q.source.as_deref().filter(|s| !s.trim().is_empty()) {
+        item.push_str(&format!("::{}::", escape(source)));
+    }
+    item.push_str(&escape(before));
+
+    let feedback = q
+        .explanation
+        .as_deref()
+        .filter(|e| !e.trim().is_empty())
+        .map(|e| format!("####{}", escape(e)));
+
+    match after {
+        Some(after) => {
+            item.push('{');
+            item.push_str(&answers);
+            if let Some(feedback) = feedback {
+                item.push(' ');
+                item.push_str(&feedback);
+            }
+            item.push('}');
+            item.push_str(&escape(after));
+        }
+        None => {
+            item.push_str(" {\n\t");
+            item.push_str(&answers);
+            if let Some(feedback) = feedback {
+                item.push_str("\n\t");
+                item.push_str(&feedback);
+            }
+            item.push_str("\n}");
+        }
+    }
+
+    Ok((item, notes))
+}
+
+fn write_choices(question: &QuestionWithDetails) -> Result<String, String> {
+    let options = &question.options;
+    let correct: Vec<_> = options.iter().filter(|o| o.is_correct).collect();
+    if correct.is_empty() {
+        return Err("Question has no correct option".to_string());
+    }
+
+    // Two options named True and False round-trip as a true/false item
+    if options.len() == 2 && correct.len() == 1 {
+        let names: Vec<String> = options
+            .iter()
+            .map(|o| o.option_text.trim().to_lowercase())
+            .collect();
+        if names.contains(&"true".to_string()) && names.contains(&"false".to_string()) {
+            let is_true = correct[0].option_text.trim().eq_ignore_ascii_case("true");
+            return Ok(if is_true { "TRUE" } else { "FALSE" }.to_string());
+        }
+    }
+
+    let multiple = question.question.question_type == "MULTIPLE_CHOICE";
+    let weight = format_number(100.0 / correct.len() as f64);
+    Ok(options
+        .iter()
+        .map(|o| match (o.is_correct, multiple) {
+            (true, false) => format!("={}", escape(&o.option_text)),
+            (true, true) => format!("~%{}%{}", weight, escape(&o.option_text)),
+            (false, _) => format!("~{}", escape(&o.option_text)),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\t"))
+}
+
+fn write_blank(blank: &QuestionBlank, notes: &mut Vec<String>) -> Result<String, String> {
+    if blank.is_numeric {
+        let answer: f64 = blank.correct_answer.trim().parse().map_err(|_| {
+            format!(
+                "Numeric answer \"{}\" is not a number",
+                blank.correct_answer
+            )
+        })?;
+        if blank.unit.as_deref().is_some_and(|u| !u.trim().is_empty()) {
+            notes.push("The unit was not exported".to_string());
+        }
+        let tolerance = blank.numeric_tolerance.unwrap_or(0.0);
+        return Ok(format!(
+            "#{}:{}",
+            format_number(answer),
+            format_number(tolerance)
+        ));
+    }
+
+    let split = |list: Option<&str>| -> Vec<String> {
+        list.unwrap_or("")
+            .split(',')
+            .map(|a| a.trim().to_string())
+            .filter(|a| !a.is_empty())
+            .collect()
+    };
+
+    if blank.input_type == "DROPDOWN" {
+        // Keep the dropdown order; the correct answer is marked where it appears
+        let correct = blank.correct_answer.trim();
+        let mut options = split(blank.dropdown_options.as_deref());
+        if !options.iter().any(|o| o == correct) {
+            options.insert(0, correct.to_string());
+        }
+        return Ok(options
+            .iter()
+            .map(|o| {
+                let marker = if o == correct { '=' } else { '~' };
+                format!("{}{}", marker, escape(o))
+            })
+            .collect::<Vec<_>>()
+            .join(" "));
+    }
+
+    let mut answers = vec![format!("={}", escape(&blank.correct_answer))];
+    answers.extend(
+        split(blank.acceptable_answers.as_deref())
+            .into_iter()
+            .map(|a| format!("={}", escape(&a))),
+    );
+    Ok(answers.join(" "))
+}
+
+/// Byte index of the first occurrence of `pattern` not preceded by a backslash
+fn find_unescaped(text: &str, pattern: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[index..].starts_with(pattern) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(next) => out.push(next),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Escape reserved characters, and the `->` that would turn an answer into a match
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            out.push_str("\\n");
+            continue;
+        }
+        if RESERVED.contains(&c) || (c == '-' && chars.peek() == Some(&'>')) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::fixtures::{dropdown_question, parsed_content, question, stored_content};
+
+    /// Write `question` and read it back, expecting exactly one question and no issues
+    fn round_trip(question: &QuestionWithDetails) -> CreateQuestionData {
+        let (text, notes) = write_question(question).unwrap();
+        assert!(notes.is_empty(), "{:?}", notes);
+
+        let (mut parsed, issues) = parse(&text, 1, 1);
+        assert!(issues.is_empty(), "{}\n{:?}", text, issues);
+        assert_eq!(parsed.len(), 1, "{}", text);
+        parsed.remove(0).data
+    }
+
+    #[test]
+    fn supported_types_round_trip() {
+        for question_type in [
+            "SINGLE_CHOICE",
+            "MULTIPLE_CHOICE",
+            "FILL_BLANK",
+            "NUMERIC_INPUT",
+            "MATCHING",
+        ] {
+            let original = question(question_type);
+            let parsed = round_trip(&original);
+            assert_eq!(
+                parsed_content(&parsed),
+                stored_content(&original),
+                "{}",
+                question_type
+            );
+        }
+
+        let original = dropdown_question();
+        assert_eq!(
+            parsed_content(&round_trip(&original)),
+            stored_content(&original)
+        );
+    }
+
+    #[test]
+    fn image_identification_comes_back_as_single_choice() {
+        let original = question("IMAGE_IDENTIFICATION");
+        let mut expected = stored_content(&original);
+        expected.question_type = "SINGLE_CHOICE".to_string();
+
+        assert_eq!(parsed_content(&round_trip(&original)), expected);
+    }
+
+    #[test]
+    fn true_false_options_round_trip_as_a_true_false_item() {
+        let mut original = question("SINGLE_CHOICE");
+        original.options.truncate(2);
+        original.options[0].option_text = "False".to_string();
+        original.options[1].option_text = "True".to_string();
+
+        let (text, _) = write_question(&original).unwrap();
+        assert!(text.contains("{\n\tFALSE"), "{}", text);
+
+        let parsed = round_trip(&original);
+        let options: Vec<_> = parsed
+            .options
+            .iter()
+            .map(|o| (o.option_text.as_str(), o.is_correct))
+            .collect();
+        assert_eq!(options, [("True", false), ("False", true)]);
+    }
+
+    #[test]
+    fn reserved_characters_are_escaped() {
+        let mut original = question("SINGLE_CHOICE");
+        original.question.question_text = "Is 1 = 1 {always}? ~maybe: #1 \\ yes".to_string();
+        original.options[1].option_text = "a -> b".to_string();
+
+        assert_eq!(
+            parsed_content(&round_trip(&original)),
+            stored_content(&original)
+        );
+    }
+
+    #[test]
+    fn calculation_keeps_only_its_first_blank() {
+        let original = question("CALCULATION");
+        let (text, notes) = write_question(&original).unwrap();
+        assert_eq!(notes, ["Blank 2 (answer 5) was dropped"]);
+
+        let (parsed, _) = parse(&text, 1, 1);
+        let numeric = parsed[0].data.numeric_data.as_ref().unwrap();
+        assert_eq!(
+            (numeric.correct_answer.as_str(), numeric.tolerance.as_str()),
+            ("12", "0")
+        );
+    }
+
+    #[test]
+    fn questions_gift_cannot_hold_are_rejected() {
+        let error = write_question(&question("FILL_BLANK_MULTIPLE")).unwrap_err();
+        assert_eq!(error, "GIFT supports only one blank per question");
+        assert!(write_question(&question("ORDERING")).is_err());
+    }
+
+    #[test]
+    fn dropdown_choices_with_commas_are_skipped() {
+        let text = "The answer is {=yes, really ~no}.\n\nPlain {=one ~two}";
+        let (parsed, issues) = parse(text, 1, 1);
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].line, 3);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 1);
+        assert!(issues[0].skipped);
+        assert_eq!(
+            issues[0].message,
+            "Dropdown choices containing commas are not supported"
+        );
+    }
+
+    #[test]
+    fn unsupported_items_are_reported_by_line() {
+        let text = "// comment\n$CATEGORY: $course$/Geo\n\nWrite an essay {}\n\n::t::Two {=a} blocks {=b}\n\nNo block";
+        let (parsed, issues) = parse(text, 1, 1);
+
+        assert!(parsed.is_empty());
+        let lines: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.line, issue.skipped))
+            .collect();
+        assert_eq!(lines, [(4, true), (6, true), (8, true)]);
+    }
+
+    #[test]
+    fn numeric_ranges_become_a_value_and_tolerance() {
+        let (parsed, issues) = parse("Pick a number {#1..5}", 1, 1);
+        assert!(issues.is_empty());
+        let numeric = parsed[0].data.numeric_data.as_ref().unwrap();
+        assert_eq!(
+            (numeric.correct_answer.as_str(), numeric.tolerance.as_str()),
+            ("3", "2")
+        );
+    }
+}
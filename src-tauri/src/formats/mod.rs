@@ -0,0 +1,475 @@
+use serde::Serialize;
+
+use crate::commands::questions::CreateQuestionData;
+
+// Each format turns file contents into `CreateQuestionData` and back. The commands
+// that read and write files and the database live in `commands::interchange`.
+pub mod anki;
+pub mod csv;
+pub mod gift;
+pub mod moodle_xml;
+pub mod qti;
+
+/// Markers the question editor accepts for a blank inside the question text
+const BLANK_MARKERS: &[&str] = &["___", "[blank]"];
+
+/// A question read from a file, with the line it started on
+#[derive(Debug)]
+pub struct ParsedQuestion {
+    pub line: usize,
+    pub data: CreateQuestionData,
+}
+
+/// Something in an imported file that could not be carried over as written
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportIssue {
+    pub line: usize,
+    pub message: String,
+    /// `true` when the whole item was left out, `false` when only part of it was lost
+    pub skipped: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub imported: usize,
+    pub question_ids: Vec<i64>,
+    pub issues: Vec<ImportIssue>,
+}
+
+/// A question that could not be written, or lost something on the way out
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportIssue {
+    pub question_id: i64,
+    pub message: String,
+    pub skipped: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub exported: usize,
+    pub issues: Vec<ExportIssue>,
+}
+
+/// A question with no options, blanks, order items or match pairs yet
+pub fn empty_question(
+    subject_id: i64,
+    topic_id: i64,
+    question_type: &str,
+    question_text: String,
+) -> CreateQuestionData {
+    CreateQuestionData {
+        subject_id,
+        topic_id,
+        question_type: question_type.to_string(),
+        question_text,
+        question_image_path: None,
+        explanation: None,
+        difficulty: "MEDIUM".to_string(),
+        points: 1,
+        source: None,
+        options: Vec::new(),
+        blanks: Vec::new(),
+        numeric_data: None,
+        order_items: None,
+        match_pairs: None,
+        tags: Vec::new(),
+    }
+}
+
+/// Position and marker of the first blank in `text`
+pub fn find_blank(text: &str) -> Option<(usize, &'static str)> {
+    BLANK_MARKERS
+        .iter()
+        .filter_map(|marker| text.find(marker).map(|index| (index, *marker)))
+        .min()
+}
+
+/// Format a number without a trailing `.0` or float noise
+pub fn format_number(value: f64) -> String {
+    let text = format!("{:.6}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// The non-empty items of a comma-separated list such as `acceptable_answers`
+pub fn split_list(list: Option<&str>) -> impl Iterator<Item = &str> {
+    list.unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// A title for formats that require one: the start of the question text
+pub fn default_name(text: &str) -> String {
+    let first_line = text.lines().next().unwrap_or("").trim();
+    if first_line.chars().count() <= 60 {
+        first_line.to_string()
+    } else {
+        first_line.chars().take(60).collect::<String>() + "..."
+    }
+}
+
+/// Escape text for use in XML element content or attribute values
+pub fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Turn plain question text into HTML, keeping line breaks
+pub fn text_to_html(text: &str) -> String {
+    escape_xml(text).replace('\n', "<br>")
+}
+
+/// Reduce an HTML fragment to the plain text the question editor stores.
+/// Block elements and `<br>` become line breaks; every other tag is dropped.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            text.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        if matches!(
+            tag.as_str(),
+            "br" | "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3" | "h4"
+        ) {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = decode_entities(&text);
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+
+    // Collapse the blank lines left behind by nested block elements
+    let mut out = String::new();
+    for line in lines {
+        if line.is_empty() && (out.is_empty() || out.ends_with("\n\n")) {
+            continue;
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let decoded = after.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &after[..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => match entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                    None => entity
+                        .strip_prefix('#')
+                        .and_then(|dec| dec.parse().ok())
+                        .and_then(char::from_u32),
+                },
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// Questions of every type for the round-trip tests of each format, and a common shape
+/// to compare what was written with what was read back
+#[cfg(test)]
+pub mod fixtures {
+    use serde_json::{json, Value};
+
+    use crate::commands::questions::{CreateQuestionData, QuestionWithDetails};
+
+    /// What a question says, without ids, ordering columns or timestamps
+    #[derive(Debug, PartialEq)]
+    pub struct Content {
+        pub question_type: String,
+        pub question_text: String,
+        pub explanation: Option<String>,
+        /// (text, is correct)
+        pub options: Vec<(String, bool)>,
+        pub blanks: Vec<BlankContent>,
+        /// Item texts in their correct order
+        pub order_items: Vec<String>,
+        /// (left, right)
+        pub matches: Vec<(String, String)>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct BlankContent {
+        pub correct_answer: String,
+        pub acceptable_answers: Option<String>,
+        pub is_numeric: bool,
+        pub numeric_tolerance: Option<f64>,
+        pub unit: Option<String>,
+        pub input_type: String,
+        pub dropdown_options: Option<String>,
+    }
+
+    /// A stored question of `question_type`, as `fetch_question_with_details` returns it
+    pub fn question(question_type: &str) -> QuestionWithDetails {
+        let (text, details) = match question_type {
+            "SINGLE_CHOICE" => (
+                "What is the capital of France?",
+                json!({ "options": options(&[("Paris", true), ("Lyon", false), ("Nice", false)]) }),
+            ),
+            "MULTIPLE_CHOICE" => (
+                "Which of these numbers are prime?",
+                json!({ "options": options(&[("2", true), ("3", true), ("4", false)]) }),
+            ),
+            "IMAGE_IDENTIFICATION" => (
+                "Which organelle is shown?",
+                json!({ "options": options(&[("Mitochondrion", true), ("Ribosome", false)]) }),
+            ),
+            "FILL_BLANK" => (
+                "The chemical symbol for gold is ___.",
+                json!({ "blanks": [blank(0, "Au", json!({ "acceptableAnswers": "au" }))] }),
+            ),
+            "FILL_BLANK_MULTIPLE" => (
+                "___ and ___ are noble gases.",
+                json!({ "blanks": [
+                    blank(0, "Helium", json!({ "acceptableAnswers": "He" })),
+                    blank(1, "Neon", json!({})),
+                ] }),
+            ),
+            "NUMERIC_INPUT" => (
+                "How fast does an object fall after one second, in m/s?",
+                json!({ "blanks": [
+                    blank(0, "9.81", json!({ "isNumeric": true, "numericTolerance": 0.05 })),
+                ] }),
+            ),
+            "CALCULATION" => (
+                "A rectangle is 3 by 4. Its area is ___ and its diagonal ___.",
+                json!({ "blanks": [
+                    blank(0, "12", json!({ "isNumeric": true, "numericTolerance": 0.0 })),
+                    blank(1, "5", json!({ "isNumeric": true, "numericTolerance": 0.1 })),
+                ] }),
+            ),
+            "ORDERING" => (
+                "Order the planets by distance from the Sun.",
+                json!({ "orderItems": order_items(&["Mercury", "Venus", "Earth"]) }),
+            ),
+            "MATCHING" => (
+                "Match each country to its capital.",
+                json!({ "matches": matches(&[("France", "Paris"), ("Japan", "Tokyo"), ("Kenya", "Nairobi")]) }),
+            ),
+            other => panic!("no fixture for {}", other),
+        };
+
+        let mut value = json!({
+            "id": 1, "subjectId": 1, "topicId": 1, "questionType": question_type,
+            "questionText": text, "explanation": "Worked answer.", "difficulty": "MEDIUM",
+            "points": 1, "createdAt": "", "updatedAt": "",
+            "options": [], "blanks": [], "orderItems": [], "matches": [],
+        });
+        for (key, rows) in details.as_object().unwrap() {
+            value[key] = rows.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// A fill-in-the-blank question answered from a dropdown
+    pub fn dropdown_question() -> QuestionWithDetails {
+        let mut question = question("FILL_BLANK");
+        question.question.question_text = "The largest planet is ___.".to_string();
+        question.blanks[0].correct_answer = "Jupiter".to_string();
+        question.blanks[0].acceptable_answers = None;
+        question.blanks[0].input_type = "DROPDOWN".to_string();
+        question.blanks[0].dropdown_options = Some("Mars, Jupiter, Venus".to_string());
+        question
+    }
+
+    fn options(options: &[(&str, bool)]) -> Value {
+        options
+            .iter()
+            .enumerate()
+            .map(|(index, (text, is_correct))| {
+                json!({
+                    "id": index + 1, "questionId": 1, "optionText": text,
+                    "isCorrect": is_correct, "displayOrder": index,
+                })
+            })
+            .collect()
+    }
+
+    fn order_items(items: &[&str]) -> Value {
+        items
+            .iter()
+            .enumerate()
+            .map(|(index, text)| json!({
+                "id": index + 1, "questionId": 1, "itemText": text, "correctPosition": index + 1,
+            }))
+            .collect()
+    }
+
+    fn matches(pairs: &[(&str, &str)]) -> Value {
+        pairs
+            .iter()
+            .enumerate()
+            .map(|(index, (left, right))| {
+                json!({
+                    "id": index + 1, "questionId": 1, "leftItem": left, "rightItem": right,
+                    "displayOrder": index,
+                })
+            })
+            .collect()
+    }
+
+    fn blank(index: i32, answer: &str, extra: Value) -> Value {
+        let mut value = json!({
+            "id": index + 1, "questionId": 1, "blankIndex": index, "correctAnswer": answer,
+            "isNumeric": false, "inputType": "INPUT",
+        });
+        for (key, field) in extra.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        value
+    }
+
+    pub fn stored_content(question: &QuestionWithDetails) -> Content {
+        let mut order_items: Vec<_> = question.order_items.iter().collect();
+        order_items.sort_by_key(|item| item.correct_position);
+
+        Content {
+            question_type: question.question.question_type.clone(),
+            question_text: question.question.question_text.clone(),
+            explanation: question.question.explanation.clone(),
+            options: question
+                .options
+                .iter()
+                .map(|option| (option.option_text.clone(), option.is_correct))
+                .collect(),
+            blanks: question
+                .blanks
+                .iter()
+                .map(|blank| BlankContent {
+                    correct_answer: blank.correct_answer.clone(),
+                    acceptable_answers: blank.acceptable_answers.clone(),
+                    is_numeric: blank.is_numeric,
+                    numeric_tolerance: blank.numeric_tolerance,
+                    unit: blank.unit.clone(),
+                    input_type: blank.input_type.clone(),
+                    dropdown_options: blank.dropdown_options.clone(),
+                })
+                .collect(),
+            order_items: order_items
+                .iter()
+                .map(|item| item.item_text.clone())
+                .collect(),
+            matches: question
+                .matches
+                .iter()
+                .map(|pair| (pair.left_item.clone(), pair.right_item.clone()))
+                .collect(),
+        }
+    }
+
+    /// The content of a parsed question as it would be stored: a numeric answer
+    /// becomes the question's only blank, as `insert_question` stores it
+    pub fn parsed_content(data: &CreateQuestionData) -> Content {
+        let mut order_items: Vec<_> = data.order_items.iter().flatten().collect();
+        order_items.sort_by_key(|item| item.correct_position);
+
+        let mut blanks: Vec<BlankContent> = data
+            .blanks
+            .iter()
+            .map(|blank| BlankContent {
+                correct_answer: blank.correct_answer.clone(),
+                acceptable_answers: blank.acceptable_answers.clone(),
+                is_numeric: blank.is_numeric,
+                numeric_tolerance: blank.numeric_tolerance,
+                unit: blank.unit.clone(),
+                input_type: blank.input_type.clone(),
+                dropdown_options: blank.dropdown_options.clone(),
+            })
+            .collect();
+        if let Some(numeric) = &data.numeric_data {
+            blanks.push(BlankContent {
+                correct_answer: numeric.correct_answer.clone(),
+                acceptable_answers: None,
+                is_numeric: true,
+                numeric_tolerance: numeric.tolerance.parse().ok(),
+                unit: numeric.unit.clone(),
+                input_type: "INPUT".to_string(),
+                dropdown_options: None,
+            });
+        }
+
+        Content {
+            question_type: data.question_type.clone(),
+            question_text: data.question_text.clone(),
+            explanation: data.explanation.clone(),
+            options: data
+                .options
+                .iter()
+                .map(|option| (option.option_text.clone(), option.is_correct))
+                .collect(),
+            blanks,
+            order_items: order_items.iter().map(|item| item.text.clone()).collect(),
+            matches: data
+                .match_pairs
+                .iter()
+                .flatten()
+                .map(|pair| (pair.left_item.clone(), pair.right_item.clone()))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,1155 @@
+use base64::Engine;
+use roxmltree::{Document, Node};
+
+use crate::commands::questions::{
+    CreateMatchPair, CreateNumericData, CreateQuestionBlank, CreateQuestionData,
+    CreateQuestionOption, QuestionWithDetails,
+};
+use crate::error::{AppError, AppResult};
+use crate::formats::{
+    default_name, empty_question, escape_xml, find_blank, format_number, html_to_text, split_list,
+    text_to_html, ExportIssue, ExportReport, ImportIssue, ParsedQuestion,
+};
+
+/// Prefix Moodle gives `src` attributes that point at a file embedded in the question
+const PLUGINFILE: &str = "@@PLUGINFILE@@/";
+
+/// Moodle numbers gap groups 1 to 20
+const MAX_GAP_GROUPS: usize = 20;
+
+/// A question read from the file and the category it was filed under
+pub struct CategorizedQuestion {
+    /// Category path below Moodle's `$course$/top`, e.g. `["Physics", "Mechanics"]`.
+    /// Empty when the question came before any category.
+    pub category: Vec<String>,
+    /// `subject_id` and `topic_id` are 0 until the caller resolves the category
+    pub question: ParsedQuestion,
+}
+
+/// A base64 `<file>` element
+struct EmbeddedFile {
+    name: String,
+    data: Vec<u8>,
+}
+
+/// Where an embedded file ends up on the imported question
+enum ImageSlot {
+    Question,
+    Option(usize),
+    MatchLeft(usize),
+}
+
+/// Collects what a question loses and the files it carries while it is read
+#[derive(Default)]
+struct ItemContext {
+    notes: Vec<String>,
+    images: Vec<(ImageSlot, EmbeddedFile)>,
+}
+
+impl ItemContext {
+    /// Keep the first file for `slot`; the editor has room for one image per field
+    fn attach(&mut self, slot: ImageSlot, files: Vec<EmbeddedFile>) {
+        let mut files = files.into_iter();
+        if let Some(file) = files.next() {
+            self.images.push((slot, file));
+        }
+        let extra = files.count();
+        if extra > 0 {
+            self.notes
+                .push(format!("{} extra embedded file(s) were dropped", extra));
+        }
+    }
+}
+
+/// Read a Moodle XML quiz file.
+///
+/// Embedded files of the questions that are kept are handed to `store_image`, which
+/// returns the path to record for them. Questions that cannot be represented are
+/// reported with the line their `<question>` element starts on.
+pub fn parse(
+    xml: &str,
+    store_image: &mut dyn FnMut(&str, &[u8]) -> AppResult<String>,
+) -> AppResult<(Vec<CategorizedQuestion>, Vec<ImportIssue>)> {
+    let doc = Document::parse(xml)
+        .map_err(|e| AppError::validation(format!("Not a valid XML file: {}", e), &["path"]))?;
+    let quiz = doc.root_element();
+    if !quiz.has_tag_name("quiz") {
+        return Err(AppError::validation(
+            "Not a Moodle XML file: the root element must be <quiz>",
+            &["path"],
+        ));
+    }
+
+    let mut questions = Vec::new();
+    let mut issues = Vec::new();
+    let mut category = Vec::new();
+
+    for node in quiz.children().filter(|n| n.has_tag_name("question")) {
+        let line = doc.text_pos_at(node.range().start).row as usize;
+        let question_type = node.attribute("type").unwrap_or("");
+
+        if question_type == "category" {
+            category = child_text(node, "category")
+                .map(|path| parse_category(&path))
+                .unwrap_or_default();
+            continue;
+        }
+
+        let mut context = ItemContext::default();
+        match read_question(node, question_type, &mut context) {
+            Ok(mut data) => {
+                for (slot, file) in context.images {
+                    let path = Some(store_image(&file.name, &file.data)?);
+                    match slot {
+                        ImageSlot::Question => data.question_image_path = path,
+                        ImageSlot::Option(index) => data.options[index].option_image_path = path,
+                        ImageSlot::MatchLeft(index) => {
+                            if let Some(pair) =
+                                data.match_pairs.as_mut().and_then(|p| p.get_mut(index))
+                            {
+                                pair.left_image_path = path;
+                            }
+                        }
+                    }
+                }
+                issues.extend(context.notes.into_iter().map(|message| ImportIssue {
+                    line,
+                    message,
+                    skipped: false,
+                }));
+                questions.push(CategorizedQuestion {
+                    category: category.clone(),
+                    question: ParsedQuestion { line, data },
+                });
+            }
+            Err(message) => issues.push(ImportIssue {
+                line,
+                message,
+                skipped: true,
+            }),
+        }
+    }
+
+    Ok((questions, issues))
+}
+
+/// `$course$/top/Physics/Mechanics` becomes `["Physics", "Mechanics"]`; `//` is a literal `/`
+fn parse_category(path: &str) -> Vec<String> {
+    let mut segments: Vec<String> = path
+        .replace("//", "\u{0}")
+        .split('/')
+        .map(|segment| segment.replace('\u{0}', "/").trim().to_string())
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    if segments
+        .first()
+        .is_some_and(|s| s.starts_with('$') && s.ends_with('$'))
+    {
+        segments.remove(0);
+    }
+    if segments.first().is_some_and(|s| s == "top") {
+        segments.remove(0);
+    }
+    segments
+}
+
+fn read_question(
+    node: Node,
+    question_type: &str,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let (question_text, files) = match child(node, "questiontext") {
+        Some(text) => rich_text(text, "html")?,
+        None => (String::new(), Vec::new()),
+    };
+    context.attach(ImageSlot::Question, files);
+
+    let mut data = match question_type {
+        "multichoice" => read_multichoice(node, question_text, context)?,
+        "truefalse" => read_true_false(node, question_text)?,
+        "shortanswer" => read_short_answer(node, question_text, context)?,
+        "numerical" => read_numerical(node, question_text, context)?,
+        "matching" => read_matching(node, question_text, context)?,
+        "ddwtos" => read_gaps(node, question_text, "dragbox")?,
+        "gapselect" => read_gaps(node, question_text, "selectoption")?,
+        other => {
+            return Err(format!(
+                "Moodle question type \"{}\" is not supported",
+                other
+            ))
+        }
+    };
+
+    if data.question_text.trim().is_empty() {
+        return Err("Question has no text".to_string());
+    }
+
+    // Moodle names every question, often with the start of its text; keep real titles
+    data.source = child_text(node, "name")
+        .map(|name| name.trim().to_string())
+        .filter(|name| {
+            !name.is_empty() && !data.question_text.starts_with(name.trim_end_matches("..."))
+        });
+    data.explanation = match child(node, "generalfeedback") {
+        Some(feedback) => Some(rich_text(feedback, "html")?.0).filter(|f| !f.is_empty()),
+        None => None,
+    };
+    if let Some(grade) = child_text(node, "defaultgrade").and_then(|g| g.trim().parse::<f64>().ok())
+    {
+        data.points = grade.round().max(0.0) as i32;
+    }
+
+    Ok(data)
+}
+
+fn read_multichoice(
+    node: Node,
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let answers = read_answers(node)?;
+    let correct = answers.iter().filter(|a| a.fraction > 0.0).count();
+    if correct == 0 {
+        return Err("Multiple choice question has no correct answer".to_string());
+    }
+
+    let single = child_text(node, "single").is_none_or(|s| matches!(s.trim(), "true" | "1"));
+    let question_type = if single && correct == 1 {
+        "SINGLE_CHOICE"
+    } else {
+        "MULTIPLE_CHOICE"
+    };
+
+    let mut data = empty_question(0, 0, question_type, question_text);
+    for (index, answer) in answers.into_iter().enumerate() {
+        context.attach(ImageSlot::Option(index), answer.files);
+        data.options.push(CreateQuestionOption {
+            option_text: answer.text,
+            option_image_path: None,
+            is_correct: answer.fraction > 0.0,
+            display_order: index as i32,
+        });
+    }
+    Ok(data)
+}
+
+fn read_true_false(node: Node, question_text: String) -> Result<CreateQuestionData, String> {
+    let answers = read_answers(node)?;
+    let is_true = answers
+        .iter()
+        .find(|a| a.fraction > 0.0)
+        .map(|a| a.text.trim().eq_ignore_ascii_case("true"))
+        .ok_or("True/false question has no correct answer")?;
+
+    let mut data = empty_question(0, 0, "SINGLE_CHOICE", question_text);
+    data.options = vec![
+        choice_option("True", is_true, 0),
+        choice_option("False", !is_true, 1),
+    ];
+    Ok(data)
+}
+
+fn read_short_answer(
+    node: Node,
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let mut accepted = Vec::new();
+    for answer in read_answers(node)? {
+        if answer.fraction < 100.0 {
+            context.notes.push(format!(
+                "Partial-credit answer \"{}\" was dropped",
+                answer.text
+            ));
+        } else if !accepted.is_empty() && answer.text.contains(',') {
+            context.notes.push(format!(
+                "Alternative answer \"{}\" contains a comma and was dropped",
+                answer.text
+            ));
+        } else {
+            accepted.push(answer.text);
+        }
+    }
+    if accepted.is_empty() {
+        return Err("Short answer question has no full-credit answer".to_string());
+    }
+
+    let correct_answer = accepted.remove(0);
+    let mut data = empty_question(0, 0, "FILL_BLANK", question_text);
+    data.blanks = vec![input_blank(0, correct_answer, accepted)];
+    Ok(data)
+}
+
+fn read_numerical(
+    node: Node,
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let mut value = None;
+    for answer in node.children().filter(|n| n.has_tag_name("answer")) {
+        let fraction = fraction(answer);
+        let text = answer_text(answer);
+        let tolerance = child_text(answer, "tolerance")
+            .and_then(|t| t.trim().parse::<f64>().ok())
+            .unwrap_or(0.0)
+            .abs();
+
+        if fraction < 100.0 || value.is_some() {
+            context
+                .notes
+                .push(format!("Numeric answer {} was dropped", text));
+            continue;
+        }
+        let number: f64 = text
+            .trim()
+            .parse()
+            .map_err(|_| format!("Numeric answer \"{}\" is not a number", text))?;
+        value = Some((number, tolerance));
+    }
+    let (answer, tolerance) = value.ok_or("Numeric question has no full-credit answer")?;
+
+    // Only the base unit (multiplier 1) fits the editor's single unit field
+    let unit = child(node, "units").and_then(|units| {
+        units
+            .children()
+            .filter(|n| n.has_tag_name("unit"))
+            .find(|unit| {
+                child_text(*unit, "multiplier")
+                    .and_then(|m| m.trim().parse::<f64>().ok())
+                    .is_none_or(|m| m == 1.0)
+            })
+            .and_then(|unit| child_text(unit, "unit_name"))
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+    });
+
+    let mut data = empty_question(0, 0, "NUMERIC_INPUT", question_text);
+    data.numeric_data = Some(CreateNumericData {
+        correct_answer: format_number(answer),
+        tolerance: format_number(tolerance),
+        unit,
+    });
+    Ok(data)
+}
+
+fn read_matching(
+    node: Node,
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let mut pairs = Vec::new();
+    for sub in node.children().filter(|n| n.has_tag_name("subquestion")) {
+        let (left_item, files) = rich_text(sub, "html")?;
+        let right_item = child(sub, "answer").map(answer_text).unwrap_or_default();
+        if left_item.is_empty() && files.is_empty() {
+            context
+                .notes
+                .push(format!("Extra distractor \"{}\" was dropped", right_item));
+            continue;
+        }
+        context.attach(ImageSlot::MatchLeft(pairs.len()), files);
+        pairs.push(CreateMatchPair {
+            left_item,
+            right_item,
+            left_image_path: None,
+            right_image_path: None,
+        });
+    }
+    if pairs.is_empty() {
+        return Err("Matching question has no pairs".to_string());
+    }
+
+    let mut data = empty_question(0, 0, "MATCHING", question_text);
+    data.match_pairs = Some(pairs);
+    Ok(data)
+}
+
+/// ddwtos and gapselect: `[[n]]` in the text is a gap filled by choice `n`; the other
+/// choices in that choice's group become the dropdown
+fn read_gaps(
+    node: Node,
+    question_text: String,
+    choice_tag: &str,
+) -> Result<CreateQuestionData, String> {
+    let choices: Vec<(String, String)> = node
+        .children()
+        .filter(|n| n.has_tag_name(choice_tag))
+        .map(|choice| {
+            (
+                child_text(choice, "text")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+                child_text(choice, "group")
+                    .unwrap_or_else(|| "1".to_string())
+                    .trim()
+                    .to_string(),
+            )
+        })
+        .collect();
+
+    let mut text = String::new();
+    let mut blanks = Vec::new();
+    let mut rest = question_text.as_str();
+    while let Some(start) = rest.find("[[") {
+        let Some(end) = rest[start..].find("]]") else {
+            break;
+        };
+        let Ok(number) = rest[start + 2..start + end].trim().parse::<usize>() else {
+            text.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        };
+        let (correct, group) = number
+            .checked_sub(1)
+            .and_then(|index| choices.get(index))
+            .ok_or_else(|| format!("Gap [[{}]] has no matching choice", number))?;
+
+        let options: Vec<&str> = choices
+            .iter()
+            .filter(|(_, g)| g == group)
+            .map(|(choice, _)| choice.as_str())
+            .collect();
+        if options.iter().any(|o| o.contains(',')) {
+            return Err("Dropdown choices containing commas are not supported".to_string());
+        }
+
+        blanks.push(CreateQuestionBlank {
+            blank_index: blanks.len() as i32,
+            correct_answer: correct.clone(),
+            acceptable_answers: None,
+            is_numeric: false,
+            numeric_tolerance: None,
+            unit: None,
+            input_type: "DROPDOWN".to_string(),
+            dropdown_options: Some(options.join(", ")),
+        });
+        text.push_str(&rest[..start]);
+        text.push_str("___");
+        rest = &rest[start + end + 2..];
+    }
+    text.push_str(rest);
+
+    if blanks.is_empty() {
+        return Err("Question text has no [[n]] gaps".to_string());
+    }
+
+    let mut data = empty_question(0, 0, "FILL_BLANK", text);
+    data.blanks = blanks;
+    Ok(data)
+}
+
+struct Answer {
+    fraction: f64,
+    text: String,
+    files: Vec<EmbeddedFile>,
+}
+
+fn read_answers(node: Node) -> Result<Vec<Answer>, String> {
+    node.children()
+        .filter(|n| n.has_tag_name("answer"))
+        .map(|answer| {
+            let (text, files) = rich_text(answer, "plain_text")?;
+            Ok(Answer {
+                fraction: fraction(answer),
+                text,
+                files,
+            })
+        })
+        .collect()
+}
+
+fn fraction(answer: Node) -> f64 {
+    answer
+        .attribute("fraction")
+        .and_then(|f| f.trim().parse().ok())
+        .unwrap_or(0.0)
+}
+
+/// Plain text of an `<answer>` that Moodle stores without formatting
+fn answer_text(answer: Node) -> String {
+    child_text(answer, "text")
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Text and embedded files of an element such as `<questiontext format="html">`
+fn rich_text(node: Node, default_format: &str) -> Result<(String, Vec<EmbeddedFile>), String> {
+    let raw = child_text(node, "text").unwrap_or_default();
+    let text = match node.attribute("format").unwrap_or(default_format) {
+        "html" => html_to_text(&raw),
+        _ => raw.trim().to_string(),
+    };
+
+    let files = node
+        .children()
+        .filter(|n| n.has_tag_name("file"))
+        .map(|file| {
+            let name = file.attribute("name").unwrap_or("image.png").to_string();
+            let encoded: String = file
+                .text()
+                .unwrap_or("")
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| format!("Embedded file {} is not valid base64: {}", name, e))?;
+            Ok(EmbeddedFile { name, data })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok((text, files))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+/// All text inside `<name><text>...</text></name>`-style children, or the child itself
+fn child_text(node: Node, name: &str) -> Option<String> {
+    let element = child(node, name)?;
+    let element = child(element, "text").unwrap_or(element);
+    Some(
+        element
+            .descendants()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect(),
+    )
+}
+
+fn choice_option(text: &str, is_correct: bool, display_order: i32) -> CreateQuestionOption {
+    CreateQuestionOption {
+        option_text: text.to_string(),
+        option_image_path: None,
+        is_correct,
+        display_order,
+    }
+}
+
+fn input_blank(
+    blank_index: i32,
+    correct_answer: String,
+    alternatives: Vec<String>,
+) -> CreateQuestionBlank {
+    CreateQuestionBlank {
+        blank_index,
+        correct_answer,
+        acceptable_answers: (!alternatives.is_empty()).then(|| alternatives.join(", ")),
+        is_numeric: false,
+        numeric_tolerance: None,
+        unit: None,
+        input_type: "INPUT".to_string(),
+        dropdown_options: None,
+    }
+}
+
+/// Questions of one topic, written under the category `path`
+pub struct ExportCategory {
+    pub path: Vec<String>,
+    pub questions: Vec<QuestionWithDetails>,
+}
+
+/// Write a Moodle XML quiz file. `load_image` reads an image by its stored path so it
+/// can be embedded; images that cannot be read are reported and left out.
+pub fn write_quiz(
+    categories: &[ExportCategory],
+    load_image: &mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+) -> (String, ExportReport) {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<quiz>\n");
+    let mut report = ExportReport {
+        exported: 0,
+        issues: Vec::new(),
+    };
+
+    for category in categories {
+        let path: Vec<String> = category.path.iter().map(|s| s.replace('/', "//")).collect();
+        xml.push_str(&format!(
+            "  <question type=\"category\">\n    <category>\n      <text>{}</text>\n    </category>\n  </question>\n",
+            escape_xml(&format!("$course$/top/{}", path.join("/")))
+        ));
+
+        for question in &category.questions {
+            let question_id = question.question.id;
+            let mut writer = QuestionWriter {
+                xml: String::new(),
+                notes: Vec::new(),
+                load_image: &mut *load_image,
+            };
+            match writer.write(question) {
+                Ok(()) => {
+                    xml.push_str(&writer.xml);
+                    report.exported += 1;
+                    report
+                        .issues
+                        .extend(writer.notes.into_iter().map(|message| ExportIssue {
+                            question_id,
+                            message,
+                            skipped: false,
+                        }));
+                }
+                Err(message) => report.issues.push(ExportIssue {
+                    question_id,
+                    message,
+                    skipped: true,
+                }),
+            }
+        }
+    }
+
+    xml.push_str("</quiz>\n");
+    (xml, report)
+}
+
+struct QuestionWriter<'a> {
+    xml: String,
+    notes: Vec<String>,
+    load_image: &'a mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+}
+
+impl QuestionWriter<'_> {
+    fn write(&mut self, question: &QuestionWithDetails) -> Result<(), String> {
+        let q = &question.question;
+        let mut question_text = q.question_text.clone();
+
+        let question_type = match q.question_type.as_str() {
+            "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION"
+                if is_true_false(question) =>
+            {
+                "truefalse"
+            }
+            "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION" => "multichoice",
+            "FILL_BLANK" | "FILL_BLANK_MULTIPLE" => {
+                let dropdowns = question
+                    .blanks
+                    .iter()
+                    .filter(|b| b.input_type == "DROPDOWN")
+                    .count();
+                if question.blanks.is_empty() {
+                    return Err("Question has no blanks".to_string());
+                } else if dropdowns == question.blanks.len() {
+                    if question.blanks.len() > MAX_GAP_GROUPS {
+                        return Err(format!(
+                            "Moodle allows at most {} dropdown groups",
+                            MAX_GAP_GROUPS
+                        ));
+                    }
+                    question_text = gap_text(&q.question_text, &gap_choice_numbers(question));
+                    "gapselect"
+                } else if question.blanks.len() == 1 && question.blanks[0].is_numeric {
+                    "numerical"
+                } else if question.blanks.len() == 1 && dropdowns == 0 {
+                    "shortanswer"
+                } else {
+                    return Err(
+                        "Several typed-in blanks need Moodle's Cloze type, which is not supported"
+                            .to_string(),
+                    );
+                }
+            }
+            "NUMERIC_INPUT" | "CALCULATION" => "numerical",
+            "MATCHING" => "matching",
+            "ORDERING" => return Err("Moodle has no built-in ordering question type".to_string()),
+            other => return Err(format!("Unknown question type: {}", other)),
+        };
+
+        let name = q
+            .source
+            .clone()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| default_name(&q.question_text));
+
+        let mut body = String::new();
+        body.push_str(&format!(
+            "    <name>\n      <text>{}</text>\n    </name>\n",
+            escape_xml(&name)
+        ));
+        body.push_str(&self.rich_text(
+            "questiontext",
+            "",
+            &question_text,
+            q.question_image_path.as_deref(),
+            "",
+        ));
+        body.push_str(&format!(
+            "    <generalfeedback format=\"html\">\n      <text>{}</text>\n    </generalfeedback>\n",
+            escape_xml(&text_to_html(q.explanation.as_deref().unwrap_or("")))
+        ));
+        body.push_str(&format!("    <defaultgrade>{}</defaultgrade>\n", q.points));
+        body.push_str("    <penalty>0.3333333</penalty>\n    <hidden>0</hidden>\n");
+
+        match question_type {
+            "truefalse" => {
+                let is_true = question
+                    .options
+                    .iter()
+                    .any(|o| o.is_correct && o.option_text.trim().eq_ignore_ascii_case("true"));
+                for (text, correct) in [("true", is_true), ("false", !is_true)] {
+                    body.push_str(&format!(
+                        "    <answer fraction=\"{}\" format=\"moodle_auto_format\">\n      <text>{}</text>\n    </answer>\n",
+                        if correct { 100 } else { 0 },
+                        text
+                    ));
+                }
+            }
+            "multichoice" => self.write_multichoice(question, &mut body)?,
+            "shortanswer" => {
+                let blank = &question.blanks[0];
+                body.push_str("    <usecase>0</usecase>\n");
+                for answer in std::iter::once(blank.correct_answer.as_str())
+                    .chain(split_list(blank.acceptable_answers.as_deref()))
+                {
+                    body.push_str(&plain_answer(100.0, answer, ""));
+                }
+            }
+            "numerical" => {
+                let blank = question
+                    .blanks
+                    .first()
+                    .ok_or("Numeric question has no answer")?;
+                // Moodle's numerical type holds one answer per question
+                for dropped in &question.blanks[1..] {
+                    self.notes.push(format!(
+                        "Blank {} (answer {}) was dropped",
+                        dropped.blank_index + 1,
+                        dropped.correct_answer
+                    ));
+                }
+                let answer: f64 = blank.correct_answer.trim().parse().map_err(|_| {
+                    format!(
+                        "Numeric answer \"{}\" is not a number",
+                        blank.correct_answer
+                    )
+                })?;
+                body.push_str(&plain_answer(
+                    100.0,
+                    &format_number(answer),
+                    &format!(
+                        "      <tolerance>{}</tolerance>\n",
+                        format_number(blank.numeric_tolerance.unwrap_or(0.0))
+                    ),
+                ));
+                if let Some(unit) = blank.unit.as_deref().filter(|u| !u.trim().is_empty()) {
+                    body.push_str(&format!(
+                        "    <units>\n      <unit>\n        <multiplier>1</multiplier>\n        <unit_name>{}</unit_name>\n      </unit>\n    </units>\n",
+                        escape_xml(unit.trim())
+                    ));
+                    body.push_str("    <unitgradingtype>0</unitgradingtype>\n    <unitpenalty>0.1</unitpenalty>\n    <showunits>3</showunits>\n    <unitsleft>0</unitsleft>\n");
+                }
+            }
+            "matching" => {
+                if question.matches.is_empty() {
+                    return Err("Matching question has no pairs".to_string());
+                }
+                body.push_str("    <shuffleanswers>true</shuffleanswers>\n");
+                for pair in &question.matches {
+                    if pair.right_image_path.is_some() {
+                        self.notes.push(
+                            "Images on the right-hand side of a match were not exported"
+                                .to_string(),
+                        );
+                    }
+                    let answer = format!(
+                        "      <answer>\n        <text>{}</text>\n      </answer>\n",
+                        escape_xml(&pair.right_item)
+                    );
+                    body.push_str(&self.rich_text(
+                        "subquestion",
+                        "",
+                        &pair.left_item,
+                        pair.left_image_path.as_deref(),
+                        &answer,
+                    ));
+                }
+            }
+            "gapselect" => write_select_options(question, &mut body),
+            _ => unreachable!(),
+        }
+
+        self.xml
+            .push_str(&format!("  <question type=\"{}\">\n", question_type));
+        self.xml.push_str(&body);
+        self.xml.push_str("  </question>\n");
+        Ok(())
+    }
+
+    fn write_multichoice(
+        &mut self,
+        question: &QuestionWithDetails,
+        body: &mut String,
+    ) -> Result<(), String> {
+        let correct = question.options.iter().filter(|o| o.is_correct).count();
+        if correct == 0 {
+            return Err("Question has no correct option".to_string());
+        }
+
+        let single = question.question.question_type != "MULTIPLE_CHOICE";
+        body.push_str(&format!(
+            "    <single>{}</single>\n    <shuffleanswers>true</shuffleanswers>\n    <answernumbering>abc</answernumbering>\n",
+            single
+        ));
+
+        let fraction = 100.0 / correct as f64;
+        for option in &question.options {
+            let fraction = if option.is_correct { fraction } else { 0.0 };
+            body.push_str(&self.rich_text(
+                "answer",
+                &format!(" fraction=\"{}\"", format_number(fraction)),
+                &option.option_text,
+                option.option_image_path.as_deref(),
+                "",
+            ));
+        }
+        Ok(())
+    }
+
+    /// An element holding HTML text with an optional embedded image. `extra` is
+    /// written inside the element after the text.
+    fn rich_text(
+        &mut self,
+        tag: &str,
+        attributes: &str,
+        text: &str,
+        image_path: Option<&str>,
+        extra: &str,
+    ) -> String {
+        let mut html = text_to_html(text);
+        let mut file = String::new();
+
+        if let Some(path) = image_path {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            match (self.load_image)(path) {
+                Ok(data) => {
+                    html.push_str(&format!(
+                        "<br><img src=\"{}{}\" alt=\"\">",
+                        PLUGINFILE,
+                        escape_xml(name)
+                    ));
+                    file = format!(
+                        "      <file name=\"{}\" path=\"/\" encoding=\"base64\">{}</file>\n",
+                        escape_xml(name),
+                        base64::engine::general_purpose::STANDARD.encode(data)
+                    );
+                }
+                Err(e) => self
+                    .notes
+                    .push(format!("Image {} was not exported: {}", path, e)),
+            }
+        }
+
+        format!(
+            "    <{tag}{attributes} format=\"html\">\n      <text>{}</text>\n{file}{extra}    </{tag}>\n",
+            escape_xml(&html),
+        )
+    }
+}
+
+/// A two-option question with True and False as its options
+fn is_true_false(question: &QuestionWithDetails) -> bool {
+    let mut names: Vec<String> = question
+        .options
+        .iter()
+        .map(|o| o.option_text.trim().to_lowercase())
+        .collect();
+    names.sort();
+    names == ["false", "true"]
+        && question.options.iter().filter(|o| o.is_correct).count() == 1
+        && question
+            .options
+            .iter()
+            .all(|o| o.option_image_path.is_none())
+}
+
+/// Each blank's dropdown choices, with the correct answer added if it is missing
+fn gap_options(question: &QuestionWithDetails) -> Vec<(&str, Vec<&str>)> {
+    question
+        .blanks
+        .iter()
+        .map(|blank| {
+            let correct = blank.correct_answer.trim();
+            let mut options: Vec<&str> = split_list(blank.dropdown_options.as_deref()).collect();
+            if !options.contains(&correct) {
+                options.insert(0, correct);
+            }
+            (correct, options)
+        })
+        .collect()
+}
+
+/// The 1-based number of the correct choice for each gap, counting choices
+/// across all groups in the order `write_select_options` writes them
+fn gap_choice_numbers(question: &QuestionWithDetails) -> Vec<usize> {
+    let mut offset = 0;
+    gap_options(question)
+        .into_iter()
+        .map(|(correct, options)| {
+            let number = offset + options.iter().position(|o| *o == correct).unwrap_or(0) + 1;
+            offset += options.len();
+            number
+        })
+        .collect()
+}
+
+/// One group of select options per blank
+fn write_select_options(question: &QuestionWithDetails, body: &mut String) {
+    body.push_str("    <shuffleanswers>0</shuffleanswers>\n");
+    for (index, (_, options)) in gap_options(question).into_iter().enumerate() {
+        for option in options {
+            body.push_str(&format!(
+                "    <selectoption>\n      <text>{}</text>\n      <group>{}</group>\n    </selectoption>\n",
+                escape_xml(option),
+                index + 1
+            ));
+        }
+    }
+}
+
+/// Replace the blank markers with `[[n]]` gaps pointing at each blank's correct
+/// choice, appending gaps for blanks the text has no marker for
+fn gap_text(text: &str, choice_numbers: &[usize]) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    let mut numbers = choice_numbers.iter();
+
+    for number in numbers.by_ref() {
+        let Some((index, marker)) = find_blank(rest) else {
+            out.push_str(rest);
+            rest = "";
+            out.push_str(&format!(" [[{}]]", number));
+            break;
+        };
+        out.push_str(&rest[..index]);
+        out.push_str(&format!("[[{}]]", number));
+        rest = &rest[index + marker.len()..];
+    }
+    out.push_str(rest);
+
+    for number in numbers {
+        out.push_str(&format!(" [[{}]]", number));
+    }
+    out
+}
+
+fn plain_answer(fraction: f64, text: &str, extra: &str) -> String {
+    format!(
+        "    <answer fraction=\"{}\" format=\"moodle_auto_format\">\n      <text>{}</text>\n{}    </answer>\n",
+        format_number(fraction),
+        escape_xml(text),
+        extra
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::fixtures::{dropdown_question, parsed_content, question, stored_content};
+
+    fn write(questions: Vec<QuestionWithDetails>) -> (String, ExportReport) {
+        let categories = [ExportCategory {
+            path: vec!["Science".to_string()],
+            questions,
+        }];
+        write_quiz(&categories, &mut |path| Ok(path.as_bytes().to_vec()))
+    }
+
+    fn read(xml: &str) -> (Vec<CategorizedQuestion>, Vec<ImportIssue>) {
+        parse(xml, &mut |name, data| {
+            Ok(format!("images/{}:{}", name, String::from_utf8_lossy(data)))
+        })
+        .unwrap()
+    }
+
+    /// Write `question` and read it back, expecting exactly one question and no issues
+    fn round_trip(question: QuestionWithDetails) -> CreateQuestionData {
+        let (xml, report) = write(vec![question]);
+        assert_eq!(report.exported, 1);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+        let (mut parsed, issues) = read(&xml);
+        assert!(issues.is_empty(), "{}\n{:?}", xml, issues);
+        assert_eq!(parsed.len(), 1, "{}", xml);
+        assert_eq!(parsed[0].category, ["Science"]);
+        parsed.remove(0).question.data
+    }
+
+    #[test]
+    fn supported_types_round_trip() {
+        for question_type in [
+            "SINGLE_CHOICE",
+            "MULTIPLE_CHOICE",
+            "FILL_BLANK",
+            "NUMERIC_INPUT",
+            "MATCHING",
+        ] {
+            let original = question(question_type);
+            let parsed = round_trip(original.clone());
+            assert_eq!(
+                parsed_content(&parsed),
+                stored_content(&original),
+                "{}",
+                question_type
+            );
+        }
+
+        let original = dropdown_question();
+        assert_eq!(
+            parsed_content(&round_trip(original.clone())),
+            stored_content(&original)
+        );
+    }
+
+    #[test]
+    fn image_identification_comes_back_as_single_choice() {
+        let original = question("IMAGE_IDENTIFICATION");
+        let mut expected = stored_content(&original);
+        expected.question_type = "SINGLE_CHOICE".to_string();
+
+        assert_eq!(parsed_content(&round_trip(original)), expected);
+    }
+
+    #[test]
+    fn true_false_options_round_trip_as_a_truefalse_question() {
+        let mut original = question("SINGLE_CHOICE");
+        original.options.truncate(2);
+        original.options[0].option_text = "False".to_string();
+        original.options[1].option_text = "True".to_string();
+
+        let (xml, _) = write(vec![original.clone()]);
+        assert!(xml.contains("<question type=\"truefalse\">"), "{}", xml);
+
+        let parsed = round_trip(original);
+        let options: Vec<_> = parsed
+            .options
+            .iter()
+            .map(|o| (o.option_text.as_str(), o.is_correct))
+            .collect();
+        assert_eq!(options, [("True", false), ("False", true)]);
+    }
+
+    #[test]
+    fn embedded_images_and_categories_round_trip() {
+        let mut original = question("SINGLE_CHOICE");
+        original.question.question_image_path = Some("images/map.png".to_string());
+        original.options[1].option_image_path = Some("images/lyon.png".to_string());
+        let categories = [ExportCategory {
+            path: vec!["Geography".to_string(), "Europe/Asia".to_string()],
+            questions: vec![original],
+        }];
+
+        let (xml, _) = write_quiz(&categories, &mut |path| Ok(path.as_bytes().to_vec()));
+        let (parsed, issues) = read(&xml);
+
+        assert!(issues.is_empty(), "{:?}", issues);
+        assert_eq!(parsed[0].category, ["Geography", "Europe/Asia"]);
+        let data = &parsed[0].question.data;
+        assert_eq!(
+            data.question_image_path.as_deref(),
+            Some("images/map.png:images/map.png")
+        );
+        assert_eq!(
+            data.options[1].option_image_path.as_deref(),
+            Some("images/lyon.png:images/lyon.png")
+        );
+        assert_eq!(data.question_text, "What is the capital of France?");
+    }
+
+    #[test]
+    fn calculation_keeps_only_its_first_blank() {
+        let (xml, report) = write(vec![question("CALCULATION")]);
+        assert_eq!(report.exported, 1);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].message, "Blank 2 (answer 5) was dropped");
+
+        let (parsed, _) = read(&xml);
+        let numeric = parsed[0].question.data.numeric_data.as_ref().unwrap();
+        assert_eq!(
+            (numeric.correct_answer.as_str(), numeric.tolerance.as_str()),
+            ("12", "0")
+        );
+    }
+
+    #[test]
+    fn questions_moodle_cannot_hold_are_rejected() {
+        let mut many_gaps = dropdown_question();
+        let blank = many_gaps.blanks[0].clone();
+        many_gaps.blanks = (0..=MAX_GAP_GROUPS as i32)
+            .map(|index| {
+                let mut blank = blank.clone();
+                blank.blank_index = index;
+                blank
+            })
+            .collect();
+
+        let (xml, report) = write(vec![
+            question("FILL_BLANK_MULTIPLE"),
+            question("ORDERING"),
+            many_gaps,
+        ]);
+
+        assert_eq!(report.exported, 0);
+        let messages: Vec<_> = report
+            .issues
+            .iter()
+            .map(|issue| issue.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Several typed-in blanks need Moodle's Cloze type, which is not supported",
+                "Moodle has no built-in ordering question type",
+                "Moodle allows at most 20 dropdown groups",
+            ]
+        );
+        assert!(report.issues.iter().all(|issue| issue.skipped));
+        assert!(!xml.contains("<question type=\"gapselect\">"));
+    }
+
+    #[test]
+    fn dropdown_choices_with_commas_are_skipped() {
+        let xml = r#"<quiz>
+  <question type="gapselect">
+    <questiontext format="html"><text>Pick [[1]]</text></questiontext>
+    <selectoption><text>yes, really</text><group>1</group></selectoption>
+    <selectoption><text>no</text><group>1</group></selectoption>
+  </question>
+</quiz>"#;
+        let (parsed, issues) = read(xml);
+
+        assert!(parsed.is_empty());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 2);
+        assert!(issues[0].skipped);
+        assert_eq!(
+            issues[0].message,
+            "Dropdown choices containing commas are not supported"
+        );
+    }
+
+    #[test]
+    fn unsupported_questions_are_reported_by_line() {
+        let xml = "<quiz>\n  <question type=\"essay\">\n    <questiontext><text>Discuss.</text></questiontext>\n  </question>\n</quiz>";
+        let (parsed, issues) = read(xml);
+
+        assert!(parsed.is_empty());
+        assert_eq!((issues[0].line, issues[0].skipped), (2, true));
+        assert_eq!(
+            issues[0].message,
+            "Moodle question type \"essay\" is not supported"
+        );
+    }
+
+    #[test]
+    fn other_root_elements_are_rejected() {
+        let Err(error) = parse("<questions/>", &mut |_, _| unreachable!()) else {
+            panic!("parsed a file without <quiz>");
+        };
+        assert!(
+            error.to_string().contains("root element must be <quiz>"),
+            "{}",
+            error
+        );
+    }
+}
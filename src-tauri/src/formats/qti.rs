@@ -0,0 +1,1758 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, Write};
+
+use roxmltree::{Document, Node};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::commands::images::MAX_IMAGE_BYTES;
+use crate::commands::questions::{
+    CreateMatchPair, CreateNumericData, CreateOrderItem, CreateQuestionBlank, CreateQuestionData,
+    CreateQuestionOption, QuestionBlank, QuestionWithDetails,
+};
+use crate::error::{AppError, AppResult};
+use crate::formats::{
+    default_name, empty_question, escape_xml, find_blank, format_number, html_to_text, split_list,
+    ExportIssue, ExportReport, ImportIssue, ParsedQuestion,
+};
+
+const QTI_NAMESPACE: &str = "http://www.imsglobal.org/xsd/imsqti_v2p1";
+const QTI_SCHEMA: &str = "http://www.imsglobal.org/xsd/qti/qtiv2p1/imsqti_v2p1.xsd";
+const CP_NAMESPACE: &str = "http://www.imsglobal.org/xsd/imscp_v1p1";
+const CP_SCHEMA: &str = "http://www.imsglobal.org/xsd/imscp_v1p1.xsd";
+
+/// Manifest resource types of items; QTI 2.0 and 2.2 items read the same way
+const ITEM_RESOURCE_PREFIX: &str = "imsqti_item_xmlv2p";
+
+/// Largest manifest or item document read from a package
+const MAX_XML_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Stands in for an inline interaction while the item body is reduced to text
+const BLANK_PLACEHOLDER: char = '\u{0}';
+
+/// Interactions the item body may contain; anything else ending in `Interaction` is rejected
+const BLOCK_INTERACTIONS: &[&str] = &["choiceInteraction", "orderInteraction", "matchInteraction"];
+const INLINE_INTERACTIONS: &[&str] = &["textEntryInteraction", "inlineChoiceInteraction"];
+
+/// Elements whose content starts on a new line when the body is reduced to text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p",
+    "div",
+    "li",
+    "tr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "dt",
+    "dd",
+    "caption",
+];
+
+/// Elements that only show up conditionally and are left out of the question text
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "feedbackBlock",
+    "feedbackInline",
+    "rubricBlock",
+    "templateBlock",
+    "templateInline",
+    "printedVariable",
+];
+
+/// Where an image from the package ends up on the imported question
+enum ImageSlot {
+    Question,
+    Option(usize),
+    MatchLeft(usize),
+    MatchRight(usize),
+}
+
+/// Collects what an item loses and the package files it uses while it is read
+struct ItemContext<'p> {
+    /// Path of the item inside the package; image paths are relative to it
+    item_path: &'p str,
+    notes: Vec<String>,
+    images: Vec<(ImageSlot, String)>,
+}
+
+impl ItemContext<'_> {
+    /// Keep the first image for `slot`; the editor has room for one image per field
+    fn attach(&mut self, slot: ImageSlot, sources: Vec<String>) {
+        let mut sources = sources.into_iter();
+        if let Some(source) = sources.next() {
+            match resolve_path(self.item_path, &source) {
+                Some(path) => self.images.push((slot, path)),
+                None => self.notes.push(format!(
+                    "Image {} is not part of the package and was dropped",
+                    source
+                )),
+            }
+        }
+        let extra = sources.count();
+        if extra > 0 {
+            self.notes
+                .push(format!("{} extra image(s) were dropped", extra));
+        }
+    }
+}
+
+/// Read the items of an IMS QTI 2.1 content package (a zip with `imsmanifest.xml`).
+///
+/// Images of the items that are kept are handed to `store_image`, which returns the
+/// path to record for them. Items are read in manifest order; problems are reported
+/// with the line of the item's `<resource>` in the manifest.
+pub fn read_package<R: Read + Seek>(
+    reader: R,
+    store_image: &mut dyn FnMut(&str, &[u8]) -> AppResult<String>,
+) -> AppResult<(Vec<ParsedQuestion>, Vec<ImportIssue>)> {
+    let mut archive = ZipArchive::new(reader)
+        .map_err(|e| AppError::validation(format!("Not a valid QTI package: {}", e), &["path"]))?;
+
+    let manifest = read_entry(&mut archive, "imsmanifest.xml", MAX_XML_BYTES)
+        .map_err(|message| AppError::validation(message, &["path"]))?;
+    let manifest = String::from_utf8_lossy(&manifest);
+    let doc = Document::parse(&manifest).map_err(|e| {
+        AppError::validation(
+            format!("imsmanifest.xml is not valid XML: {}", e),
+            &["path"],
+        )
+    })?;
+
+    let resources: Vec<(usize, String)> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("resource"))
+        .filter(|n| {
+            n.attribute("type")
+                .is_some_and(|t| t.starts_with(ITEM_RESOURCE_PREFIX))
+        })
+        .filter_map(|n| {
+            let line = doc.text_pos_at(n.range().start).row as usize;
+            n.attribute("href").map(|href| (line, href.to_string()))
+        })
+        .collect();
+    if resources.is_empty() {
+        return Err(AppError::validation(
+            "The package contains no QTI items",
+            &["path"],
+        ));
+    }
+
+    let mut questions = Vec::new();
+    let mut issues = Vec::new();
+
+    for (line, href) in resources {
+        let mut context = ItemContext {
+            item_path: &href,
+            notes: Vec::new(),
+            images: Vec::new(),
+        };
+        let item = read_entry(&mut archive, &href, MAX_XML_BYTES).and_then(|xml| {
+            read_item(&String::from_utf8_lossy(&xml), &mut context)
+                .map_err(|message| format!("{}: {}", href, message))
+        });
+
+        match item {
+            Ok(mut data) => {
+                for (slot, path) in context.images {
+                    let image = match read_entry(&mut archive, &path, MAX_IMAGE_BYTES) {
+                        Ok(image) => image,
+                        Err(message) => {
+                            context.notes.push(message);
+                            continue;
+                        }
+                    };
+                    let name = path.rsplit('/').next().unwrap_or(&path);
+                    let stored = Some(store_image(name, &image)?);
+                    match slot {
+                        ImageSlot::Question => data.question_image_path = stored,
+                        ImageSlot::Option(index) => data.options[index].option_image_path = stored,
+                        ImageSlot::MatchLeft(index) => {
+                            if let Some(pair) =
+                                data.match_pairs.as_mut().and_then(|p| p.get_mut(index))
+                            {
+                                pair.left_image_path = stored;
+                            }
+                        }
+                        ImageSlot::MatchRight(index) => {
+                            if let Some(pair) =
+                                data.match_pairs.as_mut().and_then(|p| p.get_mut(index))
+                            {
+                                pair.right_image_path = stored;
+                            }
+                        }
+                    }
+                }
+                issues.extend(context.notes.into_iter().map(|message| ImportIssue {
+                    line,
+                    message: format!("{}: {}", href, message),
+                    skipped: false,
+                }));
+                questions.push(ParsedQuestion { line, data });
+            }
+            Err(message) => issues.push(ImportIssue {
+                line,
+                message,
+                skipped: true,
+            }),
+        }
+    }
+
+    Ok((questions, issues))
+}
+
+/// Read an entry of at most `limit` bytes. The size in the zip directory can be
+/// forged, so the read itself is capped as well.
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &str,
+    limit: u64,
+) -> Result<Vec<u8>, String> {
+    let too_large = || format!("{} is larger than {} MB", path, limit / (1024 * 1024));
+
+    let entry = archive
+        .by_name(path)
+        .map_err(|_| format!("{} is missing from the package", path))?;
+    if entry.size() > limit {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    if data.len() as u64 > limit {
+        return Err(too_large());
+    }
+    Ok(data)
+}
+
+/// Resolve an image reference against the item's own path. References outside the
+/// package (URLs, absolute paths) resolve to `None`.
+fn resolve_path(item_path: &str, source: &str) -> Option<String> {
+    if source.contains(':') || source.starts_with('/') {
+        return None;
+    }
+
+    let mut segments: Vec<&str> = item_path.split('/').collect();
+    segments.pop();
+    for segment in source.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+fn read_item(xml: &str, context: &mut ItemContext) -> Result<CreateQuestionData, String> {
+    let doc = Document::parse(xml).map_err(|e| format!("Not valid XML: {}", e))?;
+    let item = doc.root_element();
+    if !item.has_tag_name("assessmentItem") {
+        return Err("The root element is not <assessmentItem>".to_string());
+    }
+    let body = child(item, "itemBody").ok_or("Item has no itemBody")?;
+
+    let mut text = FlowText::default();
+    text.read(body);
+    let FlowText {
+        html,
+        images,
+        interactions,
+    } = text;
+    context.attach(ImageSlot::Question, images);
+
+    if let Some(other) = interactions
+        .iter()
+        .map(|n| n.tag_name().name())
+        .find(|name| !BLOCK_INTERACTIONS.contains(name) && !INLINE_INTERACTIONS.contains(name))
+    {
+        return Err(format!("QTI interaction {} is not supported", other));
+    }
+    let block: Vec<Node> = interactions
+        .iter()
+        .copied()
+        .filter(|n| BLOCK_INTERACTIONS.contains(&n.tag_name().name()))
+        .collect();
+    let inline: Vec<Node> = interactions
+        .iter()
+        .copied()
+        .filter(|n| INLINE_INTERACTIONS.contains(&n.tag_name().name()))
+        .collect();
+
+    let question_text = html_to_text(&html);
+    let mut data = match (block.as_slice(), inline.is_empty()) {
+        ([], true) => return Err("Item has no interaction".to_string()),
+        ([], false) => read_blanks(item, &inline, question_text, context)?,
+        ([interaction], true) => {
+            let question_text = question_text.replace(BLANK_PLACEHOLDER, "");
+            match interaction.tag_name().name() {
+                "choiceInteraction" => read_choice(item, *interaction, question_text, context)?,
+                "orderInteraction" => read_order(item, *interaction, question_text, context)?,
+                _ => read_match(item, *interaction, question_text, context)?,
+            }
+        }
+        _ => {
+            return Err(
+                "Items with more than one kind of interaction are not supported".to_string(),
+            )
+        }
+    };
+
+    if data.question_text.trim().is_empty() {
+        return Err("Item has no text".to_string());
+    }
+
+    // Keep titles that say more than the start of the question text
+    data.source = item
+        .attribute("title")
+        .map(|title| title.trim().to_string())
+        .filter(|title| {
+            !title.is_empty()
+                && !data
+                    .question_text
+                    .starts_with(title.trim_end_matches("..."))
+        });
+    data.explanation = item
+        .children()
+        .filter(|n| n.has_tag_name("modalFeedback"))
+        .map(|feedback| {
+            let mut text = FlowText::default();
+            text.read(feedback);
+            html_to_text(&text.html)
+        })
+        .find(|text| !text.is_empty());
+    if let Some(points) = max_score(item) {
+        data.points = points.round().max(0.0) as i32;
+    }
+
+    Ok(data)
+}
+
+/// The item's MAXSCORE default, or the SCORE outcome's normalMaximum
+fn max_score(item: Node) -> Option<f64> {
+    let outcome = |identifier: &str| {
+        item.children()
+            .filter(|n| n.has_tag_name("outcomeDeclaration"))
+            .find(|n| n.attribute("identifier") == Some(identifier))
+    };
+
+    outcome("MAXSCORE")
+        .and_then(|decl| child(decl, "defaultValue"))
+        .and_then(|default| child_text(default, "value"))
+        .and_then(|value| value.trim().parse().ok())
+        .or_else(|| {
+            outcome("SCORE")
+                .and_then(|decl| decl.attribute("normalMaximum"))
+                .and_then(|max| max.trim().parse().ok())
+        })
+}
+
+fn read_choice(
+    item: Node,
+    interaction: Node,
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let correct = correct_identifiers(item, interaction);
+    let choices: Vec<Node> = interaction
+        .descendants()
+        .filter(|n| n.has_tag_name("simpleChoice"))
+        .collect();
+    let correct_count = choices
+        .iter()
+        .filter(|c| {
+            c.attribute("identifier")
+                .is_some_and(|id| correct.iter().any(|v| v == id))
+        })
+        .count();
+    if correct_count == 0 {
+        return Err("Choice interaction has no correct answer".to_string());
+    }
+
+    let single = interaction
+        .attribute("maxChoices")
+        .is_some_and(|max| max.trim() == "1");
+    let question_type = if single && correct_count == 1 {
+        "SINGLE_CHOICE"
+    } else {
+        "MULTIPLE_CHOICE"
+    };
+
+    let mut data = empty_question(0, 0, question_type, question_text);
+    for (index, choice) in choices.into_iter().enumerate() {
+        let (option_text, images) = flow_text(choice);
+        context.attach(ImageSlot::Option(index), images);
+        data.options.push(CreateQuestionOption {
+            option_text,
+            option_image_path: None,
+            is_correct: choice
+                .attribute("identifier")
+                .is_some_and(|id| correct.iter().any(|v| v == id)),
+            display_order: index as i32,
+        });
+    }
+    Ok(data)
+}
+
+fn read_order(
+    item: Node,
+    interaction: Node,
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let choices: HashMap<&str, Node> = interaction
+        .descendants()
+        .filter(|n| n.has_tag_name("simpleChoice"))
+        .filter_map(|n| n.attribute("identifier").map(|id| (id, n)))
+        .collect();
+    let order = declaration(item, interaction)
+        .map(correct_values)
+        .unwrap_or_default();
+    if order.is_empty() {
+        return Err("Order interaction has no correct order".to_string());
+    }
+
+    let mut items = Vec::with_capacity(order.len());
+    for identifier in &order {
+        let choice = choices
+            .get(identifier.as_str())
+            .ok_or_else(|| format!("Correct order names unknown choice {}", identifier))?;
+        let (text, images) = flow_text(*choice);
+        if !images.is_empty() {
+            context
+                .notes
+                .push("Images on ordering items are not supported and were dropped".to_string());
+        }
+        items.push(CreateOrderItem {
+            text,
+            correct_position: items.len() as i32 + 1,
+        });
+    }
+    if choices.len() > items.len() {
+        context.notes.push(format!(
+            "{} item(s) outside the correct order were dropped",
+            choices.len() - items.len()
+        ));
+    }
+
+    let mut data = empty_question(0, 0, "ORDERING", question_text);
+    data.order_items = Some(items);
+    Ok(data)
+}
+
+fn read_match(
+    item: Node,
+    interaction: Node,
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let sets: Vec<Vec<Node>> = interaction
+        .children()
+        .filter(|n| n.has_tag_name("simpleMatchSet"))
+        .map(|set| {
+            set.children()
+                .filter(|n| n.has_tag_name("simpleAssociableChoice"))
+                .collect()
+        })
+        .collect();
+    let [sources, targets] = sets.as_slice() else {
+        return Err("Match interaction needs exactly two match sets".to_string());
+    };
+    // Correct pairs are written `source target`
+    let correct = correct_identifiers(item, interaction);
+    let mut pairs = Vec::new();
+    let mut used_targets = vec![false; targets.len()];
+    for source in sources {
+        let identifier = source.attribute("identifier").unwrap_or("");
+        let target = correct.iter().find_map(|pair| {
+            let mut ids = pair.split_whitespace();
+            if ids.next() != Some(identifier) {
+                return None;
+            }
+            ids.next().and_then(|target| {
+                targets
+                    .iter()
+                    .position(|n| n.attribute("identifier") == Some(target))
+            })
+        });
+        let (left_item, left_images) = flow_text(*source);
+        let Some(target) = target else {
+            context.notes.push(format!(
+                "\"{}\" has no correct match and was dropped",
+                left_item
+            ));
+            continue;
+        };
+        used_targets[target] = true;
+        let (right_item, right_images) = flow_text(targets[target]);
+
+        context.attach(ImageSlot::MatchLeft(pairs.len()), left_images);
+        context.attach(ImageSlot::MatchRight(pairs.len()), right_images);
+        pairs.push(CreateMatchPair {
+            left_item,
+            right_item,
+            left_image_path: None,
+            right_image_path: None,
+        });
+    }
+    for (target, used) in targets.iter().zip(used_targets) {
+        if !used {
+            context.notes.push(format!(
+                "Extra distractor \"{}\" was dropped",
+                flow_text(*target).0
+            ));
+        }
+    }
+    if pairs.is_empty() {
+        return Err("Match interaction has no correct pairs".to_string());
+    }
+
+    let mut data = empty_question(0, 0, "MATCHING", question_text);
+    data.match_pairs = Some(pairs);
+    Ok(data)
+}
+
+/// Text entry and inline choice interactions become blanks at their place in the text.
+/// A lone numeric text entry is a numeric question.
+fn read_blanks(
+    item: Node,
+    interactions: &[Node],
+    question_text: String,
+    context: &mut ItemContext,
+) -> Result<CreateQuestionData, String> {
+    let mut blanks = Vec::with_capacity(interactions.len());
+
+    for (index, interaction) in interactions.iter().enumerate() {
+        let blank_index = index as i32;
+        let decl = declaration(item, *interaction);
+        let correct = decl.map(correct_values).unwrap_or_default();
+
+        if interaction.has_tag_name("inlineChoiceInteraction") {
+            let choices: Vec<(&str, String)> = interaction
+                .children()
+                .filter(|n| n.has_tag_name("inlineChoice"))
+                .map(|n| (n.attribute("identifier").unwrap_or(""), flow_text(n).0))
+                .collect();
+            let correct = correct_identifiers(item, *interaction);
+            let correct_answer = choices
+                .iter()
+                .find(|(id, _)| correct.iter().any(|v| v == id))
+                .map(|(_, text)| text.clone())
+                .ok_or_else(|| format!("Dropdown {} has no correct choice", index + 1))?;
+            if choices.iter().any(|(_, text)| text.contains(',')) {
+                return Err("Dropdown choices containing commas are not supported".to_string());
+            }
+            blanks.push(CreateQuestionBlank {
+                blank_index,
+                correct_answer,
+                acceptable_answers: None,
+                is_numeric: false,
+                numeric_tolerance: None,
+                unit: None,
+                input_type: "DROPDOWN".to_string(),
+                dropdown_options: Some(
+                    choices
+                        .into_iter()
+                        .map(|(_, text)| text)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            });
+            continue;
+        }
+
+        // Full-credit mapping keys are alternative spellings
+        let mut accepted = correct;
+        if let Some(mapping) = decl.and_then(|d| child(d, "mapping")) {
+            for entry in mapping.children().filter(|n| n.has_tag_name("mapEntry")) {
+                let key = entry.attribute("mapKey").unwrap_or("").trim().to_string();
+                let value: f64 = entry
+                    .attribute("mappedValue")
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(0.0);
+                if value > 0.0 && !key.is_empty() && !accepted.contains(&key) {
+                    accepted.push(key);
+                }
+            }
+        }
+        if accepted.is_empty() {
+            return Err(format!("Text entry {} has no correct answer", index + 1));
+        }
+        let correct_answer = accepted.remove(0);
+        accepted.retain(|answer| {
+            let keep = !answer.contains(',');
+            if !keep {
+                context.notes.push(format!(
+                    "Alternative answer \"{}\" contains a comma and was dropped",
+                    answer
+                ));
+            }
+            keep
+        });
+
+        let is_numeric = decl
+            .and_then(|d| d.attribute("baseType"))
+            .is_some_and(|base| matches!(base, "float" | "integer"));
+        let numeric_tolerance = if is_numeric {
+            Some(tolerance(item, *interaction, context))
+        } else {
+            None
+        };
+
+        blanks.push(CreateQuestionBlank {
+            blank_index,
+            correct_answer,
+            acceptable_answers: (!accepted.is_empty()).then(|| accepted.join(", ")),
+            is_numeric,
+            numeric_tolerance,
+            unit: None,
+            input_type: "INPUT".to_string(),
+            dropdown_options: None,
+        });
+    }
+
+    // An entry on a line of its own below the question; the rest of that line is the unit
+    if let ([blank], Some((before, after))) = (
+        blanks.as_slice(),
+        question_text.split_once(BLANK_PLACEHOLDER),
+    ) {
+        let on_own_line = before.trim_end_matches(' ').ends_with('\n') && !after.contains('\n');
+        if blank.is_numeric && blank.acceptable_answers.is_none() && on_own_line {
+            let mut data = empty_question(0, 0, "NUMERIC_INPUT", before.trim().to_string());
+            data.numeric_data = Some(CreateNumericData {
+                correct_answer: blank.correct_answer.clone(),
+                tolerance: format_number(blank.numeric_tolerance.unwrap_or(0.0)),
+                unit: Some(after.trim().to_string()).filter(|unit| !unit.is_empty()),
+            });
+            return Ok(data);
+        }
+    }
+
+    let question_type = if blanks.len() == 1 {
+        "FILL_BLANK"
+    } else {
+        "FILL_BLANK_MULTIPLE"
+    };
+    let mut data = empty_question(
+        0,
+        0,
+        question_type,
+        question_text.replace(BLANK_PLACEHOLDER, "___"),
+    );
+    data.blanks = blanks;
+    Ok(data)
+}
+
+/// The absolute tolerance of an `<equal>` comparison on the interaction's response
+fn tolerance(item: Node, interaction: Node, context: &mut ItemContext) -> f64 {
+    let identifier = interaction.attribute("responseIdentifier").unwrap_or("");
+    let equal = child(item, "responseProcessing").and_then(|processing| {
+        processing
+            .descendants()
+            .filter(|n| n.has_tag_name("equal"))
+            .find(|equal| {
+                equal.children().any(|n| {
+                    n.has_tag_name("variable") && n.attribute("identifier") == Some(identifier)
+                })
+            })
+    });
+    let Some(equal) = equal else {
+        return 0.0;
+    };
+
+    let value = equal
+        .attribute("tolerance")
+        .and_then(|t| t.split_whitespace().next())
+        .and_then(|t| t.parse::<f64>().ok())
+        .unwrap_or(0.0)
+        .abs();
+    match equal.attribute("toleranceMode").unwrap_or("exact") {
+        "absolute" => value,
+        "relative" => {
+            context
+                .notes
+                .push("A relative tolerance was dropped; answers must be exact".to_string());
+            0.0
+        }
+        _ => 0.0,
+    }
+}
+
+/// The response declaration an interaction writes to
+fn declaration<'a, 'input>(item: Node<'a, 'input>, interaction: Node) -> Option<Node<'a, 'input>> {
+    let identifier = interaction.attribute("responseIdentifier")?;
+    item.children()
+        .filter(|n| n.has_tag_name("responseDeclaration"))
+        .find(|n| n.attribute("identifier") == Some(identifier))
+}
+
+fn correct_values(decl: Node) -> Vec<String> {
+    child(decl, "correctResponse")
+        .map(|correct| {
+            correct
+                .children()
+                .filter(|n| n.has_tag_name("value"))
+                .map(|n| n.text().unwrap_or("").trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Correct identifiers (or `source target` pairs) of an interaction: its correct
+/// response, or the mapping keys that earn credit when there is none
+fn correct_identifiers(item: Node, interaction: Node) -> Vec<String> {
+    let Some(decl) = declaration(item, interaction) else {
+        return Vec::new();
+    };
+    let correct = correct_values(decl);
+    if !correct.is_empty() {
+        return correct;
+    }
+
+    child(decl, "mapping")
+        .map(|mapping| {
+            mapping
+                .children()
+                .filter(|n| n.has_tag_name("mapEntry"))
+                .filter(|n| {
+                    n.attribute("mappedValue")
+                        .and_then(|v| v.trim().parse::<f64>().ok())
+                        .is_some_and(|v| v > 0.0)
+                })
+                .filter_map(|n| n.attribute("mapKey").map(|k| k.trim().to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Body content reduced to the HTML `html_to_text` understands, with the images and
+/// interactions found along the way. Inline interactions leave `BLANK_PLACEHOLDER`.
+#[derive(Default)]
+struct FlowText<'a, 'input> {
+    html: String,
+    images: Vec<String>,
+    interactions: Vec<Node<'a, 'input>>,
+}
+
+impl<'a, 'input> FlowText<'a, 'input> {
+    fn read(&mut self, parent: Node<'a, 'input>) {
+        for node in parent.children() {
+            if node.is_text() {
+                self.html.push_str(&escape_xml(node.text().unwrap_or("")));
+                continue;
+            }
+            if !node.is_element() {
+                continue;
+            }
+
+            let name = node.tag_name().name();
+            if name == "img" {
+                self.images
+                    .extend(node.attribute("src").map(str::to_string));
+            } else if name == "object" {
+                if node
+                    .attribute("type")
+                    .is_some_and(|t| t.starts_with("image/"))
+                {
+                    self.images
+                        .extend(node.attribute("data").map(str::to_string));
+                }
+            } else if name == "br" {
+                self.html.push_str("<br>");
+            } else if INLINE_INTERACTIONS.contains(&name) {
+                self.html.push(BLANK_PLACEHOLDER);
+                self.interactions.push(node);
+            } else if name.ends_with("Interaction") {
+                // The prompt is part of the question; the choices are read separately
+                if let Some(prompt) = child(node, "prompt") {
+                    self.html.push_str("<p>");
+                    self.read(prompt);
+                    self.html.push_str("</p>");
+                }
+                self.interactions.push(node);
+            } else if SKIPPED_ELEMENTS.contains(&name) {
+                continue;
+            } else if BLOCK_ELEMENTS.contains(&name) {
+                self.html.push_str("<p>");
+                self.read(node);
+                self.html.push_str("</p>");
+            } else {
+                self.read(node);
+            }
+        }
+    }
+}
+
+/// Plain text and image references of a choice or other flow container
+fn flow_text(node: Node) -> (String, Vec<String>) {
+    let mut text = FlowText::default();
+    text.read(node);
+    (html_to_text(&text.html), text.images)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name).map(|n| n.text().unwrap_or("").to_string())
+}
+
+/// Questions written as one section of the assessment test
+pub struct ExportSection {
+    pub title: String,
+    /// How many of the section's questions a sitting draws; all of them when `None`
+    pub select: Option<usize>,
+    pub questions: Vec<QuestionWithDetails>,
+}
+
+/// The assessment test written alongside the items
+pub struct ExportTest {
+    pub title: String,
+    pub time_limit_minutes: Option<i32>,
+    pub shuffle_questions: bool,
+    pub shuffle_choices: bool,
+    pub sections: Vec<ExportSection>,
+}
+
+/// Write an IMS QTI 2.1 content package: one item file per question under `items/`,
+/// their images under `items/media/`, an assessment test with one section per
+/// `ExportSection`, and the manifest listing them. `load_image` reads an image by its
+/// stored path; images that cannot be read are reported and left out.
+pub fn write_package<W: Write + Seek>(
+    writer: W,
+    test: &ExportTest,
+    load_image: &mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+) -> AppResult<ExportReport> {
+    let mut report = ExportReport {
+        exported: 0,
+        issues: Vec::new(),
+    };
+    let mut media = Media {
+        load_image,
+        files: BTreeMap::new(),
+        paths: HashMap::new(),
+    };
+    // Item file contents and the media each uses, by question id
+    let mut items: BTreeMap<i64, (String, Vec<String>)> = BTreeMap::new();
+    let mut section_refs = Vec::with_capacity(test.sections.len());
+
+    for section in &test.sections {
+        let mut refs = Vec::new();
+        for question in &section.questions {
+            let question_id = question.question.id;
+            if items.contains_key(&question_id) {
+                refs.push(question_id);
+                continue;
+            }
+
+            let mut writer = ItemWriter {
+                notes: Vec::new(),
+                files: Vec::new(),
+                media: &mut media,
+                shuffle: test.shuffle_choices,
+            };
+            match writer.write(question) {
+                Ok(xml) => {
+                    report.exported += 1;
+                    report
+                        .issues
+                        .extend(writer.notes.into_iter().map(|message| ExportIssue {
+                            question_id,
+                            message,
+                            skipped: false,
+                        }));
+                    items.insert(question_id, (xml, writer.files));
+                    refs.push(question_id);
+                }
+                Err(message) => report.issues.push(ExportIssue {
+                    question_id,
+                    message,
+                    skipped: true,
+                }),
+            }
+        }
+        section_refs.push(refs);
+    }
+
+    let mut zip = ZipWriter::new(writer);
+
+    add_file(
+        &mut zip,
+        "imsmanifest.xml",
+        write_manifest(&items).as_bytes(),
+    )?;
+    add_file(
+        &mut zip,
+        "test.xml",
+        write_test(test, &section_refs).as_bytes(),
+    )?;
+    for (question_id, (xml, _)) in &items {
+        add_file(&mut zip, &item_path(*question_id), xml.as_bytes())?;
+    }
+    for (path, data) in &media.files {
+        add_file(&mut zip, path, data)?;
+    }
+    zip.finish()
+        .map_err(|e| AppError::io(format!("Failed to write package: {}", e)))?;
+
+    Ok(report)
+}
+
+fn add_file<W: Write + Seek>(zip: &mut ZipWriter<W>, path: &str, data: &[u8]) -> AppResult<()> {
+    zip.start_file(path, SimpleFileOptions::default())
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path, e)))?;
+    zip.write_all(data)?;
+    Ok(())
+}
+
+fn item_path(question_id: i64) -> String {
+    format!("items/{}.xml", item_identifier(question_id))
+}
+
+fn item_identifier(question_id: i64) -> String {
+    format!("q{}", question_id)
+}
+
+fn write_manifest(items: &BTreeMap<i64, (String, Vec<String>)>) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<manifest xmlns=\"{CP_NAMESPACE}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"{CP_NAMESPACE} {CP_SCHEMA} {QTI_NAMESPACE} {QTI_SCHEMA}\" identifier=\"quizforge-package\">\n"
+    );
+    xml.push_str("  <metadata>\n    <schema>QTIv2.1 Package</schema>\n    <schemaversion>1.0.0</schemaversion>\n  </metadata>\n");
+    xml.push_str("  <organizations/>\n  <resources>\n");
+
+    xml.push_str("    <resource identifier=\"test\" type=\"imsqti_test_xmlv2p1\" href=\"test.xml\">\n      <file href=\"test.xml\"/>\n");
+    for question_id in items.keys() {
+        xml.push_str(&format!(
+            "      <dependency identifierref=\"{}\"/>\n",
+            item_identifier(*question_id)
+        ));
+    }
+    xml.push_str("    </resource>\n");
+
+    for (question_id, (_, files)) in items {
+        let path = item_path(*question_id);
+        xml.push_str(&format!(
+            "    <resource identifier=\"{}\" type=\"imsqti_item_xmlv2p1\" href=\"{}\">\n      <file href=\"{}\"/>\n",
+            item_identifier(*question_id),
+            path,
+            path
+        ));
+        for file in files {
+            xml.push_str(&format!("      <file href=\"{}\"/>\n", escape_xml(file)));
+        }
+        xml.push_str("    </resource>\n");
+    }
+
+    xml.push_str("  </resources>\n</manifest>\n");
+    xml
+}
+
+fn write_test(test: &ExportTest, section_refs: &[Vec<i64>]) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<assessmentTest xmlns=\"{QTI_NAMESPACE}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"{QTI_NAMESPACE} {QTI_SCHEMA}\" identifier=\"test\" title=\"{}\">\n",
+        escape_xml(&test.title)
+    );
+    xml.push_str(
+        "  <outcomeDeclaration identifier=\"SCORE\" cardinality=\"single\" baseType=\"float\"/>\n",
+    );
+    if let Some(minutes) = test.time_limit_minutes.filter(|m| *m > 0) {
+        xml.push_str(&format!("  <timeLimits maxTime=\"{}\"/>\n", minutes * 60));
+    }
+    xml.push_str("  <testPart identifier=\"part\" navigationMode=\"nonlinear\" submissionMode=\"simultaneous\">\n");
+
+    for (index, (section, refs)) in test.sections.iter().zip(section_refs).enumerate() {
+        xml.push_str(&format!(
+            "    <assessmentSection identifier=\"section{}\" title=\"{}\" visible=\"true\">\n",
+            index + 1,
+            escape_xml(&section.title)
+        ));
+        // Drawing more items than a section has is an error in most players
+        if let Some(select) = section.select {
+            xml.push_str(&format!(
+                "      <selection select=\"{}\"/>\n",
+                select.min(refs.len())
+            ));
+        }
+        if test.shuffle_questions {
+            xml.push_str("      <ordering shuffle=\"true\"/>\n");
+        }
+        for question_id in refs {
+            xml.push_str(&format!(
+                "      <assessmentItemRef identifier=\"{}\" href=\"{}\"/>\n",
+                item_identifier(*question_id),
+                item_path(*question_id)
+            ));
+        }
+        xml.push_str("    </assessmentSection>\n");
+    }
+
+    xml.push_str("  </testPart>\n");
+    xml.push_str("  <outcomeProcessing>\n    <setOutcomeValue identifier=\"SCORE\">\n      <sum>\n        <testVariables variableIdentifier=\"SCORE\"/>\n      </sum>\n    </setOutcomeValue>\n  </outcomeProcessing>\n");
+    xml.push_str("</assessmentTest>\n");
+    xml
+}
+
+/// Images copied into the package, shared by every item that uses them
+struct Media<'a> {
+    load_image: &'a mut dyn FnMut(&str) -> AppResult<Vec<u8>>,
+    /// Package path to contents
+    files: BTreeMap<String, Vec<u8>>,
+    /// Stored image path to package path
+    paths: HashMap<String, String>,
+}
+
+impl Media<'_> {
+    /// Package path of a stored image, copying it in on first use
+    fn add(&mut self, image_path: &str) -> AppResult<String> {
+        if let Some(path) = self.paths.get(image_path) {
+            return Ok(path.clone());
+        }
+
+        let data = (self.load_image)(image_path)?;
+        let name = image_path.rsplit('/').next().unwrap_or(image_path);
+        let mut path = format!("items/media/{}", name);
+        let mut counter = 1;
+        while self.files.contains_key(&path) {
+            path = format!("items/media/{}_{}", counter, name);
+            counter += 1;
+        }
+
+        self.files.insert(path.clone(), data);
+        self.paths.insert(image_path.to_string(), path.clone());
+        Ok(path)
+    }
+}
+
+/// How a response adds to the item's SCORE
+enum Scoring {
+    /// Full weight when the response equals the correct response
+    Match,
+    /// Full weight when the response is one of the mapped answers, ignoring case
+    AnyMapped,
+    /// Full weight when the response is within an absolute tolerance of the correct one
+    Tolerance(f64),
+    /// The mapped value of the response, scaled by the weight
+    Mapped,
+}
+
+struct Response {
+    cardinality: &'static str,
+    base_type: &'static str,
+    correct: Vec<String>,
+    /// Map entries and whether they are case sensitive
+    mapping: Vec<(String, f64, bool)>,
+    scoring: Scoring,
+}
+
+struct ItemWriter<'a, 'm> {
+    notes: Vec<String>,
+    /// Package paths of the media the item uses
+    files: Vec<String>,
+    media: &'a mut Media<'m>,
+    shuffle: bool,
+}
+
+impl ItemWriter<'_, '_> {
+    fn write(&mut self, question: &QuestionWithDetails) -> Result<String, String> {
+        let q = &question.question;
+        let mut responses = Vec::new();
+
+        let body = match q.question_type.as_str() {
+            "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION" => {
+                self.write_choice(question, &mut responses)?
+            }
+            "FILL_BLANK" | "FILL_BLANK_MULTIPLE" => {
+                if question.blanks.is_empty() {
+                    return Err("Question has no blanks".to_string());
+                }
+                self.write_blanks(question, &mut responses)
+            }
+            "NUMERIC_INPUT" | "CALCULATION" => {
+                let blank = question
+                    .blanks
+                    .first()
+                    .ok_or("Numeric question has no answer")?;
+                // A numeric item holds one entry below the question text
+                for dropped in &question.blanks[1..] {
+                    self.notes.push(format!(
+                        "Blank {} (answer {}) was dropped",
+                        dropped.blank_index + 1,
+                        dropped.correct_answer
+                    ));
+                }
+                let mut body = self.question_text(question);
+                body.push_str(&format!(
+                    "    <p>{}{}</p>\n",
+                    text_entry(blank, "RESPONSE", &mut responses),
+                    blank
+                        .unit
+                        .as_deref()
+                        .filter(|u| !u.trim().is_empty())
+                        .map(|u| format!(" {}", escape_xml(u.trim())))
+                        .unwrap_or_default()
+                ));
+                body
+            }
+            "ORDERING" => self.write_order(question, &mut responses)?,
+            "MATCHING" => self.write_match(question, &mut responses)?,
+            other => return Err(format!("Unknown question type: {}", other)),
+        };
+
+        let title = q
+            .source
+            .clone()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| default_name(&q.question_text));
+        let explanation = q
+            .explanation
+            .as_deref()
+            .map(str::trim)
+            .filter(|e| !e.is_empty());
+
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<assessmentItem xmlns=\"{QTI_NAMESPACE}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"{QTI_NAMESPACE} {QTI_SCHEMA}\" identifier=\"{}\" title=\"{}\" adaptive=\"false\" timeDependent=\"false\">\n",
+            item_identifier(q.id),
+            escape_xml(&title)
+        );
+        for (identifier, response) in &responses {
+            xml.push_str(&response_declaration(identifier, response));
+        }
+        xml.push_str(&format!(
+            "  <outcomeDeclaration identifier=\"SCORE\" cardinality=\"single\" baseType=\"float\" normalMaximum=\"{points}\">\n    <defaultValue>\n      <value>0</value>\n    </defaultValue>\n  </outcomeDeclaration>\n  <outcomeDeclaration identifier=\"MAXSCORE\" cardinality=\"single\" baseType=\"float\">\n    <defaultValue>\n      <value>{points}</value>\n    </defaultValue>\n  </outcomeDeclaration>\n",
+            points = q.points
+        ));
+        if explanation.is_some() {
+            xml.push_str("  <outcomeDeclaration identifier=\"FEEDBACK\" cardinality=\"single\" baseType=\"identifier\"/>\n");
+        }
+        xml.push_str("  <itemBody>\n");
+        xml.push_str(&body);
+        xml.push_str("  </itemBody>\n");
+        xml.push_str(&response_processing(
+            &responses,
+            q.points,
+            explanation.is_some(),
+        ));
+        if let Some(explanation) = explanation {
+            xml.push_str(&format!(
+                "  <modalFeedback outcomeIdentifier=\"FEEDBACK\" identifier=\"EXPLANATION\" showHide=\"show\">{}</modalFeedback>\n",
+                xhtml(explanation)
+            ));
+        }
+        xml.push_str("</assessmentItem>\n");
+        Ok(xml)
+    }
+
+    fn write_choice(
+        &mut self,
+        question: &QuestionWithDetails,
+        responses: &mut Vec<(String, Response)>,
+    ) -> Result<String, String> {
+        let correct: Vec<String> = question
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.is_correct)
+            .map(|(index, _)| format!("choice{}", index + 1))
+            .collect();
+        if correct.is_empty() {
+            return Err("Question has no correct option".to_string());
+        }
+
+        let single = question.question.question_type != "MULTIPLE_CHOICE";
+        let mut body = self.question_text(question);
+        body.push_str(&format!(
+            "    <choiceInteraction responseIdentifier=\"RESPONSE\" shuffle=\"{}\" maxChoices=\"{}\">\n",
+            self.shuffle,
+            if single { 1 } else { 0 }
+        ));
+        for (index, option) in question.options.iter().enumerate() {
+            body.push_str(&format!(
+                "      <simpleChoice identifier=\"choice{}\">{}{}</simpleChoice>\n",
+                index + 1,
+                xhtml(&option.option_text),
+                self.image(option.option_image_path.as_deref())
+            ));
+        }
+        body.push_str("    </choiceInteraction>\n");
+
+        responses.push((
+            "RESPONSE".to_string(),
+            Response {
+                cardinality: if single { "single" } else { "multiple" },
+                base_type: "identifier",
+                correct,
+                mapping: Vec::new(),
+                scoring: Scoring::Match,
+            },
+        ));
+        Ok(body)
+    }
+
+    /// Each blank becomes an interaction in place of its marker; blanks the text has
+    /// no marker for are appended
+    fn write_blanks(
+        &mut self,
+        question: &QuestionWithDetails,
+        responses: &mut Vec<(String, Response)>,
+    ) -> String {
+        let q = &question.question;
+        let mut paragraph = String::new();
+        let mut rest = q.question_text.as_str();
+
+        for (index, blank) in question.blanks.iter().enumerate() {
+            let identifier = format!("RESPONSE{}", index + 1);
+            let interaction = if blank.input_type == "DROPDOWN" {
+                inline_choice(blank, &identifier, index + 1, responses)
+            } else {
+                text_entry(blank, &identifier, responses)
+            };
+            match find_blank(rest) {
+                Some((position, marker)) => {
+                    paragraph.push_str(&xhtml(&rest[..position]));
+                    rest = &rest[position + marker.len()..];
+                }
+                None => {
+                    paragraph.push_str(&xhtml(rest));
+                    paragraph.push(' ');
+                    rest = "";
+                }
+            }
+            paragraph.push_str(&interaction);
+        }
+        paragraph.push_str(&xhtml(rest));
+
+        format!(
+            "    <p>{}</p>\n{}",
+            paragraph,
+            self.image_block(q.question_image_path.as_deref())
+        )
+    }
+
+    fn write_order(
+        &mut self,
+        question: &QuestionWithDetails,
+        responses: &mut Vec<(String, Response)>,
+    ) -> Result<String, String> {
+        if question.order_items.is_empty() {
+            return Err("Ordering question has no items".to_string());
+        }
+
+        let mut items: Vec<_> = question.order_items.iter().enumerate().collect();
+        let mut body = self.question_text(question);
+        body.push_str("    <orderInteraction responseIdentifier=\"RESPONSE\" shuffle=\"true\">\n");
+        for (index, item) in &items {
+            body.push_str(&format!(
+                "      <simpleChoice identifier=\"item{}\">{}</simpleChoice>\n",
+                index + 1,
+                xhtml(&item.item_text)
+            ));
+        }
+        body.push_str("    </orderInteraction>\n");
+
+        items.sort_by_key(|(_, item)| item.correct_position);
+        responses.push((
+            "RESPONSE".to_string(),
+            Response {
+                cardinality: "ordered",
+                base_type: "identifier",
+                correct: items
+                    .iter()
+                    .map(|(index, _)| format!("item{}", index + 1))
+                    .collect(),
+                mapping: Vec::new(),
+                scoring: Scoring::Match,
+            },
+        ));
+        Ok(body)
+    }
+
+    /// Partial credit per pair, as the app grades matching
+    fn write_match(
+        &mut self,
+        question: &QuestionWithDetails,
+        responses: &mut Vec<(String, Response)>,
+    ) -> Result<String, String> {
+        let pairs = &question.matches;
+        if pairs.is_empty() {
+            return Err("Matching question has no pairs".to_string());
+        }
+
+        let mut body = self.question_text(question);
+        body.push_str(&format!(
+            "    <matchInteraction responseIdentifier=\"RESPONSE\" shuffle=\"true\" maxAssociations=\"{}\">\n      <simpleMatchSet>\n",
+            pairs.len()
+        ));
+        for (index, pair) in pairs.iter().enumerate() {
+            body.push_str(&format!(
+                "        <simpleAssociableChoice identifier=\"left{}\" matchMax=\"1\">{}{}</simpleAssociableChoice>\n",
+                index + 1,
+                xhtml(&pair.left_item),
+                self.image(pair.left_image_path.as_deref())
+            ));
+        }
+        body.push_str("      </simpleMatchSet>\n      <simpleMatchSet>\n");
+        for (index, pair) in pairs.iter().enumerate() {
+            body.push_str(&format!(
+                "        <simpleAssociableChoice identifier=\"right{}\" matchMax=\"1\">{}{}</simpleAssociableChoice>\n",
+                index + 1,
+                xhtml(&pair.right_item),
+                self.image(pair.right_image_path.as_deref())
+            ));
+        }
+        body.push_str("      </simpleMatchSet>\n    </matchInteraction>\n");
+
+        let correct: Vec<String> = (1..=pairs.len())
+            .map(|n| format!("left{} right{}", n, n))
+            .collect();
+        let weight = 1.0 / pairs.len() as f64;
+        responses.push((
+            "RESPONSE".to_string(),
+            Response {
+                cardinality: "multiple",
+                base_type: "directedPair",
+                mapping: correct
+                    .iter()
+                    .map(|pair| (pair.clone(), weight, true))
+                    .collect(),
+                correct,
+                scoring: Scoring::Mapped,
+            },
+        ));
+        Ok(body)
+    }
+
+    /// The question text as paragraphs, followed by the question image
+    fn question_text(&mut self, question: &QuestionWithDetails) -> String {
+        let q = &question.question;
+        let mut body: String = q
+            .question_text
+            .split("\n\n")
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| format!("    <p>{}</p>\n", xhtml(p.trim())))
+            .collect();
+        body.push_str(&self.image_block(q.question_image_path.as_deref()));
+        body
+    }
+
+    fn image_block(&mut self, image_path: Option<&str>) -> String {
+        let image = self.image(image_path);
+        if image.is_empty() {
+            image
+        } else {
+            format!("    <p>{}</p>\n", image)
+        }
+    }
+
+    /// An `<img>` for a stored image, copied into the package
+    fn image(&mut self, image_path: Option<&str>) -> String {
+        let Some(image_path) = image_path else {
+            return String::new();
+        };
+        match self.media.add(image_path) {
+            Ok(path) => {
+                if !self.files.contains(&path) {
+                    self.files.push(path.clone());
+                }
+                let src = path.strip_prefix("items/").unwrap_or(&path);
+                format!("<img src=\"{}\" alt=\"\"/>", escape_xml(src))
+            }
+            Err(e) => {
+                self.notes
+                    .push(format!("Image {} was not exported: {}", image_path, e));
+                String::new()
+            }
+        }
+    }
+}
+
+fn text_entry(
+    blank: &QuestionBlank,
+    identifier: &str,
+    responses: &mut Vec<(String, Response)>,
+) -> String {
+    let number = blank
+        .is_numeric
+        .then(|| blank.correct_answer.trim().parse::<f64>().ok())
+        .flatten();
+
+    let response = match number {
+        Some(number) => Response {
+            cardinality: "single",
+            base_type: "float",
+            correct: vec![format_number(number)],
+            mapping: Vec::new(),
+            scoring: Scoring::Tolerance(blank.numeric_tolerance.unwrap_or(0.0).abs()),
+        },
+        None => {
+            let answers: Vec<String> = std::iter::once(blank.correct_answer.trim())
+                .chain(split_list(blank.acceptable_answers.as_deref()))
+                .map(str::to_string)
+                .collect();
+            Response {
+                cardinality: "single",
+                base_type: "string",
+                correct: vec![answers[0].clone()],
+                mapping: answers
+                    .into_iter()
+                    .map(|answer| (answer, 1.0, false))
+                    .collect(),
+                scoring: Scoring::AnyMapped,
+            }
+        }
+    };
+    responses.push((identifier.to_string(), response));
+
+    format!(
+        "<textEntryInteraction responseIdentifier=\"{}\"/>",
+        identifier
+    )
+}
+
+fn inline_choice(
+    blank: &QuestionBlank,
+    identifier: &str,
+    number: usize,
+    responses: &mut Vec<(String, Response)>,
+) -> String {
+    let correct = blank.correct_answer.trim();
+    let mut options: Vec<&str> = split_list(blank.dropdown_options.as_deref()).collect();
+    if !options.contains(&correct) {
+        options.insert(0, correct);
+    }
+
+    let mut xml = format!(
+        "<inlineChoiceInteraction responseIdentifier=\"{}\" shuffle=\"false\">",
+        identifier
+    );
+    for (index, option) in options.iter().enumerate() {
+        xml.push_str(&format!(
+            "<inlineChoice identifier=\"blank{}_{}\">{}</inlineChoice>",
+            number,
+            index + 1,
+            escape_xml(option)
+        ));
+    }
+    xml.push_str("</inlineChoiceInteraction>");
+
+    let position = options.iter().position(|o| *o == correct).unwrap_or(0);
+    responses.push((
+        identifier.to_string(),
+        Response {
+            cardinality: "single",
+            base_type: "identifier",
+            correct: vec![format!("blank{}_{}", number, position + 1)],
+            mapping: Vec::new(),
+            scoring: Scoring::Match,
+        },
+    ));
+    xml
+}
+
+fn response_declaration(identifier: &str, response: &Response) -> String {
+    let mut xml = format!(
+        "  <responseDeclaration identifier=\"{}\" cardinality=\"{}\" baseType=\"{}\">\n    <correctResponse>\n",
+        identifier, response.cardinality, response.base_type
+    );
+    for value in &response.correct {
+        xml.push_str(&format!("      <value>{}</value>\n", escape_xml(value)));
+    }
+    xml.push_str("    </correctResponse>\n");
+
+    if !response.mapping.is_empty() {
+        xml.push_str("    <mapping defaultValue=\"0\">\n");
+        for (key, value, case_sensitive) in &response.mapping {
+            xml.push_str(&format!(
+                "      <mapEntry mapKey=\"{}\" mappedValue=\"{}\" caseSensitive=\"{}\"/>\n",
+                escape_xml(key),
+                format_number(*value),
+                case_sensitive
+            ));
+        }
+        xml.push_str("    </mapping>\n");
+    }
+
+    xml.push_str("  </responseDeclaration>\n");
+    xml
+}
+
+/// Every response carries an equal share of the points, like the app's partial
+/// credit per blank
+fn response_processing(
+    responses: &[(String, Response)],
+    points: i32,
+    has_feedback: bool,
+) -> String {
+    let weight = format_number(points as f64 / responses.len().max(1) as f64);
+    let add_to_score = |value: String| {
+        format!(
+            "<setOutcomeValue identifier=\"SCORE\"><sum><variable identifier=\"SCORE\"/>{}</sum></setOutcomeValue>",
+            value
+        )
+    };
+    let weight_value = format!("<baseValue baseType=\"float\">{}</baseValue>", weight);
+
+    let mut xml = String::from("  <responseProcessing>\n");
+    for (identifier, response) in responses {
+        let condition = match response.scoring {
+            Scoring::Match => format!(
+                "<match><variable identifier=\"{id}\"/><correct identifier=\"{id}\"/></match>",
+                id = identifier
+            ),
+            Scoring::AnyMapped => format!(
+                "<gt><mapResponse identifier=\"{}\"/><baseValue baseType=\"float\">0</baseValue></gt>",
+                identifier
+            ),
+            Scoring::Tolerance(tolerance) => format!(
+                "<equal toleranceMode=\"absolute\" tolerance=\"{tolerance}\"><variable identifier=\"{id}\"/><correct identifier=\"{id}\"/></equal>",
+                tolerance = format_number(tolerance),
+                id = identifier
+            ),
+            Scoring::Mapped => {
+                xml.push_str(&format!(
+                    "    {}\n",
+                    add_to_score(format!(
+                        "<product><mapResponse identifier=\"{}\"/>{}</product>",
+                        identifier, weight_value
+                    ))
+                ));
+                continue;
+            }
+        };
+        xml.push_str(&format!(
+            "    <responseCondition>\n      <responseIf>\n        {}\n        {}\n      </responseIf>\n    </responseCondition>\n",
+            condition,
+            add_to_score(weight_value.clone())
+        ));
+    }
+    if has_feedback {
+        xml.push_str("    <setOutcomeValue identifier=\"FEEDBACK\"><baseValue baseType=\"identifier\">EXPLANATION</baseValue></setOutcomeValue>\n");
+    }
+    xml.push_str("  </responseProcessing>\n");
+    xml
+}
+
+/// Plain text as XHTML content, keeping line breaks
+fn xhtml(text: &str) -> String {
+    escape_xml(text).replace('\n', "<br/>")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::formats::fixtures::{dropdown_question, parsed_content, question, stored_content};
+
+    fn test_of(questions: Vec<QuestionWithDetails>) -> ExportTest {
+        ExportTest {
+            title: "Science".to_string(),
+            time_limit_minutes: None,
+            shuffle_questions: false,
+            shuffle_choices: false,
+            sections: vec![ExportSection {
+                title: "Section".to_string(),
+                select: None,
+                questions,
+            }],
+        }
+    }
+
+    fn write(test: &ExportTest) -> (Vec<u8>, ExportReport) {
+        let mut package = Cursor::new(Vec::new());
+        let report =
+            write_package(&mut package, test, &mut |path| Ok(path.as_bytes().to_vec())).unwrap();
+        (package.into_inner(), report)
+    }
+
+    fn read(package: Vec<u8>) -> AppResult<(Vec<ParsedQuestion>, Vec<ImportIssue>)> {
+        read_package(Cursor::new(package), &mut |name, data| {
+            Ok(format!("images/{}:{}", name, String::from_utf8_lossy(data)))
+        })
+    }
+
+    /// Write `question` and read it back, expecting exactly one question and no issues
+    fn round_trip(question: QuestionWithDetails) -> CreateQuestionData {
+        let (package, report) = write(&test_of(vec![question]));
+        assert_eq!(report.exported, 1);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+        let (mut parsed, issues) = read(package).unwrap();
+        assert!(issues.is_empty(), "{:?}", issues);
+        assert_eq!(parsed.len(), 1);
+        parsed.remove(0).data
+    }
+
+    /// A package holding only `imsmanifest.xml` and the item `items/q1.xml`
+    fn package_with_item(item: &str) -> Vec<u8> {
+        let manifest = "<manifest>\n  <resources>\n    <resource type=\"imsqti_item_xmlv2p1\" href=\"items/q1.xml\"/>\n  </resources>\n</manifest>";
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        add_file(&mut zip, "imsmanifest.xml", manifest.as_bytes()).unwrap();
+        add_file(&mut zip, "items/q1.xml", item.as_bytes()).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn supported_types_round_trip() {
+        for question_type in [
+            "SINGLE_CHOICE",
+            "MULTIPLE_CHOICE",
+            "FILL_BLANK",
+            "FILL_BLANK_MULTIPLE",
+            "NUMERIC_INPUT",
+            "ORDERING",
+            "MATCHING",
+        ] {
+            let original = question(question_type);
+            let parsed = round_trip(original.clone());
+            assert_eq!(
+                parsed_content(&parsed),
+                stored_content(&original),
+                "{}",
+                question_type
+            );
+        }
+
+        let original = dropdown_question();
+        assert_eq!(
+            parsed_content(&round_trip(original.clone())),
+            stored_content(&original)
+        );
+    }
+
+    #[test]
+    fn image_identification_comes_back_as_single_choice() {
+        let original = question("IMAGE_IDENTIFICATION");
+        let mut expected = stored_content(&original);
+        expected.question_type = "SINGLE_CHOICE".to_string();
+
+        assert_eq!(parsed_content(&round_trip(original)), expected);
+    }
+
+    #[test]
+    fn images_are_packaged_once_and_read_back() {
+        let mut original = question("MATCHING");
+        original.question.question_image_path = Some("images/map.png".to_string());
+        original.matches[0].left_image_path = Some("images/map.png".to_string());
+        original.matches[1].right_image_path = Some("other/map.png".to_string());
+
+        let (package, report) = write(&test_of(vec![original]));
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        let archive = ZipArchive::new(Cursor::new(package.clone())).unwrap();
+        let media: Vec<_> = archive
+            .file_names()
+            .filter(|name| name.starts_with("items/media/"))
+            .collect();
+        assert_eq!(media.len(), 2, "{:?}", media);
+
+        let (parsed, _) = read(package).unwrap();
+        let data = &parsed[0].data;
+        assert_eq!(
+            data.question_image_path.as_deref(),
+            Some("images/map.png:images/map.png")
+        );
+        let pairs = data.match_pairs.as_ref().unwrap();
+        assert_eq!(
+            pairs[0].left_image_path.as_deref(),
+            Some("images/map.png:images/map.png")
+        );
+        assert_eq!(
+            pairs[1].right_image_path.as_deref(),
+            Some("images/1_map.png:other/map.png")
+        );
+    }
+
+    #[test]
+    fn calculation_keeps_only_its_first_blank() {
+        let (package, report) = write(&test_of(vec![question("CALCULATION")]));
+        assert_eq!(report.exported, 1);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].message, "Blank 2 (answer 5) was dropped");
+
+        let (parsed, _) = read(package).unwrap();
+        let numeric = parsed[0].data.numeric_data.as_ref().unwrap();
+        assert_eq!(
+            (numeric.correct_answer.as_str(), numeric.tolerance.as_str()),
+            ("12", "0")
+        );
+    }
+
+    #[test]
+    fn a_question_in_several_sections_is_written_once() {
+        let mut test = test_of(vec![question("SINGLE_CHOICE")]);
+        test.sections.push(ExportSection {
+            title: "Again".to_string(),
+            select: Some(5),
+            questions: vec![question("SINGLE_CHOICE")],
+        });
+
+        let (package, report) = write(&test);
+        assert_eq!(report.exported, 1);
+        let mut archive = ZipArchive::new(Cursor::new(package)).unwrap();
+        let test_xml =
+            String::from_utf8(read_entry(&mut archive, "test.xml", MAX_XML_BYTES).unwrap())
+                .unwrap();
+        assert_eq!(test_xml.matches("<assessmentItemRef ").count(), 2);
+        assert!(
+            test_xml.contains("<selection select=\"1\"/>"),
+            "{}",
+            test_xml
+        );
+    }
+
+    #[test]
+    fn dropdown_choices_with_commas_are_skipped() {
+        let item = r#"<assessmentItem>
+  <responseDeclaration identifier="R" cardinality="single" baseType="identifier">
+    <correctResponse><value>a</value></correctResponse>
+  </responseDeclaration>
+  <itemBody>
+    <p>Pick <inlineChoiceInteraction responseIdentifier="R">
+      <inlineChoice identifier="a">yes, really</inlineChoice>
+      <inlineChoice identifier="b">no</inlineChoice>
+    </inlineChoiceInteraction></p>
+  </itemBody>
+</assessmentItem>"#;
+        let (parsed, issues) = read(package_with_item(item)).unwrap();
+
+        assert!(parsed.is_empty());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 3);
+        assert!(issues[0].skipped);
+        assert_eq!(
+            issues[0].message,
+            "items/q1.xml: Dropdown choices containing commas are not supported"
+        );
+    }
+
+    #[test]
+    fn unsupported_interactions_are_skipped() {
+        let item = "<assessmentItem><itemBody><p>Discuss.</p><extendedTextInteraction responseIdentifier=\"R\"/></itemBody></assessmentItem>";
+        let (parsed, issues) = read(package_with_item(item)).unwrap();
+
+        assert!(parsed.is_empty());
+        assert_eq!(
+            issues[0].message,
+            "items/q1.xml: QTI interaction extendedTextInteraction is not supported"
+        );
+    }
+
+    #[test]
+    fn packages_without_items_are_rejected() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        add_file(&mut zip, "imsmanifest.xml", b"<manifest/>").unwrap();
+        let package = zip.finish().unwrap().into_inner();
+
+        let Err(error) = read(package) else {
+            panic!("read a package without items");
+        };
+        assert_eq!(error.to_string(), "The package contains no QTI items");
+    }
+}
@@ -0,0 +1,597 @@
+use std::collections::{BTreeMap, HashSet};
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::questions::{fetch_question_with_details, QuestionBlank, QuestionWithDetails};
+use crate::error::{AppError, AppResult};
+
+/// Tolerance used for numeric blanks that have none stored
+/// (same default `create_question` applies to NUMERIC_INPUT data)
+const DEFAULT_NUMERIC_TOLERANCE: f64 = 0.1;
+
+/// A learner's answer to a single question, in the shape the quiz and exam pages send it.
+///
+/// - SINGLE_CHOICE / IMAGE_IDENTIFICATION: the selected option id (`"12"`)
+/// - MULTIPLE_CHOICE: the selected option ids (`["12", "14"]`)
+/// - FILL_BLANK / FILL_BLANK_MULTIPLE / CALCULATION: one value per blank, in blank order
+/// - NUMERIC_INPUT: the typed value, optionally followed by the unit (`"9.81 m/s"`)
+/// - ORDERING: order item ids in the order the learner placed them
+/// - MATCHING: left match id -> chosen right match id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ResponseData {
+    Text(String),
+    Number(f64),
+    Ids(Vec<i64>),
+    Texts(Vec<Option<String>>),
+    Pairs(BTreeMap<String, i64>),
+}
+
+impl ResponseData {
+    /// The response as a list of raw values (blank answers, option ids, ...)
+    fn texts(&self) -> Vec<String> {
+        match self {
+            ResponseData::Text(text) => vec![text.clone()],
+            ResponseData::Number(number) => vec![number.to_string()],
+            ResponseData::Ids(ids) => ids.iter().map(|id| id.to_string()).collect(),
+            ResponseData::Texts(texts) => texts
+                .iter()
+                .map(|text| text.clone().unwrap_or_default())
+                .collect(),
+            ResponseData::Pairs(_) => Vec::new(),
+        }
+    }
+
+    /// The response as a list of ids (selected options or ordered items)
+    pub fn ids(&self) -> Vec<i64> {
+        match self {
+            ResponseData::Ids(ids) => ids.clone(),
+            ResponseData::Number(number) => vec![*number as i64],
+            _ => self
+                .texts()
+                .iter()
+                .filter_map(|text| text.trim().parse().ok())
+                .collect(),
+        }
+    }
+
+    /// The response as left match id -> right match id pairs
+    fn pairs(&self) -> Vec<(i64, i64)> {
+        match self {
+            ResponseData::Pairs(pairs) => pairs
+                .iter()
+                .filter_map(|(left, right)| left.trim().parse().ok().map(|left| (left, *right)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GradeResult {
+    pub is_correct: bool,
+    pub points_earned: f64,
+}
+
+/// One question of a submitted attempt; `answer` is `None` when it was skipped
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmittedResponse {
+    pub question_id: i64,
+    pub answer: Option<ResponseData>,
+    pub time_spent_seconds: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GradedResponse {
+    pub question_id: i64,
+    pub answer: Option<ResponseData>,
+    pub is_correct: bool,
+    pub points_earned: f64,
+    pub max_points: i32,
+    pub time_spent_seconds: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GradedSubmission {
+    pub score: f64,
+    pub max_score: i32,
+    pub percentage: f64,
+    pub responses: Vec<GradedResponse>,
+}
+
+/// Returned by the attempt-save commands so the results page shows the stored score
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttemptResult {
+    pub attempt_id: i64,
+    pub score: f64,
+    pub max_score: i32,
+    pub percentage: f64,
+    pub time_taken_seconds: i32,
+    pub passed: bool,
+    pub responses: Vec<GradedResponse>,
+}
+
+/// A stored response together with the question as it was answered
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseDetail {
+    pub question: QuestionWithDetails,
+    /// Revision of the question the answer was given against; `None` for answers
+    /// saved before revisions were tracked
+    pub revision: Option<i64>,
+    pub answer: Option<ResponseData>,
+    pub is_correct: bool,
+    pub points_earned: f64,
+    pub time_spent_seconds: Option<i32>,
+}
+
+/// Grade a single question. A missing response earns no points.
+pub fn grade_question(
+    question: &QuestionWithDetails,
+    response: Option<&ResponseData>,
+) -> AppResult<GradeResult> {
+    let fraction = match question.question.question_type.as_str() {
+        "SINGLE_CHOICE" | "MULTIPLE_CHOICE" | "IMAGE_IDENTIFICATION" => {
+            response.map_or(0.0, |r| grade_choice(question, r))
+        }
+        "FILL_BLANK" | "FILL_BLANK_MULTIPLE" | "NUMERIC_INPUT" | "CALCULATION" => {
+            response.map_or(0.0, |r| grade_blanks(question, r))
+        }
+        "ORDERING" => response.map_or(0.0, |r| grade_ordering(question, r)),
+        "MATCHING" => response.map_or(0.0, |r| grade_matching(question, r)),
+        other => {
+            return Err(AppError::database(format!(
+                "Unknown question type: {}",
+                other
+            )))
+        }
+    };
+
+    let points = question.question.points as f64;
+    Ok(GradeResult {
+        is_correct: fraction >= 1.0,
+        points_earned: round_points(fraction * points),
+    })
+}
+
+/// Grade a submission against the questions that were served for an attempt, in
+/// served order. Responses to questions that were not served are rejected, and served
+/// questions without a response count as unanswered, so leaving out a question can
+/// never raise the score. `attempt` names the attempt in error messages.
+pub fn grade_served(
+    conn: &Connection,
+    attempt: &str,
+    served: &[i64],
+    mut responses: Vec<SubmittedResponse>,
+) -> AppResult<GradedSubmission> {
+    if let Some(extra) = responses.iter().find(|r| !served.contains(&r.question_id)) {
+        return Err(AppError::validation(
+            format!(
+                "Question {} was not served in {}",
+                extra.question_id, attempt
+            ),
+            &["responses"],
+        ));
+    }
+
+    let mut ordered = Vec::with_capacity(served.len());
+    for &question_id in served {
+        match responses.iter().position(|r| r.question_id == question_id) {
+            Some(index) => ordered.push(responses.swap_remove(index)),
+            None => ordered.push(SubmittedResponse {
+                question_id,
+                answer: None,
+                time_spent_seconds: None,
+            }),
+        }
+    }
+    if let Some(duplicate) = responses.first() {
+        return Err(AppError::validation(
+            format!(
+                "Question {} was submitted more than once",
+                duplicate.question_id
+            ),
+            &["responses"],
+        ));
+    }
+
+    grade_submission(conn, &ordered)
+}
+
+/// Grade every response of a submission against the stored questions
+fn grade_submission(
+    conn: &Connection,
+    responses: &[SubmittedResponse],
+) -> AppResult<GradedSubmission> {
+    let mut seen = HashSet::new();
+    let mut graded = Vec::with_capacity(responses.len());
+    let mut score = 0.0;
+    let mut max_score = 0;
+
+    for response in responses {
+        if !seen.insert(response.question_id) {
+            return Err(AppError::validation(
+                format!(
+                    "Question {} was submitted more than once",
+                    response.question_id
+                ),
+                &["responses"],
+            ));
+        }
+
+        let question = fetch_question_with_details(conn, response.question_id)?;
+        let result = grade_question(&question, response.answer.as_ref())?;
+
+        score += result.points_earned;
+        max_score += question.question.points;
+        graded.push(GradedResponse {
+            question_id: response.question_id,
+            answer: response.answer.clone(),
+            is_correct: result.is_correct,
+            points_earned: result.points_earned,
+            max_points: question.question.points,
+            time_spent_seconds: response.time_spent_seconds,
+        });
+    }
+
+    let score = round_points(score);
+    let percentage = if max_score > 0 {
+        score / max_score as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    Ok(GradedSubmission {
+        score,
+        max_score,
+        percentage,
+        responses: graded,
+    })
+}
+
+/// All or nothing: the selected options must be exactly the correct ones
+fn grade_choice(question: &QuestionWithDetails, response: &ResponseData) -> f64 {
+    let correct: HashSet<i64> = question
+        .options
+        .iter()
+        .filter(|option| option.is_correct)
+        .map(|option| option.id)
+        .collect();
+    let selected: HashSet<i64> = response.ids().into_iter().collect();
+
+    if !correct.is_empty() && selected == correct {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Partial credit per blank; answers are matched to blanks by position
+fn grade_blanks(question: &QuestionWithDetails, response: &ResponseData) -> f64 {
+    if question.blanks.is_empty() {
+        return 0.0;
+    }
+
+    let answers = response.texts();
+    let correct = question
+        .blanks
+        .iter()
+        .enumerate()
+        .filter(|(index, blank)| {
+            answers
+                .get(*index)
+                .is_some_and(|answer| blank_accepts(blank, answer))
+        })
+        .count();
+
+    correct as f64 / question.blanks.len() as f64
+}
+
+fn blank_accepts(blank: &QuestionBlank, answer: &str) -> bool {
+    let answer = normalize_text(answer);
+    if answer.is_empty() {
+        return false;
+    }
+
+    if answer == normalize_text(&blank.correct_answer) {
+        return true;
+    }
+
+    let acceptable = blank.acceptable_answers.as_deref().unwrap_or("");
+    if acceptable
+        .split(',')
+        .map(normalize_text)
+        .any(|candidate| !candidate.is_empty() && candidate == answer)
+    {
+        return true;
+    }
+
+    if blank.is_numeric {
+        let unit = blank.unit.as_deref();
+        if let (Some(given), Some(expected)) = (
+            parse_numeric(&answer, unit),
+            parse_numeric(&blank.correct_answer, unit),
+        ) {
+            let tolerance = blank
+                .numeric_tolerance
+                .unwrap_or(DEFAULT_NUMERIC_TOLERANCE)
+                .abs();
+            // Leave room for floating point noise so a tolerance of 0 still accepts exact answers
+            let epsilon = f64::EPSILON * expected.abs().max(1.0) * 4.0;
+            return (given - expected).abs() <= tolerance + epsilon;
+        }
+    }
+
+    false
+}
+
+/// Partial credit for each item placed at its `correct_position` (1-based)
+fn grade_ordering(question: &QuestionWithDetails, response: &ResponseData) -> f64 {
+    let items = &question.order_items;
+    let order = response.ids();
+    if items.is_empty() || order.len() != items.len() {
+        return 0.0;
+    }
+
+    let correct = order
+        .iter()
+        .enumerate()
+        .filter(|(index, item_id)| {
+            items
+                .iter()
+                .find(|item| item.id == **item_id)
+                .is_some_and(|item| item.correct_position == *index as i32 + 1)
+        })
+        .count();
+
+    correct as f64 / items.len() as f64
+}
+
+/// Partial credit per pair. Right items are identified by the id of the match row
+/// they belong to, so rows with identical right text are interchangeable.
+fn grade_matching(question: &QuestionWithDetails, response: &ResponseData) -> f64 {
+    let matches = &question.matches;
+    if matches.is_empty() {
+        return 0.0;
+    }
+
+    let chosen: BTreeMap<i64, i64> = response.pairs().into_iter().collect();
+    let correct = matches
+        .iter()
+        .filter(|pair| {
+            chosen.get(&pair.id).is_some_and(|right_id| {
+                *right_id == pair.id
+                    || matches.iter().any(|other| {
+                        other.id == *right_id
+                            && normalize_text(&other.right_item) == normalize_text(&pair.right_item)
+                    })
+            })
+        })
+        .count();
+
+    correct as f64 / matches.len() as f64
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Parse a numeric answer, ignoring a trailing unit when the blank has one
+fn parse_numeric(text: &str, unit: Option<&str>) -> Option<f64> {
+    let mut value = text.trim();
+    if let Some(unit) = unit.map(str::trim).filter(|u| !u.is_empty()) {
+        if value.len() >= unit.len()
+            && value.is_char_boundary(value.len() - unit.len())
+            && value[value.len() - unit.len()..].eq_ignore_ascii_case(unit)
+        {
+            value = value[..value.len() - unit.len()].trim_end();
+        }
+    }
+    value.parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+fn round_points(points: f64) -> f64 {
+    (points * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// A question of `question_type` worth `points`, with the option, blank, order item
+    /// and match rows given in `details`
+    fn question(question_type: &str, points: i32, details: Value) -> QuestionWithDetails {
+        let mut value = json!({
+            "id": 1, "subjectId": 1, "topicId": 1, "questionType": question_type,
+            "questionText": "Q", "difficulty": "EASY", "points": points,
+            "createdAt": "", "updatedAt": "",
+            "options": [], "blanks": [], "orderItems": [], "matches": [],
+        });
+        for (key, rows) in details.as_object().unwrap() {
+            value[key] = rows.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn options(correct: &[i64]) -> Value {
+        (1..=4)
+            .map(|id| {
+                json!({
+                    "id": id, "questionId": 1, "optionText": format!("Option {}", id),
+                    "isCorrect": correct.contains(&id), "displayOrder": id,
+                })
+            })
+            .collect()
+    }
+
+    fn blank(index: i32, answer: &str, extra: Value) -> Value {
+        let mut value = json!({
+            "id": index + 1, "questionId": 1, "blankIndex": index, "correctAnswer": answer,
+            "isNumeric": false, "inputType": "INPUT",
+        });
+        for (key, field) in extra.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        value
+    }
+
+    fn grade(question: &QuestionWithDetails, response: Value) -> GradeResult {
+        let response: Option<ResponseData> = serde_json::from_value(response).unwrap();
+        grade_question(question, response.as_ref()).unwrap()
+    }
+
+    fn assert_grade(result: GradeResult, is_correct: bool, points_earned: f64) {
+        assert_eq!(result.is_correct, is_correct, "{:?}", result);
+        assert_eq!(result.points_earned, points_earned, "{:?}", result);
+    }
+
+    #[test]
+    fn single_choice_needs_the_correct_option() {
+        let q = question("SINGLE_CHOICE", 2, json!({ "options": options(&[3]) }));
+
+        assert_grade(grade(&q, json!("3")), true, 2.0);
+        assert_grade(grade(&q, json!(3)), true, 2.0);
+        assert_grade(grade(&q, json!("1")), false, 0.0);
+        assert_grade(grade(&q, json!(null)), false, 0.0);
+    }
+
+    #[test]
+    fn multiple_choice_is_all_or_nothing() {
+        let q = question("MULTIPLE_CHOICE", 3, json!({ "options": options(&[1, 4]) }));
+
+        assert_grade(grade(&q, json!(["4", "1"])), true, 3.0);
+        assert_grade(grade(&q, json!([1, 4])), true, 3.0);
+        assert_grade(grade(&q, json!(["1"])), false, 0.0);
+        assert_grade(grade(&q, json!(["1", "2", "4"])), false, 0.0);
+    }
+
+    #[test]
+    fn image_identification_grades_like_single_choice() {
+        let q = question(
+            "IMAGE_IDENTIFICATION",
+            1,
+            json!({ "options": options(&[2]) }),
+        );
+
+        assert_grade(grade(&q, json!("2")), true, 1.0);
+        assert_grade(grade(&q, json!("4")), false, 0.0);
+    }
+
+    #[test]
+    fn fill_blank_ignores_case_and_spacing_and_takes_acceptable_answers() {
+        let q = question(
+            "FILL_BLANK",
+            1,
+            json!({ "blanks": [blank(0, "Mitochondria", json!({ "acceptableAnswers": "mitochondrion, powerhouse" }))] }),
+        );
+
+        assert_grade(grade(&q, json!(["  mitochondria "])), true, 1.0);
+        assert_grade(grade(&q, json!(["Powerhouse"])), true, 1.0);
+        assert_grade(grade(&q, json!(["nucleus"])), false, 0.0);
+        assert_grade(grade(&q, json!([""])), false, 0.0);
+    }
+
+    #[test]
+    fn fill_blank_multiple_gives_partial_credit_by_position() {
+        let q = question(
+            "FILL_BLANK_MULTIPLE",
+            3,
+            json!({ "blanks": [blank(0, "red", json!({})), blank(1, "green", json!({})), blank(2, "blue", json!({}))] }),
+        );
+
+        assert_grade(grade(&q, json!(["red", "green", "blue"])), true, 3.0);
+        assert_grade(grade(&q, json!(["red", null, "blue"])), false, 2.0);
+        assert_grade(grade(&q, json!(["green", "red"])), false, 0.0);
+        // One of three blanks on a one-point question rounds to cents
+        let q = question("FILL_BLANK_MULTIPLE", 1, json!({ "blanks": q.blanks }));
+        assert_grade(grade(&q, json!(["red"])), false, 0.33);
+    }
+
+    #[test]
+    fn numeric_input_accepts_values_within_tolerance_and_a_unit() {
+        let q = question(
+            "NUMERIC_INPUT",
+            2,
+            json!({ "blanks": [blank(0, "9.81", json!({ "isNumeric": true, "numericTolerance": 0.05, "unit": "m/s" }))] }),
+        );
+
+        assert_grade(grade(&q, json!("9.81 m/s")), true, 2.0);
+        assert_grade(grade(&q, json!("9.85")), true, 2.0);
+        assert_grade(grade(&q, json!(9.8)), true, 2.0);
+        assert_grade(grade(&q, json!("9.9 m/s")), false, 0.0);
+        assert_grade(grade(&q, json!("fast")), false, 0.0);
+    }
+
+    #[test]
+    fn calculation_grades_each_numeric_blank() {
+        let q = question(
+            "CALCULATION",
+            2,
+            json!({ "blanks": [
+                blank(0, "12", json!({ "isNumeric": true, "numericTolerance": 0.0 })),
+                blank(1, "3.5", json!({ "isNumeric": true })),
+            ] }),
+        );
+
+        assert_grade(grade(&q, json!(["12", "3.45"])), true, 2.0);
+        assert_grade(grade(&q, json!(["12.01", "3.5"])), false, 1.0);
+        assert_grade(grade(&q, json!(["12"])), false, 1.0);
+    }
+
+    #[test]
+    fn ordering_gives_credit_per_item_in_place() {
+        let items: Value = (1..=4)
+            .map(|id| json!({ "id": id * 10, "questionId": 1, "itemText": format!("Step {}", id), "correctPosition": id }))
+            .collect();
+        let q = question("ORDERING", 4, json!({ "orderItems": items }));
+
+        assert_grade(grade(&q, json!([10, 20, 30, 40])), true, 4.0);
+        assert_grade(grade(&q, json!(["10", "20", "40", "30"])), false, 2.0);
+        assert_grade(grade(&q, json!([10, 20, 30])), false, 0.0);
+    }
+
+    #[test]
+    fn matching_gives_credit_per_pair_and_treats_equal_right_items_alike() {
+        let pairs = json!([
+            { "id": 1, "questionId": 1, "leftItem": "H", "rightItem": "Hydrogen", "displayOrder": 0 },
+            { "id": 2, "questionId": 1, "leftItem": "He", "rightItem": "Noble gas", "displayOrder": 1 },
+            { "id": 3, "questionId": 1, "leftItem": "Ne", "rightItem": "noble  gas", "displayOrder": 2 },
+            { "id": 4, "questionId": 1, "leftItem": "O", "rightItem": "Oxygen", "displayOrder": 3 },
+        ]);
+        let q = question("MATCHING", 4, json!({ "matches": pairs }));
+
+        assert_grade(
+            grade(&q, json!({ "1": 1, "2": 2, "3": 3, "4": 4 })),
+            true,
+            4.0,
+        );
+        assert_grade(
+            grade(&q, json!({ "1": 1, "2": 3, "3": 2, "4": 4 })),
+            true,
+            4.0,
+        );
+        assert_grade(
+            grade(&q, json!({ "1": 4, "2": 2, "3": 3, "4": 1 })),
+            false,
+            2.0,
+        );
+        assert_grade(grade(&q, json!({ "1": 1 })), false, 1.0);
+    }
+
+    #[test]
+    fn unknown_question_types_are_an_error() {
+        let q = question("ESSAY", 1, json!({}));
+        assert!(grade_question(&q, None).is_err());
+    }
+}
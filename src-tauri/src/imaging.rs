@@ -0,0 +1,226 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageReader, Rgb, RgbImage};
+
+use crate::commands::images::ImageFormat;
+use crate::commands::settings::ImageSettings;
+use crate::error::{AppError, AppResult};
+
+/// JPEG quality of thumbnails; they are small enough that artefacts do not show
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// A decoded raster image and what the pipeline needs to know about the original
+struct Decoded {
+    image: DynamicImage,
+    /// EXIF, XMP or IPTC data was present (camera details, GPS position, ...)
+    has_metadata: bool,
+    /// The pixels were rotated or flipped to honour the EXIF orientation
+    reoriented: bool,
+}
+
+/// Apply `settings` to an image about to be stored: turn it upright, scale it down to
+/// `max_dimension`, drop its metadata and re-encode it to the chosen format.
+///
+/// WebP is written lossless. When that would make the image bigger than it was, it
+/// keeps its own format: untouched if nothing else needed doing, re-encoded otherwise.
+///
+/// Returns `None` when the original bytes can be kept: already small enough, without
+/// metadata and in the right format. That makes processing an image twice a no-op,
+/// rather than another round of lossy compression. GIF (which may be animated) and SVG
+/// are always kept as they are.
+pub fn process(
+    data: &[u8],
+    format: ImageFormat,
+    settings: &ImageSettings,
+) -> AppResult<Option<Vec<u8>>> {
+    if matches!(format, ImageFormat::Gif | ImageFormat::Svg) {
+        return Ok(None);
+    }
+    let decoded = decode(data)?;
+    let mut image = decoded.image;
+
+    let own_format = match format {
+        ImageFormat::Bmp => ImageFormat::Png,
+        format => format,
+    };
+    let target = match settings.format.as_str() {
+        "JPEG" if has_transparency(&image) => match format {
+            // JPEG cannot hold transparency; keep such images lossless
+            ImageFormat::Webp => ImageFormat::Webp,
+            _ => ImageFormat::Png,
+        },
+        "JPEG" => ImageFormat::Jpeg,
+        "WEBP" => ImageFormat::Webp,
+        _ => own_format,
+    };
+
+    let max = settings.max_dimension;
+    let too_large = max > 0 && image.width().max(image.height()) > max;
+    if !too_large && !decoded.has_metadata && !decoded.reoriented && target == format {
+        return Ok(None);
+    }
+
+    if too_large {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+    let mut encoded = encode(&image, target, settings.jpeg_quality)?;
+    let changed = too_large || decoded.has_metadata || decoded.reoriented;
+
+    // A format change alone is not worth a bigger file
+    if !changed && encoded.len() >= data.len() {
+        return Ok(None);
+    }
+    // Lossless WebP of a photo easily outgrows its JPEG, so an image that had to be
+    // re-encoded anyway stays in its own format when that comes out smaller
+    if changed && target == ImageFormat::Webp && own_format != ImageFormat::Webp {
+        let own = encode(&image, own_format, settings.jpeg_quality)?;
+        if own.len() < encoded.len() {
+            encoded = own;
+        }
+    }
+
+    Ok(Some(encoded))
+}
+
+/// A JPEG thumbnail whose longest side is `size`, or `None` for SVG, which scales by
+/// itself. Transparent areas are filled with white.
+pub fn thumbnail(data: &[u8], format: ImageFormat, size: u32) -> AppResult<Option<Vec<u8>>> {
+    if format == ImageFormat::Svg {
+        return Ok(None);
+    }
+    let image = decode(data)?.image;
+    let image = if image.width().max(image.height()) > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+
+    let image = DynamicImage::ImageRgb8(flatten(&image));
+    encode(&image, ImageFormat::Jpeg, THUMBNAIL_QUALITY).map(Some)
+}
+
+fn decode(data: &[u8]) -> AppResult<Decoded> {
+    let invalid = |e: image::ImageError| {
+        AppError::unsupported_image(format!("The image could not be decoded: {}", e))
+    };
+
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| AppError::io(format!("Failed to read image: {}", e)))?;
+    let mut decoder = reader.into_decoder().map_err(invalid)?;
+
+    // Damaged metadata is no reason to reject the picture itself
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let has_metadata = decoder.exif_metadata().ok().flatten().is_some()
+        || decoder.xmp_metadata().ok().flatten().is_some()
+        || decoder.iptc_metadata().ok().flatten().is_some();
+
+    let mut image = DynamicImage::from_decoder(decoder).map_err(invalid)?;
+    image.apply_orientation(orientation);
+
+    Ok(Decoded {
+        image,
+        has_metadata,
+        reoriented: orientation != Orientation::NoTransforms,
+    })
+}
+
+/// Encode without any metadata; none of the encoders used here write EXIF on their own
+fn encode(image: &DynamicImage, format: ImageFormat, jpeg_quality: u8) -> AppResult<Vec<u8>> {
+    let mut data = Vec::new();
+
+    let result = match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(flatten(image))
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, jpeg_quality)),
+        // The WebP encoder only takes 8-bit RGB(A)
+        ImageFormat::Webp if image.color().has_alpha() => {
+            DynamicImage::ImageRgba8(image.to_rgba8())
+                .write_with_encoder(WebPEncoder::new_lossless(&mut data))
+        }
+        ImageFormat::Webp => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(WebPEncoder::new_lossless(&mut data)),
+        _ => image.write_with_encoder(PngEncoder::new(&mut data)),
+    };
+    result.map_err(|e| AppError::io(format!("Failed to encode image: {}", e)))?;
+
+    Ok(data)
+}
+
+fn has_transparency(image: &DynamicImage) -> bool {
+    image.color().has_alpha() && image.to_rgba8().pixels().any(|pixel| pixel[3] < u8::MAX)
+}
+
+/// The image on a white background, without an alpha channel
+fn flatten(image: &DynamicImage) -> RgbImage {
+    if !image.color().has_alpha() {
+        return image.to_rgb8();
+    }
+
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend =
+            |channel: u8| ((channel as u32 * a as u32 + 255 * (255 - a as u32) + 127) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A JPEG of `size` square pixels of noise, which lossless WebP compresses badly
+    fn noisy_jpeg(size: u32) -> Vec<u8> {
+        let mut seed = 1u32;
+        let image = RgbImage::from_fn(size, size, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            Rgb([r, g, b])
+        });
+        encode(&DynamicImage::ImageRgb8(image), ImageFormat::Jpeg, 50).unwrap()
+    }
+
+    fn settings(format: &str, max_dimension: u32) -> ImageSettings {
+        ImageSettings {
+            max_dimension,
+            format: format.to_string(),
+            ..ImageSettings::default()
+        }
+    }
+
+    #[test]
+    fn webp_that_would_grow_keeps_the_original() {
+        let data = noisy_jpeg(64);
+        assert_eq!(
+            process(&data, ImageFormat::Jpeg, &settings("WEBP", 0)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn resized_photo_stays_jpeg_when_webp_would_be_larger() {
+        let data = noisy_jpeg(128);
+        let resized = process(&data, ImageFormat::Jpeg, &settings("WEBP", 64))
+            .unwrap()
+            .unwrap();
+        assert_eq!(ImageFormat::sniff(&resized), Some(ImageFormat::Jpeg));
+
+        let image = decode(&resized).unwrap().image;
+        assert_eq!((image.width(), image.height()), (64, 64));
+    }
+
+    #[test]
+    fn flat_image_is_written_as_webp() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(128, 128, Rgb([200, 30, 30])));
+        let data = encode(&image, ImageFormat::Png, 0).unwrap();
+        let converted = process(&data, ImageFormat::Png, &settings("WEBP", 64))
+            .unwrap()
+            .unwrap();
+        assert_eq!(ImageFormat::sniff(&converted), Some(ImageFormat::Webp));
+    }
+}
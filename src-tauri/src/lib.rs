@@ -1,12 +1,29 @@
+mod asset_protocol;
 mod commands;
 mod db;
+mod error;
+mod formats;
+mod grading;
+mod imaging;
+mod random;
+mod scheduling;
+mod similarity;
+mod statistics;
 
-use commands::subjects::*;
-use commands::topics::*;
-use commands::questions::*;
-use commands::quizzes::*;
+use commands::analysis::*;
+use commands::duplicates::*;
 use commands::exams::*;
 use commands::images::*;
+use commands::interchange::*;
+use commands::library::*;
+use commands::questions::*;
+use commands::quizzes::*;
+use commands::reviews::*;
+use commands::revisions::*;
+use commands::settings::*;
+use commands::subjects::*;
+use commands::tags::*;
+use commands::topics::*;
 
 #[allow(unused_imports)]
 use tauri::Manager;
@@ -22,6 +39,16 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .register_asynchronous_uri_scheme_protocol(
+            asset_protocol::ASSET_SCHEME,
+            |ctx, request, responder| {
+                let app_handle = ctx.app_handle().clone();
+                // Keep file reads off the thread that drives the webview
+                std::thread::spawn(move || {
+                    responder.respond(asset_protocol::handle(&app_handle, &request));
+                });
+            },
+        )
         .setup(|app| {
             // Initialize database
             let app_handle = app.handle().clone();
@@ -46,27 +73,62 @@ pub fn run() {
             delete_topic,
             get_questions,
             get_question,
+            search_questions,
             create_question,
             update_question,
             delete_question,
+            get_tags,
+            create_tag,
+            update_tag,
+            delete_tag,
+            get_question_revisions,
+            get_question_revision,
+            diff_question_revisions,
+            restore_question_revision,
+            find_duplicate_questions,
+            merge_questions,
             get_quizzes,
             get_quiz,
             create_quiz,
             update_quiz,
             delete_quiz,
             get_all_quiz_attempts,
-            save_quiz_attempt,
+            get_quiz_attempt_detail,
+            start_quiz_attempt,
+            complete_quiz_attempt,
             get_topic_performance,
             get_exams,
             get_exam,
             create_exam,
             update_exam,
             delete_exam,
+            assemble_exam,
             get_all_exam_attempts,
             save_exam_attempt,
+            get_exam_attempt_detail,
             get_subject_performance,
             copy_image_to_assets,
+            import_image_data,
             read_image_as_data_url,
+            collect_unused_images,
+            optimize_existing_images,
+            get_image_settings,
+            update_image_settings,
+            get_item_analysis,
+            get_test_statistics,
+            get_due_reviews,
+            record_review,
+            import_gift,
+            export_gift,
+            import_moodle_xml,
+            export_moodle_xml,
+            import_qti_package,
+            export_qti_package,
+            export_exam_qti_package,
+            export_anki,
+            import_csv,
+            export_library,
+            import_library,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,56 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seeds are kept below 2^53 so they survive the round trip through JavaScript numbers
+const MAX_SEED: u64 = (1 << 53) - 1;
+
+/// Small deterministic generator (SplitMix64) used to draw and shuffle questions.
+///
+/// The algorithm is implemented here rather than taken from a crate so that a seed
+/// stored with an attempt keeps producing the same order across dependency upgrades.
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: i64) -> Self {
+        SeededRng { state: seed as u64 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound` (bound must be non-zero)
+    pub fn below(&mut self, bound: usize) -> usize {
+        let bound = bound as u64;
+        // Reject the top partial range so every value is equally likely
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    /// Fisher–Yates shuffle
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+/// A new seed for an attempt that was not given one
+pub fn fresh_seed() -> i64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    (SeededRng::new(nanos as i64).next_u64() & MAX_SEED) as i64
+}
@@ -0,0 +1,221 @@
+use rusqlite::Connection;
+
+use crate::error::AppResult;
+use crate::grading::GradedResponse;
+
+/// Ease factor given to a question the first time it is reviewed
+const INITIAL_EASE: f64 = 2.5;
+/// SM-2 never lets the ease factor drop below this
+const MIN_EASE: f64 = 1.3;
+/// Grades below this count as a failed recall
+const PASSING_GRADE: i32 = 3;
+
+/// SM-2 state for one question
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleState {
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    pub repetitions: i32,
+    pub lapses: i32,
+}
+
+impl Default for ScheduleState {
+    fn default() -> Self {
+        ScheduleState {
+            ease_factor: INITIAL_EASE,
+            interval_days: 0,
+            repetitions: 0,
+            lapses: 0,
+        }
+    }
+}
+
+/// Apply one SM-2 review with a recall grade from 0 (blackout) to 5 (perfect)
+pub fn next_state(state: &ScheduleState, grade: i32) -> ScheduleState {
+    let grade = grade.clamp(0, 5);
+    let mut next = state.clone();
+
+    if grade >= PASSING_GRADE {
+        // As in the original SM-2, the interval grows by the ease factor from before this
+        // review; the factor updated below first applies to the next interval
+        next.interval_days = match state.repetitions {
+            0 => 1,
+            1 => 6,
+            _ => (state.interval_days as f64 * state.ease_factor).round() as i32,
+        };
+        next.repetitions += 1;
+    } else {
+        if state.repetitions > 0 {
+            next.lapses += 1;
+        }
+        next.repetitions = 0;
+        next.interval_days = 1;
+    }
+
+    let miss = (5 - grade) as f64;
+    next.ease_factor = (state.ease_factor + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+    next
+}
+
+/// Map a graded quiz or exam response onto an SM-2 grade
+pub fn grade_from_response(response: &GradedResponse) -> i32 {
+    if response.answer.is_none() {
+        return 0;
+    }
+    if response.is_correct {
+        return 4;
+    }
+    let fraction = if response.max_points > 0 {
+        response.points_earned / response.max_points as f64
+    } else {
+        0.0
+    };
+    if fraction >= 0.5 {
+        3
+    } else {
+        1
+    }
+}
+
+/// Record a review of `question_id` and move its due date
+pub fn record_grade(conn: &Connection, question_id: i64, grade: i32) -> AppResult<ScheduleState> {
+    let current = conn
+        .query_row(
+            "SELECT ease_factor, interval_days, repetitions, lapses
+             FROM review_schedule WHERE question_id = ?",
+            [question_id],
+            |row| {
+                Ok(ScheduleState {
+                    ease_factor: row.get(0)?,
+                    interval_days: row.get(1)?,
+                    repetitions: row.get(2)?,
+                    lapses: row.get(3)?,
+                })
+            },
+        )
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(ScheduleState::default()),
+            e => Err(e),
+        })?;
+
+    let next = next_state(&current, grade);
+
+    conn.execute(
+        "INSERT INTO review_schedule (question_id, ease_factor, interval_days, repetitions, lapses,
+         due_at, last_reviewed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now', '+' || ?3 || ' days'), datetime('now'))
+         ON CONFLICT(question_id) DO UPDATE SET
+            ease_factor = excluded.ease_factor,
+            interval_days = excluded.interval_days,
+            repetitions = excluded.repetitions,
+            lapses = excluded.lapses,
+            due_at = excluded.due_at,
+            last_reviewed_at = excluded.last_reviewed_at",
+        (
+            question_id,
+            next.ease_factor,
+            next.interval_days,
+            next.repetitions,
+            next.lapses,
+        ),
+    )?;
+
+    Ok(next)
+}
+
+/// Feed the answers of a finished quiz or exam into the review schedule
+pub fn record_responses(conn: &Connection, responses: &[GradedResponse]) -> AppResult<()> {
+    for response in responses {
+        record_grade(conn, response.question_id, grade_from_response(response))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(ease_factor: f64, interval_days: i32, repetitions: i32, lapses: i32) -> ScheduleState {
+        ScheduleState {
+            ease_factor,
+            interval_days,
+            repetitions,
+            lapses,
+        }
+    }
+
+    fn assert_state(actual: ScheduleState, expected: ScheduleState) {
+        assert!(
+            (actual.ease_factor - expected.ease_factor).abs() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        assert_eq!(
+            (actual.interval_days, actual.repetitions, actual.lapses),
+            (
+                expected.interval_days,
+                expected.repetitions,
+                expected.lapses
+            ),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn passing_reviews_grow_the_interval() {
+        let first = next_state(&ScheduleState::default(), 4);
+        assert_state(first.clone(), state(2.5, 1, 1, 0));
+        let second = next_state(&first, 5);
+        assert_state(second.clone(), state(2.6, 6, 2, 0));
+        // 6 days times the ease factor before the review, 2.6
+        assert_state(next_state(&second, 4), state(2.6, 16, 3, 0));
+    }
+
+    #[test]
+    fn interval_uses_the_ease_factor_from_before_the_review() {
+        // A grade of 3 lowers the ease to 2.36, but the interval is 6 * 2.5
+        assert_state(next_state(&state(2.5, 6, 2, 0), 3), state(2.36, 15, 3, 0));
+    }
+
+    #[test]
+    fn failed_recall_resets_the_repetitions() {
+        assert_state(next_state(&state(2.5, 15, 3, 0), 2), state(2.18, 1, 0, 1));
+        assert_state(next_state(&state(2.5, 15, 3, 1), 0), state(1.7, 1, 0, 2));
+        // Failing a question that was never recalled is not a lapse
+        assert_state(
+            next_state(&ScheduleState::default(), 1),
+            state(1.96, 1, 0, 0),
+        );
+    }
+
+    #[test]
+    fn ease_factor_never_drops_below_the_minimum() {
+        let mut current = ScheduleState::default();
+        for _ in 0..5 {
+            current = next_state(&current, 0);
+        }
+        assert_state(current.clone(), state(MIN_EASE, 1, 0, 0));
+
+        // A grade of 3 would take 0.14 off the minimum
+        assert_state(
+            next_state(&state(MIN_EASE, 6, 2, 0), 3),
+            state(MIN_EASE, 8, 3, 0),
+        );
+    }
+
+    #[test]
+    fn grades_outside_zero_to_five_are_clamped() {
+        assert_eq!(
+            next_state(&state(2.0, 6, 2, 0), 9),
+            next_state(&state(2.0, 6, 2, 0), 5)
+        );
+        assert_eq!(
+            next_state(&state(2.0, 6, 2, 0), -3),
+            next_state(&state(2.0, 6, 2, 0), 0)
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::commands::questions::QuestionWithDetails;
+use crate::random::SeededRng;
+
+/// Words per shingle of question text
+const SHINGLE_WORDS: usize = 3;
+/// MinHash values per question
+const SIGNATURE_LENGTH: usize = 128;
+/// Signature values per LSH band. Two questions become a candidate pair when any band
+/// matches exactly; with 32 bands of 4, pairs at 0.6 similarity are found about 99% of
+/// the time and unrelated pairs almost never need comparing.
+const BAND_ROWS: usize = 4;
+/// Fixed so signatures are comparable between runs
+const MINHASH_SEED: i64 = 0x5155_495A;
+
+/// The shingles of a question and their MinHash signature
+pub struct Fingerprint {
+    shingles: HashSet<u64>,
+    signature: Vec<u64>,
+}
+
+impl Fingerprint {
+    pub fn is_empty(&self) -> bool {
+        self.shingles.is_empty()
+    }
+}
+
+/// Fingerprint a question from its normalised text (as overlapping runs of words) and
+/// its answer set (one shingle per option, blank answer, match pair and order item).
+/// Option order, case, punctuation and blank markers make no difference.
+pub fn fingerprint(question: &QuestionWithDetails) -> Fingerprint {
+    let mut shingles = HashSet::new();
+
+    let text = question.question.question_text.replace("[blank]", " ");
+    let text = normalize(&text);
+    let words: Vec<&str> = text.split(' ').filter(|word| !word.is_empty()).collect();
+    if words.len() <= SHINGLE_WORDS {
+        if !words.is_empty() {
+            shingles.insert(hash(&format!("q:{}", words.join(" "))));
+        }
+    } else {
+        for window in words.windows(SHINGLE_WORDS) {
+            shingles.insert(hash(&format!("q:{}", window.join(" "))));
+        }
+    }
+
+    let mut answer = |kind: &str, text: &str| {
+        let text = normalize(text);
+        if !text.is_empty() {
+            shingles.insert(hash(&format!("{}:{}", kind, text)));
+        }
+    };
+    for option in &question.options {
+        answer("o", &option.option_text);
+    }
+    for blank in &question.blanks {
+        answer("b", &blank.correct_answer);
+    }
+    for pair in &question.matches {
+        answer("m", &format!("{} = {}", pair.left_item, pair.right_item));
+    }
+    for item in &question.order_items {
+        answer("i", &item.item_text);
+    }
+
+    let signature = minhash_seeds()
+        .iter()
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|shingle| mix(shingle ^ seed))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect();
+
+    Fingerprint {
+        shingles,
+        signature,
+    }
+}
+
+/// Share of shingles two fingerprints have in common (Jaccard similarity)
+pub fn jaccard(a: &Fingerprint, b: &Fingerprint) -> f64 {
+    let union = a.shingles.union(&b.shingles).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.shingles.intersection(&b.shingles).count() as f64 / union as f64
+}
+
+/// Index pairs `(a, b, similarity)` with `a < b` whose similarity is at least `threshold`.
+/// Candidates come from the MinHash bands; each one is checked against the exact shingles.
+pub fn similar_pairs(fingerprints: &[Fingerprint], threshold: f64) -> Vec<(usize, usize, f64)> {
+    let mut candidates = HashSet::new();
+
+    for band in 0..SIGNATURE_LENGTH / BAND_ROWS {
+        let rows = band * BAND_ROWS..(band + 1) * BAND_ROWS;
+        let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+        for (index, fingerprint) in fingerprints.iter().enumerate() {
+            if !fingerprint.is_empty() {
+                buckets
+                    .entry(&fingerprint.signature[rows.clone()])
+                    .or_default()
+                    .push(index);
+            }
+        }
+        for bucket in buckets.values() {
+            for (position, a) in bucket.iter().enumerate() {
+                for b in &bucket[position + 1..] {
+                    candidates.insert((*a, *b));
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize, f64)> = candidates
+        .into_iter()
+        .map(|(a, b)| (a, b, jaccard(&fingerprints[a], &fingerprints[b])))
+        .filter(|(_, _, similarity)| *similarity >= threshold)
+        .collect();
+    pairs.sort_by_key(|(a, b, _)| (*a, *b));
+    pairs
+}
+
+/// Group indexes linked by `pairs` into clusters of two or more, each in ascending order
+pub fn clusters(count: usize, pairs: &[(usize, usize, f64)]) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..count).collect();
+    fn root(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+
+    for (a, b, _) in pairs {
+        let (a, b) = (root(&mut parent, *a), root(&mut parent, *b));
+        if a != b {
+            parent[a.max(b)] = a.min(b);
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..count {
+        let group = root(&mut parent, index);
+        groups.entry(group).or_default().push(index);
+    }
+
+    let mut clusters: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    clusters.sort();
+    clusters
+}
+
+/// Lowercase words separated by single spaces; everything but letters and digits is dropped
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn minhash_seeds() -> Vec<u64> {
+    let mut rng = SeededRng::new(MINHASH_SEED);
+    (0..SIGNATURE_LENGTH).map(|_| rng.next_u64()).collect()
+}
+
+/// FNV-1a; implemented here so fingerprints do not depend on the std hasher
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+/// SplitMix64 finalizer, turning `shingle ^ seed` into an independent-looking hash
+fn mix(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,194 @@
+/// Arithmetic mean; `None` for no values
+pub fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Middle value, or the mean of the two middle values; `None` for no values
+pub fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let middle = sorted.len() / 2;
+    Some(if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    })
+}
+
+/// Population variance (dividing by n), the form classical test theory uses alongside
+/// item p-values
+pub fn variance(values: &[f64]) -> Option<f64> {
+    let mean = mean(values)?;
+    Some(
+        values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / values.len() as f64,
+    )
+}
+
+/// Population standard deviation
+pub fn std_dev(values: &[f64]) -> Option<f64> {
+    variance(values).map(f64::sqrt)
+}
+
+/// Cronbach's alpha of a score matrix with one row per test taker and one column per
+/// item. With items scored 0 or full marks it equals KR-20. `None` for fewer than two
+/// items or takers, or when the total scores do not vary.
+pub fn cronbach_alpha(scores: &[Vec<f64>]) -> Option<f64> {
+    let items = scores.first()?.len();
+    if items < 2 || scores.len() < 2 || scores.iter().any(|row| row.len() != items) {
+        return None;
+    }
+
+    let item_variance: f64 = (0..items)
+        .map(|item| {
+            let column: Vec<f64> = scores.iter().map(|row| row[item]).collect();
+            variance(&column).unwrap_or(0.0)
+        })
+        .sum();
+    let totals: Vec<f64> = scores.iter().map(|row| row.iter().sum()).collect();
+    let total_variance = variance(&totals)?;
+    if total_variance <= f64::EPSILON {
+        return None;
+    }
+
+    let k = items as f64;
+    Some(k / (k - 1.0) * (1.0 - item_variance / total_variance))
+}
+
+/// Pearson correlation of paired values. With 0/1 item scores on one side this is the
+/// point-biserial correlation. `None` when either side does not vary.
+pub fn correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    if xs.len() != ys.len() || xs.len() < 2 {
+        return None;
+    }
+    let (mean_x, mean_y) = (mean(xs)?, mean(ys)?);
+
+    let mut covariance = 0.0;
+    let mut variance_x = 0.0;
+    let mut variance_y = 0.0;
+    for (x, y) in xs.iter().zip(ys) {
+        let (dx, dy) = (x - mean_x, y - mean_y);
+        covariance += dx * dy;
+        variance_x += dx * dx;
+        variance_y += dy * dy;
+    }
+
+    // Rounding can leave a tiny spread where the values are all equal
+    if variance_x <= f64::EPSILON || variance_y <= f64::EPSILON {
+        return None;
+    }
+    Some((covariance / (variance_x * variance_y).sqrt()).clamp(-1.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Five takers on four items scored 0 or 1, a perfect Guttman pattern: totals 4 to 0
+    fn guttman() -> Vec<Vec<f64>> {
+        vec![
+            vec![1.0, 1.0, 1.0, 1.0],
+            vec![1.0, 1.0, 1.0, 0.0],
+            vec![1.0, 1.0, 0.0, 0.0],
+            vec![1.0, 0.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 0.0],
+        ]
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("a value");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn descriptive_statistics() {
+        let values = [4.0, 3.0, 2.0, 1.0, 0.0];
+        assert_close(mean(&values), 2.0);
+        assert_close(median(&values), 2.0);
+        assert_close(median(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+        // Population variance: 10 / 5
+        assert_close(variance(&values), 2.0);
+        assert_close(std_dev(&values), 2.0_f64.sqrt());
+
+        assert_eq!(mean(&[]), None);
+        assert_eq!(median(&[]), None);
+        assert_eq!(variance(&[]), None);
+    }
+
+    #[test]
+    fn point_biserial_correlation_of_an_item_with_the_totals() {
+        let matrix = guttman();
+        let totals: Vec<f64> = matrix.iter().map(|row| row.iter().sum()).collect();
+        let item: Vec<f64> = matrix.iter().map(|row| row[0]).collect();
+
+        // Covariance sum 2, squared deviations 0.8 and 10: 2 / sqrt(8)
+        assert_close(correlation(&item, &totals), 1.0 / 2.0_f64.sqrt());
+        assert_close(correlation(&[1.0, 2.0, 3.0], &[6.0, 4.0, 2.0]), -1.0);
+    }
+
+    #[test]
+    fn correlation_needs_spread_on_both_sides() {
+        assert_eq!(correlation(&[1.0, 1.0, 1.0], &[1.0, 2.0, 3.0]), None);
+        assert_eq!(correlation(&[1.0, 2.0, 3.0], &[0.1, 0.1, 0.1]), None);
+        assert_eq!(correlation(&[1.0], &[2.0]), None);
+        assert_eq!(correlation(&[1.0, 2.0], &[1.0, 2.0, 3.0]), None);
+    }
+
+    #[test]
+    fn kr20_of_items_scored_all_or_nothing() {
+        // Item variances p(1 - p): 0.16 + 0.24 + 0.24 + 0.16 = 0.8; total variance 2.
+        // 4 / 3 * (1 - 0.8 / 2) = 0.8
+        assert_close(cronbach_alpha(&guttman()), 0.8);
+    }
+
+    #[test]
+    fn alpha_of_items_with_partial_credit() {
+        let scores = vec![vec![2.0, 1.0], vec![1.0, 1.0], vec![0.0, 0.0]];
+        // Item variances 2/3 and 2/9, total variance 14/9: 2 * (1 - 8/14) = 6/7
+        assert_close(cronbach_alpha(&scores), 6.0 / 7.0);
+    }
+
+    #[test]
+    fn alpha_drops_to_zero_and_below_when_items_disagree() {
+        let scores = vec![
+            vec![1.0, 0.0, 1.0],
+            vec![0.0, 1.0, 1.0],
+            vec![1.0, 1.0, 0.0],
+            vec![0.0, 0.0, 0.0],
+        ];
+        // Item variances 3 * 0.25, totals 2, 2, 2, 0 with variance 0.75: 1.5 * (1 - 1) = 0
+        assert_close(cronbach_alpha(&scores), 0.0);
+        let scores = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        // Item variances 2/9 each add up to twice the total variance 2/9: 2 * (1 - 2) = -2
+        assert_close(cronbach_alpha(&scores), -2.0);
+    }
+
+    #[test]
+    fn alpha_is_undefined_without_spread_or_enough_data() {
+        // Every taker has the same total
+        assert_eq!(cronbach_alpha(&[vec![1.0, 0.0], vec![0.0, 1.0]]), None);
+        assert_eq!(cronbach_alpha(&[vec![1.0, 1.0], vec![1.0, 1.0]]), None);
+        // A single item
+        assert_eq!(cronbach_alpha(&[vec![1.0], vec![0.0], vec![1.0]]), None);
+        // A single taker
+        assert_eq!(cronbach_alpha(&[vec![1.0, 0.0, 1.0]]), None);
+        assert_eq!(cronbach_alpha(&[]), None);
+        // Rows of different lengths
+        assert_eq!(cronbach_alpha(&[vec![1.0, 0.0], vec![0.0]]), None);
+    }
+}
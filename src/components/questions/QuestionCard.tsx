@@ -2,7 +2,7 @@ import { useState, useEffect } from 'react';
 import { Edit2, Trash2, CheckCircle, Play } from 'lucide-react';
 import { Button } from '../ui/Button';
 import { QuestionWithDetails } from '../../hooks/useQuestions';
-import { getImageUrl } from '../../utils/images';
+import { getImageUrl, getThumbnailUrl } from '../../utils/images';
 
 interface QuestionCardProps {
   question: QuestionWithDetails;
@@ -35,8 +35,7 @@ export function QuestionCard({ question, onEdit, onDelete, onTest }: QuestionCar
   useEffect(() => {
     const loadImageUrl = async () => {
       if (question.questionImagePath) {
-        const url = await getImageUrl(question.questionImagePath);
-        setQuestionImageUrl(url);
+        setQuestionImageUrl(getThumbnailUrl(question.questionImagePath));
       } else {
         setQuestionImageUrl(undefined);
       }
@@ -82,6 +81,11 @@ export function QuestionCard({ question, onEdit, onDelete, onTest }: QuestionCar
               src={questionImageUrl}
               alt="Question preview"
               className="max-w-full max-h-32 rounded mb-3"
+              onError={async () => {
+                // No thumbnail yet: show the full image instead
+                const url = await getImageUrl(question.questionImagePath);
+                if (url && url !== questionImageUrl) setQuestionImageUrl(url);
+              }}
             />
           )}
 
@@ -2,6 +2,7 @@ import { useState, useEffect } from 'react';
 import { X, Image as ImageIcon } from 'lucide-react';
 import { open } from '@tauri-apps/plugin-dialog';
 import { invoke } from '@tauri-apps/api/core';
+import { getImageUrl } from '../../utils/images';
 
 interface ImageUploadProps {
   value?: string;
@@ -34,13 +35,8 @@ export function ImageUpload({ value, onChange, label, placeholder, compact = fal
           sourcePath: selected,
         });
 
-        // Get the data URL for preview
-        const dataUrl = await invoke<string>('read_image_as_data_url', {
-          relativePath,
-        });
-
         onChange(relativePath);
-        setPreviewUrl(dataUrl);
+        setPreviewUrl(await getImageUrl(relativePath));
         setImageError(false);
       }
     } catch (error) {
@@ -49,6 +45,52 @@ export function ImageUpload({ value, onChange, label, placeholder, compact = fal
     }
   };
 
+  // Store an image pasted from the clipboard or dropped from a browser
+  const importImageData = async (source: { bytes: number[] } | { dataUrl: string }, fileName?: string) => {
+    try {
+      const relativePath = await invoke<string>('import_image_data', { source, fileName });
+
+      onChange(relativePath);
+      setPreviewUrl(await getImageUrl(relativePath));
+      setImageError(false);
+    } catch (error) {
+      console.error('Error importing image:', error);
+      setImageError(true);
+    }
+  };
+
+  const importTransfer = async (transfer: DataTransfer) => {
+    const file = Array.from(transfer.files).find((f) => f.type.startsWith('image/'));
+    if (file) {
+      const bytes = Array.from(new Uint8Array(await file.arrayBuffer()));
+      await importImageData({ bytes }, file.name || undefined);
+      return true;
+    }
+
+    // Browsers drag inline images as a data: URL rather than a file
+    const text = transfer.getData('text/uri-list') || transfer.getData('text/plain');
+    const dataUrl = text.split(/\r?\n/).find((line) => line.trim().startsWith('data:'));
+    if (dataUrl) {
+      await importImageData({ dataUrl: dataUrl.trim() });
+      return true;
+    }
+    return false;
+  };
+
+  const handlePaste = (event: React.ClipboardEvent) => {
+    const hasImage = Array.from(event.clipboardData.files).some((f) => f.type.startsWith('image/'))
+      || event.clipboardData.getData('text/plain').trim().startsWith('data:image/');
+    if (hasImage) {
+      event.preventDefault();
+      importTransfer(event.clipboardData);
+    }
+  };
+
+  const handleDrop = (event: React.DragEvent) => {
+    event.preventDefault();
+    importTransfer(event.dataTransfer);
+  };
+
   const handleRemoveImage = () => {
     onChange(undefined);
     setPreviewUrl(undefined);
@@ -60,10 +102,7 @@ export function ImageUpload({ value, onChange, label, placeholder, compact = fal
     const updatePreview = async () => {
       if (value) {
         try {
-          const dataUrl = await invoke<string>('read_image_as_data_url', {
-            relativePath: value,
-          });
-          setPreviewUrl(dataUrl);
+          setPreviewUrl(await getImageUrl(value));
           setImageError(false);
         } catch (error) {
           console.error('Error loading image preview:', error);
@@ -81,7 +120,12 @@ export function ImageUpload({ value, onChange, label, placeholder, compact = fal
   // Compact mode - just a toggle button
   if (compact) {
     return (
-      <div className="flex items-center gap-2">
+      <div
+        className="flex items-center gap-2"
+        onPaste={handlePaste}
+        onDragOver={(event) => event.preventDefault()}
+        onDrop={handleDrop}
+      >
         {previewUrl && !imageError ? (
           <>
             <img
@@ -119,7 +163,12 @@ export function ImageUpload({ value, onChange, label, placeholder, compact = fal
 
   // Full mode - large drag-and-drop area
   return (
-    <div className="space-y-2">
+    <div
+      className="space-y-2"
+      onPaste={handlePaste}
+      onDragOver={(event) => event.preventDefault()}
+      onDrop={handleDrop}
+    >
       {label && (
         <label className="block text-sm font-medium text-text-primary">
           {label}
@@ -174,7 +223,7 @@ export function ImageUpload({ value, onChange, label, placeholder, compact = fal
         >
           <ImageIcon size={32} className="text-text-tertiary" />
           <span className="text-sm font-medium">
-            {placeholder || 'Click to select image'}
+            {placeholder || 'Click to select, paste or drop an image'}
           </span>
           <span className="text-xs text-text-tertiary">
             PNG, JPG, GIF, WebP, or SVG
@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { getErrorMessage } from '../utils/errors';
 
 export interface ExamTopicWithName {
   id: number;
@@ -73,7 +74,7 @@ export function useExams(subjectId: number | null) {
       setExams(results);
       setError(null);
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch exams');
+      setError(getErrorMessage(err, 'Failed to fetch exams'));
     } finally {
       setLoading(false);
     }
@@ -89,7 +90,7 @@ export function useExams(subjectId: number | null) {
       await fetchExams();
       return exam;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to create exam');
+      setError(getErrorMessage(err, 'Failed to create exam'));
       return null;
     }
   };
@@ -103,7 +104,7 @@ export function useExams(subjectId: number | null) {
       await fetchExams();
       return exam;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to update exam');
+      setError(getErrorMessage(err, 'Failed to update exam'));
       return null;
     }
   };
@@ -114,7 +115,7 @@ export function useExams(subjectId: number | null) {
       await fetchExams();
       return true;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to delete exam');
+      setError(getErrorMessage(err, 'Failed to delete exam'));
       return false;
     }
   };
@@ -124,7 +125,7 @@ export function useExams(subjectId: number | null) {
       const exam = await invoke<Exam>('get_exam', { id });
       return exam;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch exam');
+      setError(getErrorMessage(err, 'Failed to fetch exam'));
       return null;
     }
   };
@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { getErrorMessage } from '../utils/errors';
 
 // Backend types (matching Rust structs with camelCase)
 export interface Question {
@@ -162,7 +163,7 @@ export function useQuestions(topicId: number | null) {
       setQuestions(results);
       setError(null);
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch questions');
+      setError(getErrorMessage(err, 'Failed to fetch questions'));
     } finally {
       setLoading(false);
     }
@@ -182,9 +183,7 @@ export function useQuestions(topicId: number | null) {
       await fetchQuestions();
       return question;
     } catch (err) {
-      setError(
-        err instanceof Error ? err.message : 'Failed to create question'
-      );
+      setError(getErrorMessage(err, 'Failed to create question'));
       return null;
     }
   };
@@ -201,9 +200,7 @@ export function useQuestions(topicId: number | null) {
       await fetchQuestions();
       return question;
     } catch (err) {
-      setError(
-        err instanceof Error ? err.message : 'Failed to update question'
-      );
+      setError(getErrorMessage(err, 'Failed to update question'));
       return null;
     }
   };
@@ -214,9 +211,7 @@ export function useQuestions(topicId: number | null) {
       await fetchQuestions();
       return true;
     } catch (err) {
-      setError(
-        err instanceof Error ? err.message : 'Failed to delete question'
-      );
+      setError(getErrorMessage(err, 'Failed to delete question'));
       return false;
     }
   };
@@ -226,7 +221,7 @@ export function useQuestions(topicId: number | null) {
       const question = await invoke<QuestionWithDetails>('get_question', { id });
       return question;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch question');
+      setError(getErrorMessage(err, 'Failed to fetch question'));
       return null;
     }
   };
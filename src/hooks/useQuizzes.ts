@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { getErrorMessage } from '../utils/errors';
 
 export interface Quiz {
   id: number;
@@ -57,7 +58,7 @@ export function useQuizzes(topicId: number | null) {
       setQuizzes(results);
       setError(null);
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch quizzes');
+      setError(getErrorMessage(err, 'Failed to fetch quizzes'));
     } finally {
       setLoading(false);
     }
@@ -73,7 +74,7 @@ export function useQuizzes(topicId: number | null) {
       await fetchQuizzes();
       return quiz;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to create quiz');
+      setError(getErrorMessage(err, 'Failed to create quiz'));
       return null;
     }
   };
@@ -87,7 +88,7 @@ export function useQuizzes(topicId: number | null) {
       await fetchQuizzes();
       return quiz;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to update quiz');
+      setError(getErrorMessage(err, 'Failed to update quiz'));
       return null;
     }
   };
@@ -98,7 +99,7 @@ export function useQuizzes(topicId: number | null) {
       await fetchQuizzes();
       return true;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to delete quiz');
+      setError(getErrorMessage(err, 'Failed to delete quiz'));
       return false;
     }
   };
@@ -108,7 +109,7 @@ export function useQuizzes(topicId: number | null) {
       const quiz = await invoke<Quiz>('get_quiz', { id });
       return quiz;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch quiz');
+      setError(getErrorMessage(err, 'Failed to fetch quiz'));
       return null;
     }
   };
@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { getErrorMessage } from '../utils/errors';
 import { Subject, SubjectFormData } from '../types';
 
 export function useSubjects() {
@@ -14,7 +15,7 @@ export function useSubjects() {
       setSubjects(results);
       setError(null);
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch subjects');
+      setError(getErrorMessage(err, 'Failed to fetch subjects'));
       console.error('Error fetching subjects:', err);
     } finally {
       setLoading(false);
@@ -27,7 +28,7 @@ export function useSubjects() {
       await fetchSubjects(); // Refresh list
       return subject;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to create subject');
+      setError(getErrorMessage(err, 'Failed to create subject'));
       console.error('Error creating subject:', err);
       return null;
     }
@@ -39,7 +40,7 @@ export function useSubjects() {
       await fetchSubjects(); // Refresh list
       return true;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to update subject');
+      setError(getErrorMessage(err, 'Failed to update subject'));
       console.error('Error updating subject:', err);
       return false;
     }
@@ -51,7 +52,7 @@ export function useSubjects() {
       await fetchSubjects(); // Refresh list
       return true;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to delete subject');
+      setError(getErrorMessage(err, 'Failed to delete subject'));
       console.error('Error deleting subject:', err);
       return false;
     }
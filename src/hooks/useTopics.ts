@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { getErrorMessage } from '../utils/errors';
 import { Topic, TopicFormData } from '../types';
 
 export function useTopics(subjectId?: number) {
@@ -20,7 +21,7 @@ export function useTopics(subjectId?: number) {
       setTopics(results);
       setError(null);
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to fetch topics');
+      setError(getErrorMessage(err, 'Failed to fetch topics'));
       console.error('Error fetching topics:', err);
     } finally {
       setLoading(false);
@@ -33,7 +34,7 @@ export function useTopics(subjectId?: number) {
       await fetchTopics(); // Refresh list
       return topic;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to create topic');
+      setError(getErrorMessage(err, 'Failed to create topic'));
       console.error('Error creating topic:', err);
       return null;
     }
@@ -45,7 +46,7 @@ export function useTopics(subjectId?: number) {
       await fetchTopics(); // Refresh list
       return true;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to update topic');
+      setError(getErrorMessage(err, 'Failed to update topic'));
       console.error('Error updating topic:', err);
       return false;
     }
@@ -57,7 +58,7 @@ export function useTopics(subjectId?: number) {
       await fetchTopics(); // Refresh list
       return true;
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to delete topic');
+      setError(getErrorMessage(err, 'Failed to delete topic'));
       console.error('Error deleting topic:', err);
       return false;
     }
@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { getErrorMessage } from '../utils/errors';
 import { MainLayout } from '../components/layout/MainLayout';
 import { TrendingUp, TrendingDown, Minus, Clock, Award, Target, BookOpen } from 'lucide-react';
 import { LineChart, Line, XAxis, YAxis, CartesianGrid, Tooltip, ResponsiveContainer, BarChart, Bar } from 'recharts';
@@ -77,7 +78,7 @@ export default function AnalyticsPage() {
       setError(null);
     } catch (err) {
       console.error('Failed to load analytics:', err);
-      setError(err instanceof Error ? err.message : 'Failed to load analytics data');
+      setError(getErrorMessage(err, 'Failed to load analytics data'));
     } finally {
       setLoading(false);
     }
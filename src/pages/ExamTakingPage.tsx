@@ -11,6 +11,13 @@ import { OrderingRenderer } from '../components/questions/renderers/OrderingRend
 import { MatchingRenderer } from '../components/questions/renderers/MatchingRenderer';
 import { getImageUrl } from '../utils/images';
 
+interface AssembledExam {
+  examId: number;
+  attemptId: number;
+  seed: number;
+  questions: QuestionWithDetails[];
+}
+
 interface ExamAnswer {
   questionId: number;
   answer: string | string[] | number[] | Record<number, number>;
@@ -30,6 +37,7 @@ export default function ExamTakingPage() {
   const [timeRemaining, setTimeRemaining] = useState<number | null>(null);
   const [loading, setLoading] = useState(true);
   const [startTime] = useState<number>(Date.now());
+  const [attemptId, setAttemptId] = useState<number | null>(null);
   const [questionImageUrl, setQuestionImageUrl] = useState<string | undefined>();
   const [optionImageUrls, setOptionImageUrls] = useState<Map<number, string>>(new Map());
 
@@ -95,34 +103,10 @@ export default function ExamTakingPage() {
       const examData = await invoke<Exam>('get_exam', { id: examId });
       setExam(examData);
 
-      // For each topic in the exam, fetch questions
-      let allQuestions: QuestionWithDetails[] = [];
-
-      for (const topic of examData.topics) {
-        const topicQuestions = await invoke<QuestionWithDetails[]>('get_questions', {
-          topicId: topic.topicId,
-        });
-
-        // Randomly select the specified number of questions from this topic
-        const shuffled = [...topicQuestions].sort(() => Math.random() - 0.5);
-        const selected = shuffled.slice(0, topic.questionCount);
-        allQuestions = [...allQuestions, ...selected];
-      }
-
-      // Shuffle all questions if enabled
-      if (examData.shuffleQuestions) {
-        allQuestions = allQuestions.sort(() => Math.random() - 0.5);
-      }
-
-      // Shuffle options within each question if enabled
-      if (examData.shuffleOptions) {
-        allQuestions = allQuestions.map((q) => ({
-          ...q,
-          options: [...q.options].sort(() => Math.random() - 0.5),
-        }));
-      }
-
-      setQuestions(allQuestions);
+      // The backend draws each topic's quota, applies the shuffle settings and records the attempt
+      const assembled = await invoke<AssembledExam>('assemble_exam', { examId });
+      setAttemptId(assembled.attemptId);
+      setQuestions(assembled.questions);
 
       // Set timer if time limit exists
       if (examData.timeLimitMinutes) {
@@ -148,7 +132,7 @@ export default function ExamTakingPage() {
     navigate(
       `/subjects/${subjectId}/exams/${examId}/results`,
       {
-        state: { answers: answersArray, questions, exam, startTime },
+        state: { answers: answersArray, questions, exam, startTime, attemptId },
       }
     );
   };
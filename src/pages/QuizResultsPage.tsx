@@ -6,6 +6,7 @@ import { MainLayout } from '../components/layout/MainLayout';
 import { Button } from '../components/ui/Button';
 import { Quiz } from '../hooks/useQuizzes';
 import { QuestionWithDetails } from '../hooks/useQuestions';
+import { AttemptResult } from '../types/attempt';
 
 interface QuizAnswer {
   questionId: number;
@@ -17,6 +18,7 @@ interface LocationState {
   questions: QuestionWithDetails[];
   quiz: Quiz;
   startTime: number;
+  attemptId: number | null;
 }
 
 interface QuestionResult {
@@ -71,19 +73,23 @@ export default function QuizResultsPage() {
     setMaxScore(max);
     setPercentage(Math.round(pct));
 
-    // Save quiz attempt (only once using ref to avoid re-renders)
-    if (!attemptSavedRef.current) {
+    // Complete the attempt the backend started (only once using ref to avoid re-renders)
+    if (!attemptSavedRef.current && state.attemptId !== null) {
       attemptSavedRef.current = true;
-      const timeTaken = state.startTime ? Math.floor((Date.now() - state.startTime) / 1000) : 0;
-      invoke('save_quiz_attempt', {
-        data: {
-          quizId: state.quiz.id,
-          score: earned,
-          maxScore: max,
-          percentage: pct,
-          timeTakenSeconds: timeTaken,
-        },
-      }).catch((err) => console.error('Failed to save quiz attempt:', err));
+      const responses = questions.map((q) => ({
+        questionId: q.id,
+        answer: answersMap.get(q.id)?.answer ?? null,
+      }));
+      invoke<AttemptResult>('complete_quiz_attempt', {
+        data: { attemptId: state.attemptId, responses },
+      })
+        .then((saved) => {
+          // The backend grades the attempt; show the score it stored
+          setTotalScore(saved.score);
+          setMaxScore(saved.maxScore);
+          setPercentage(Math.round(saved.percentage));
+        })
+        .catch((err) => console.error('Failed to save quiz attempt:', err));
     }
   }, [state]);
 
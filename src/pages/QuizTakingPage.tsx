@@ -11,6 +11,13 @@ import { OrderingRenderer } from '../components/questions/renderers/OrderingRend
 import { MatchingRenderer } from '../components/questions/renderers/MatchingRenderer';
 import { getImageUrl } from '../utils/images';
 
+interface StartedQuizAttempt {
+  attemptId: number;
+  seed: number;
+  startedAt: string;
+  questions: QuestionWithDetails[];
+}
+
 interface QuizAnswer {
   questionId: number;
   answer: string | string[] | number[] | Record<number, number>;
@@ -30,6 +37,7 @@ export default function QuizTakingPage() {
   const [answers, setAnswers] = useState<Map<number, QuizAnswer>>(new Map());
   const [timeRemaining, setTimeRemaining] = useState<number | null>(null);
   const [loading, setLoading] = useState(true);
+  const [attemptId, setAttemptId] = useState<number | null>(null);
   const [startTime] = useState<number>(Date.now());
   const [questionImageUrl, setQuestionImageUrl] = useState<string | undefined>();
   const [optionImageUrls, setOptionImageUrls] = useState<Map<number, string>>(new Map());
@@ -96,31 +104,12 @@ export default function QuizTakingPage() {
       const quizData = await invoke<Quiz>('get_quiz', { id: quizId });
       setQuiz(quizData);
 
-      // Load all questions from topic
-      const allQuestions = await invoke<QuestionWithDetails[]>('get_questions', {
-        topicId: quizData.topicId,
+      // The backend draws and orders the questions and records the attempt
+      const started = await invoke<StartedQuizAttempt>('start_quiz_attempt', {
+        quizId,
       });
-
-      // Select and shuffle questions
-      let selectedQuestions = [...allQuestions];
-
-      // Shuffle if enabled
-      if (quizData.shuffleQuestions) {
-        selectedQuestions = shuffleArray(selectedQuestions);
-      }
-
-      // Take only the required number
-      selectedQuestions = selectedQuestions.slice(0, quizData.questionCount);
-
-      // Shuffle options within each question if enabled
-      if (quizData.shuffleOptions) {
-        selectedQuestions = selectedQuestions.map((q) => ({
-          ...q,
-          options: shuffleArray([...q.options]),
-        }));
-      }
-
-      setQuestions(selectedQuestions);
+      setAttemptId(started.attemptId);
+      setQuestions(started.questions);
 
       // Set timer if time limit exists
       if (quizData.timeLimitMinutes) {
@@ -134,15 +123,6 @@ export default function QuizTakingPage() {
     }
   };
 
-  const shuffleArray = <T,>(array: T[]): T[] => {
-    const shuffled = [...array];
-    for (let i = shuffled.length - 1; i > 0; i--) {
-      const j = Math.floor(Math.random() * (i + 1));
-      [shuffled[i], shuffled[j]] = [shuffled[j], shuffled[i]];
-    }
-    return shuffled;
-  };
-
   const handleAnswer = (questionId: number, answer: string | string[] | number[] | Record<number, number>) => {
     const newAnswers = new Map(answers);
     newAnswers.set(questionId, { questionId, answer });
@@ -155,7 +135,7 @@ export default function QuizTakingPage() {
     navigate(
       `/subjects/${subjectId}/topics/${topicId}/quizzes/${quizId}/results`,
       {
-        state: { answers: answersArray, questions, quiz, startTime },
+        state: { answers: answersArray, questions, quiz, startTime, attemptId },
       }
     );
   };
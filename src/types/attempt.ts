@@ -30,6 +30,15 @@ export interface ResponseResult {
   explanation?: string;
 }
 
+export interface GradedResponse {
+  questionId: number;
+  answer: any; // JSON - structure depends on question type
+  isCorrect: boolean;
+  pointsEarned: number;
+  maxPoints: number;
+  timeSpentSeconds?: number;
+}
+
 export interface AttemptResult {
   attemptId: number;
   score: number;
@@ -37,6 +46,7 @@ export interface AttemptResult {
   percentage: number;
   timeTakenSeconds: number;
   passed: boolean;
+  responses: GradedResponse[];
 }
 
 // Analytics types
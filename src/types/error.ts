@@ -0,0 +1,20 @@
+export type AppErrorKind =
+  | 'NotFound'
+  | 'Validation'
+  | 'Conflict'
+  | 'Io'
+  | 'InvalidAssetPath'
+  | 'UnsupportedImage'
+  | 'FileTooLarge'
+  | 'Database';
+
+/** Error rejected by every backend command */
+export interface AppError {
+  kind: AppErrorKind;
+  message: string;
+  /**
+   * `{ fields }` for Validation, `{ size, limit }` in bytes for FileTooLarge, `{ code }`
+   * for some Database errors, otherwise null
+   */
+  details: { fields?: string[]; size?: number; limit?: number; code?: string } | null;
+}
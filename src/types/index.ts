@@ -3,3 +3,4 @@ export * from './topic';
 export * from './question';
 export * from './quiz';
 export * from './attempt';
+export * from './error';
@@ -0,0 +1,22 @@
+import { AppError } from '../types';
+
+export function isAppError(error: unknown): error is AppError {
+  return (
+    typeof error === 'object' &&
+    error !== null &&
+    'kind' in error &&
+    'message' in error
+  );
+}
+
+/**
+ * Get a message to show for a failed command
+ * @param error - The value the command rejected with
+ * @param fallback - Shown when the error carries no message
+ */
+export function getErrorMessage(error: unknown, fallback: string): string {
+  if (isAppError(error)) return error.message;
+  if (error instanceof Error) return error.message;
+  if (typeof error === 'string') return error;
+  return fallback;
+}
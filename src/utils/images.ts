@@ -1,20 +1,27 @@
-import { invoke } from '@tauri-apps/api/core';
+import { convertFileSrc } from '@tauri-apps/api/core';
+
+/** URI scheme the backend serves the assets directory on (see `asset_protocol.rs`) */
+const ASSET_SCHEME = 'quizforge-asset';
 
 /**
- * Convert a relative image path to a displayable data URL
- * @param relativePath - The relative path stored in the database (e.g., "assets/images/123_image.png")
- * @returns A data URL that can be used in img src attributes
+ * Convert a relative image path to a URL the webview can load
+ * @param relativePath - The relative path stored in the database (e.g., "assets/images/<hash>.png")
+ * @returns A quizforge-asset URL that can be used in img src attributes
  */
 export async function getImageUrl(relativePath: string | undefined): Promise<string | undefined> {
   if (!relativePath) return undefined;
 
-  try {
-    const dataUrl = await invoke<string>('read_image_as_data_url', {
-      relativePath,
-    });
-    return dataUrl;
-  } catch (error) {
-    console.error('Error getting image URL:', error);
-    return undefined;
-  }
+  return convertFileSrc(relativePath, ASSET_SCHEME);
+}
+
+/**
+ * URL of the small thumbnail made for list views (see `thumbnail_path` in `images.rs`).
+ * Images imported before thumbnails existed have none until `optimize_existing_images`
+ * runs, so callers should fall back to `getImageUrl` when it fails to load.
+ * @param relativePath - The relative path of the full image
+ */
+export function getThumbnailUrl(relativePath: string): string {
+  const fileName = relativePath.split('/').pop() ?? relativePath;
+  const stem = fileName.includes('.') ? fileName.slice(0, fileName.lastIndexOf('.')) : fileName;
+  return convertFileSrc(`assets/thumbnails/${stem}.jpg`, ASSET_SCHEME);
 }